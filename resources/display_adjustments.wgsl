@@ -0,0 +1,136 @@
+// Draws an image texture into the paint callback rect applying the display adjustments.
+// Textures hold gamma encoded values, same as egui expects.
+
+struct Params {
+    // xy: top left uv, zw: top right uv
+    uv_top: vec4<f32>,
+    // xy: bottom left uv, zw: bottom right uv
+    uv_bottom: vec4<f32>,
+    // x: exposure (EV), y: gamma, z: contrast, w: opacity
+    values: vec4<f32>,
+    // rgb: focus peaking color, w: focus peaking threshold
+    peaking: vec4<f32>,
+    // x: invert, y: grayscale, z: channel (0 all, 1 red, 2 green, 3 blue), w: linear framebuffer
+    flags: vec4<u32>,
+    // x: focus peaking
+    peaking_flags: vec4<u32>,
+};
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var image_texture: texture_2d<f32>;
+@group(0) @binding(2) var image_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Drawn as a triangle strip: top left, bottom left, top right, bottom right
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    switch vertex_index {
+        case 0u: {
+            out.position = vec4<f32>(-1.0, 1.0, 0.0, 1.0);
+            out.uv = params.uv_top.xy;
+        }
+        case 1u: {
+            out.position = vec4<f32>(-1.0, -1.0, 0.0, 1.0);
+            out.uv = params.uv_bottom.xy;
+        }
+        case 2u: {
+            out.position = vec4<f32>(1.0, 1.0, 0.0, 1.0);
+            out.uv = params.uv_top.zw;
+        }
+        default: {
+            out.position = vec4<f32>(1.0, -1.0, 0.0, 1.0);
+            out.uv = params.uv_bottom.zw;
+        }
+    }
+
+    return out;
+}
+
+fn linear_from_gamma(srgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = srgb < vec3<f32>(0.04045);
+    let lower = srgb / vec3<f32>(12.92);
+    let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+    return select(higher, lower, cutoff);
+}
+
+fn gamma_from_linear(rgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = rgb < vec3<f32>(0.0031308);
+    let lower = rgb * vec3<f32>(12.92);
+    let higher = vec3<f32>(1.055) * pow(rgb, vec3<f32>(1.0 / 2.4)) - vec3<f32>(0.055);
+    return select(higher, lower, cutoff);
+}
+
+fn luma_at(uv: vec2<f32>, offset: vec2<f32>) -> f32 {
+    let texel = 1.0 / vec2<f32>(textureDimensions(image_texture));
+    let color = textureSampleLevel(image_texture, image_sampler, uv + offset * texel, 0.0).rgb;
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Sobel magnitude of the luminance around uv, scaled so that a hard edge between black and white is 1
+fn edge_strength(uv: vec2<f32>) -> f32 {
+    let tl = luma_at(uv, vec2<f32>(-1.0, -1.0));
+    let t = luma_at(uv, vec2<f32>(0.0, -1.0));
+    let tr = luma_at(uv, vec2<f32>(1.0, -1.0));
+    let l = luma_at(uv, vec2<f32>(-1.0, 0.0));
+    let r = luma_at(uv, vec2<f32>(1.0, 0.0));
+    let bl = luma_at(uv, vec2<f32>(-1.0, 1.0));
+    let b = luma_at(uv, vec2<f32>(0.0, 1.0));
+    let br = luma_at(uv, vec2<f32>(1.0, 1.0));
+
+    let gx = (tr + 2.0 * r + br) - (tl + 2.0 * l + bl);
+    let gy = (bl + 2.0 * b + br) - (tl + 2.0 * t + tr);
+    return length(vec2<f32>(gx, gy)) / 4.0;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(image_texture, image_sampler, in.uv).rgb;
+
+    // Exposure is applied in linear light so that it behaves like a stop of light
+    let exposed = linear_from_gamma(color) * exp2(params.values.x);
+    color = gamma_from_linear(clamp(exposed, vec3<f32>(0.0), vec3<f32>(1.0)));
+
+    color = pow(color, vec3<f32>(1.0 / params.values.y));
+    color = clamp((color - vec3<f32>(0.5)) * params.values.z + vec3<f32>(0.5), vec3<f32>(0.0), vec3<f32>(1.0));
+
+    switch params.flags.z {
+        case 1u: {
+            color = vec3<f32>(color.r);
+        }
+        case 2u: {
+            color = vec3<f32>(color.g);
+        }
+        case 3u: {
+            color = vec3<f32>(color.b);
+        }
+        default: {
+            if params.flags.y != 0u {
+                color = vec3<f32>(dot(color, vec3<f32>(0.2126, 0.7152, 0.0722)));
+            }
+        }
+    }
+
+    if params.flags.x != 0u {
+        color = vec3<f32>(1.0) - color;
+    }
+
+    if params.peaking_flags.x != 0u {
+        let threshold = params.peaking.w;
+        let strength = smoothstep(threshold, threshold * 1.25, edge_strength(in.uv));
+        color = mix(color, params.peaking.rgb, strength);
+    }
+
+    if params.flags.w != 0u {
+        color = linear_from_gamma(color);
+    }
+
+    // Premultiplied alpha, the opacity fades the image in and out during slideshow transitions
+    let opacity = params.values.w;
+    return vec4<f32>(color * opacity, opacity);
+}
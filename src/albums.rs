@@ -0,0 +1,358 @@
+use crate::db::{AlbumImage, DbRepository};
+use eframe::egui::{self, Ui};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+///Album gathering images across directories until cleared or saved as an album
+pub const QUICK_COLLECTION: &str = "Quick Collection";
+///Bytes read from the start and the end of a file for its content hash
+const HASH_SAMPLE: u64 = 64 * 1024;
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |hash, b| (hash ^ *b as u64).wrapping_mul(FNV_PRIME))
+}
+
+///Hash of the file size and its first and last 64KiB, enough to tell photos apart while staying
+///quick for large raw files. Used to find album images after they were moved.
+pub fn content_hash(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+
+    let mut head = vec![];
+    file.by_ref().take(HASH_SAMPLE).read_to_end(&mut head)?;
+    let mut hash = fnv1a(fnv1a(FNV_OFFSET, &len.to_le_bytes()), &head);
+
+    if len > HASH_SAMPLE {
+        let mut tail = vec![];
+        file.seek(SeekFrom::Start(len - HASH_SAMPLE))?;
+        file.take(HASH_SAMPLE).read_to_end(&mut tail)?;
+        hash = fnv1a(hash, &tail);
+    }
+
+    Ok(format!("{hash:016x}"))
+}
+
+///Named, ordered lists of images stored in the database. Shortcuts add to and remove from the
+///target album, picked in the side panel.
+pub struct Albums {
+    albums: Vec<(String, u32)>,
+    target: Option<String>,
+    new_name: String,
+    db_repo: DbRepository,
+}
+
+impl Albums {
+    pub fn new(db_repo: &DbRepository) -> Albums {
+        let mut albums = Albums {
+            albums: vec![],
+            target: None,
+            new_name: String::new(),
+            db_repo: db_repo.clone(),
+        };
+
+        albums.load();
+        albums
+    }
+
+    fn load(&mut self) {
+        match self.db_repo.get_albums() {
+            Ok(albums) => self.albums = albums,
+            Err(e) => tracing::error!("Failure fetching albums -> {e}"),
+        }
+    }
+
+    fn len(&self, album: &str) -> u32 {
+        self.albums
+            .iter()
+            .find(|(name, _)| name == album)
+            .map(|(_, len)| *len)
+            .unwrap_or_default()
+    }
+
+    pub fn add(&mut self, album: &str, paths: &[PathBuf]) {
+        let images: Vec<AlbumImage> = paths
+            .iter()
+            .map(|path| {
+                let hash = content_hash(path)
+                    .inspect_err(|e| tracing::error!("Failure hashing {path:?} -> {e}"))
+                    .ok();
+                (path.to_string_lossy().to_string(), hash)
+            })
+            .collect();
+
+        match self.db_repo.add_to_album(album, &images) {
+            Ok(_) => tracing::info!("Added {} images to {album}", images.len()),
+            Err(e) => tracing::error!("Failure adding images to {album} -> {e}"),
+        }
+
+        self.load();
+    }
+
+    pub fn remove(&mut self, album: &str, paths: &[PathBuf]) {
+        let paths: Vec<String> = paths
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+
+        match self.db_repo.remove_from_album(album, &paths) {
+            Ok(_) => tracing::info!("Removed {} images from {album}", paths.len()),
+            Err(e) => tracing::error!("Failure removing images from {album} -> {e}"),
+        }
+
+        self.load();
+    }
+
+    pub fn add_to_target(&mut self, paths: &[PathBuf]) {
+        match self.target.clone() {
+            Some(album) if !paths.is_empty() => self.add(&album, paths),
+            Some(_) => {}
+            None => tracing::info!("No target album, pick one in the side panel"),
+        }
+    }
+
+    pub fn remove_from_target(&mut self, paths: &[PathBuf]) {
+        match self.target.clone() {
+            Some(album) if !paths.is_empty() => self.remove(&album, paths),
+            Some(_) => {}
+            None => tracing::info!("No target album, pick one in the side panel"),
+        }
+    }
+
+    ///Removes the images when all of them are already in the quick collection, adds them otherwise
+    pub fn toggle_quick_collection(&mut self, paths: &[PathBuf]) {
+        if paths.is_empty() {
+            return;
+        }
+
+        let collected: Vec<PathBuf> = match self.db_repo.get_album_images(QUICK_COLLECTION) {
+            Ok(images) => images.into_iter().map(|(p, _)| PathBuf::from(p)).collect(),
+            Err(e) => {
+                tracing::error!("Failure fetching the quick collection -> {e}");
+                return;
+            }
+        };
+
+        if paths.iter().all(|p| collected.contains(p)) {
+            self.remove(QUICK_COLLECTION, paths);
+        } else {
+            self.add(QUICK_COLLECTION, paths);
+        }
+    }
+
+    ///Images of the album in order. Missing files are looked up by name in the library and
+    ///relinked when their content hash matches, the rest are skipped.
+    pub fn open(&mut self, album: &str) -> Vec<PathBuf> {
+        let images = match self.db_repo.get_album_images(album) {
+            Ok(images) => images,
+            Err(e) => {
+                tracing::error!("Failure fetching images of {album} -> {e}");
+                return vec![];
+            }
+        };
+
+        let mut paths = vec![];
+        let mut missing = 0;
+        for (path, hash) in images {
+            if Path::new(&path).exists() {
+                paths.push(PathBuf::from(path));
+                continue;
+            }
+
+            match hash.and_then(|hash| self.find_moved(&path, &hash)) {
+                Some(new_path) => {
+                    tracing::info!("Relinking {path} to {new_path} in {album}");
+                    if let Err(e) = self.db_repo.relink_album_image(album, &path, &new_path) {
+                        tracing::error!("Failure relinking {path} in {album} -> {e}");
+                    }
+                    paths.push(PathBuf::from(new_path));
+                }
+                None => missing += 1,
+            }
+        }
+
+        if missing > 0 {
+            tracing::info!("{missing} images of {album} could not be found");
+        }
+
+        paths
+    }
+
+    fn find_moved(&mut self, path: &str, hash: &str) -> Option<String> {
+        let name = Path::new(path).file_name()?.to_string_lossy().to_string();
+        let candidates = match self.db_repo.get_paths_by_file_name(&name) {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                tracing::error!("Failure fetching library images named {name} -> {e}");
+                return None;
+            }
+        };
+
+        candidates.into_iter().find(|candidate| {
+            candidate != path
+                && content_hash(Path::new(candidate)).is_ok_and(|candidate| candidate == hash)
+        })
+    }
+
+    fn create(&mut self, name: &str) {
+        if let Err(e) = self.db_repo.create_album(name) {
+            tracing::error!("Failure creating album {name} -> {e}");
+            return;
+        }
+
+        self.target = Some(name.to_string());
+        self.load();
+    }
+
+    ///Turns the quick collection into a regular album, leaving it empty
+    fn save_quick_collection(&mut self, name: &str) {
+        if let Err(e) = self.db_repo.rename_album(QUICK_COLLECTION, name) {
+            tracing::error!("Failure saving the quick collection as {name} -> {e}");
+            return;
+        }
+
+        self.target = Some(name.to_string());
+        self.load();
+    }
+
+    fn delete(&mut self, album: &str) {
+        if let Err(e) = self.db_repo.delete_album(album) {
+            tracing::error!("Failure deleting album {album} -> {e}");
+            return;
+        }
+
+        if self.target.as_deref() == Some(album) && album != QUICK_COLLECTION {
+            self.target = None;
+        }
+        self.load();
+    }
+
+    ///Album list for the side panel, returns the images of the clicked album
+    pub fn ui(&mut self, ui: &mut Ui) -> Option<Vec<PathBuf>> {
+        ui.label(egui::RichText::new("Albums").heading());
+        ui.add_space(10.);
+
+        let mut opened: Option<String> = None;
+        let mut target: Option<String> = None;
+        let mut deleted: Option<String> = None;
+        let name = self.new_name.trim().to_string();
+        let name_taken =
+            name == QUICK_COLLECTION || self.albums.iter().any(|(album, _)| *album == name);
+
+        let quick_len = self.len(QUICK_COLLECTION);
+        let resp = ui.selectable_label(
+            self.target.as_deref() == Some(QUICK_COLLECTION),
+            format!("{QUICK_COLLECTION} ({quick_len})"),
+        );
+        if resp.clicked() {
+            opened = Some(QUICK_COLLECTION.to_string());
+        }
+        resp.context_menu(|ui| {
+            if ui.button("Use for shortcuts").clicked() {
+                target = Some(QUICK_COLLECTION.to_string());
+            }
+
+            if ui
+                .add_enabled(
+                    !name.is_empty() && !name_taken,
+                    egui::Button::new("Save as album"),
+                )
+                .on_disabled_hover_text("Type a new album name below")
+                .clicked()
+            {
+                self.save_quick_collection(&name);
+                self.new_name.clear();
+            }
+
+            if ui.button("Clear").clicked() {
+                deleted = Some(QUICK_COLLECTION.to_string());
+            }
+        });
+
+        for (album, len) in &self.albums {
+            if album == QUICK_COLLECTION {
+                continue;
+            }
+
+            let resp = ui.selectable_label(
+                self.target.as_ref() == Some(album),
+                format!("{album} ({len})"),
+            );
+            if resp.clicked() {
+                opened = Some(album.clone());
+            }
+            resp.context_menu(|ui| {
+                if ui.button("Use for shortcuts").clicked() {
+                    target = Some(album.clone());
+                }
+
+                if ui.button("Delete").clicked() {
+                    deleted = Some(album.clone());
+                }
+            });
+        }
+
+        ui.add_space(5.);
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_name)
+                    .hint_text("New album")
+                    .desired_width(120.),
+            );
+
+            if ui
+                .add_enabled(!name.is_empty() && !name_taken, egui::Button::new("Create"))
+                .clicked()
+            {
+                self.create(&name);
+                self.new_name.clear();
+            }
+        });
+
+        if target.is_some() {
+            self.target = target;
+        }
+
+        if let Some(album) = deleted {
+            self.delete(&album);
+        }
+
+        let album = opened?;
+        let paths = self.open(&album);
+        if paths.is_empty() {
+            tracing::info!("No images in {album}");
+            return None;
+        }
+
+        Some(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn content_hash_follows_the_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &[u8]| {
+            let path = dir.path().join(name);
+            File::create(&path).unwrap().write_all(content).unwrap();
+            content_hash(&path).unwrap()
+        };
+
+        let large: Vec<u8> = (0..3 * HASH_SAMPLE).map(|i| (i % 251) as u8).collect();
+        let mut changed_tail = large.clone();
+        *changed_tail.last_mut().unwrap() ^= 1;
+
+        assert_eq!(write("a.jpg", b"photo"), write("b.jpg", b"photo"));
+        assert_ne!(write("a.jpg", b"photo"), write("b.jpg", b"photos"));
+        assert_eq!(write("a.raw", &large), write("b.raw", &large));
+        assert_ne!(write("a.raw", &large), write("b.raw", &changed_tail));
+    }
+}
@@ -1,17 +1,26 @@
 use crate::db::DbRepository;
-use crate::filters::Filters;
+use crate::filters::{FilterQuery, Filters};
 use crate::image_store::ImageStore;
 use crate::worker::Worker;
 use crate::{
     VALID_EXTENSIONS,
+    albums::Albums,
     callback::Callback,
-    config::{Config, GeneralConfig},
+    config::{Config, ContactSheetConfig, ExportConfig, GeneralConfig, WebGalleryConfig},
+    contact_sheet::ContactSheetJob,
     crawler,
+    display_adjustments::AdjustmentsPipeline,
+    export::ExportJob,
     grid_view::GridView,
     image_view::ImageView,
     navigator,
     perf_metrics::PerfMetrics,
+    photo_frame::Playlist,
+    rating::{ColorLabel, MAX_RATING, REJECTED, Ratings},
+    tags::Tags,
     tree, utils,
+    web_gallery::WebGalleryJob,
+    xmp::XmpWriter,
 };
 use eframe::Frame;
 use eframe::egui::{self, KeyboardShortcut, Panel, RichText, Ui, ViewportCommand, Window, frame};
@@ -54,10 +63,26 @@ pub struct App {
     fullscreen: bool,
     image_store: ImageStore,
     thumbnail_store: ImageStore,
+    ///Images of the slideshow, taken from the library
+    playlist: Option<Playlist>,
+    ///Images and active image shown before the playlist replaced them, back when the slideshow stops
+    playlist_restore: Option<(Vec<PathBuf>, Option<PathBuf>)>,
+    export_config: ExportConfig,
+    contact_sheet_config: ContactSheetConfig,
+    web_gallery_config: WebGalleryConfig,
+    ratings: Ratings,
+    tags: Tags,
+    albums: Albums,
+    worker_busy: bool,
 }
 
 impl App {
-    pub fn new(cc: &eframe::CreationContext<'_>, slideshow: bool, fullscreen: bool) -> Self {
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        slideshow: bool,
+        fullscreen: bool,
+        search: Option<String>,
+    ) -> Self {
         let cfg = Config::new();
 
         crate::theme::apply_theme(&cc.egui_ctx);
@@ -74,29 +99,43 @@ impl App {
                 .send_viewport_cmd(ViewportCommand::Fullscreen(true));
         }
 
-        let (mut img_paths, opened_img_path) = crawler::paths_from_args();
-
-        img_paths.sort();
-
         let mut db_repo = DbRepository::new();
-        let worker = Worker::new(cc.egui_ctx.clone(), &db_repo);
-
-        match db_repo.init_db() {
+        let db_initiated = match db_repo.init_db() {
             Ok(_) => {
                 tracing::info!("Database initiated successfully");
-                match db_repo.trim_db(&cfg.general.limit_cached) {
-                    Ok(_) => worker.send_job(crate::worker::Job::CacheMetadataForImages(
-                        img_paths.clone(),
-                    )),
-                    Err(e) => {
-                        tracing::info!("Failure trimming db {e}");
-                    }
-                };
+                true
             }
             Err(e) => {
                 tracing::info!("Failure initiating db -> {e}");
+                false
+            }
+        };
+
+        //Saved searches keep their own order
+        let search_paths = search
+            .as_deref()
+            .and_then(|name| FilterQuery::run_saved(&mut db_repo, name));
+        let opened_search = search.filter(|_| search_paths.is_some());
+        let (img_paths, opened_img_path) = match search_paths {
+            Some(paths) => (paths, None),
+            None => {
+                let (mut img_paths, opened_img_path) = crawler::paths_from_args();
+                img_paths.sort();
+                (img_paths, opened_img_path)
             }
         };
+        let worker = Worker::new(cc.egui_ctx.clone(), &db_repo);
+
+        if db_initiated {
+            match db_repo.trim_db(&cfg.general.limit_cached) {
+                Ok(_) => worker.send_job(crate::worker::Job::CacheMetadataForImages(
+                    img_paths.clone(),
+                )),
+                Err(e) => {
+                    tracing::info!("Failure trimming db {e}");
+                }
+            };
+        }
 
         let render_state = match cc.wgpu_render_state.clone() {
             Some(rs) => rs,
@@ -104,6 +143,7 @@ impl App {
         };
 
         let max_texture_size = render_state.adapter.limits().max_texture_dimension_2d;
+        AdjustmentsPipeline::register(&render_state);
 
         let base_path = Self::get_base_path(&img_paths, &opened_img_path);
         let worker = Arc::new(Mutex::new(worker));
@@ -114,7 +154,9 @@ impl App {
             &db_repo,
             cfg.general.simultaneous_load,
             &cfg.general.raw_exiftool_preview_ext,
-        );
+        )
+        .keep_pixels(cfg.image_view.keep_pixels)
+        .persisted_view_transform(cfg.image_view.persist_view_transform);
         let thumbnail_store = ImageStore::new(
             cfg.general.output_icc_profile.to_owned(),
             max_texture_size,
@@ -123,6 +165,22 @@ impl App {
             cfg.general.simultaneous_load,
             &cfg.general.raw_exiftool_preview_ext,
         );
+        let xmp_writer = XmpWriter::new(cfg.general.xmp_sidecar_naming);
+        let mut ratings = Ratings::new(&db_repo, &cfg.general, &xmp_writer);
+        ratings.load(&img_paths);
+        let mut tags = Tags::new(&db_repo, &cfg.general, &xmp_writer);
+        tags.load(&img_paths);
+        let mut filters = Filters::new(
+            cfg.filter,
+            base_path.to_str().unwrap_or(""),
+            worker.clone(),
+            &db_repo,
+        );
+        if let Some(name) = opened_search {
+            filters.fill_saved_search(&name);
+        }
+        let playlist = Playlist::new(&cfg.slideshow, &db_repo);
+
         Self {
             gallery: ImageView::new(
                 &img_paths,
@@ -131,9 +189,10 @@ impl App {
                 slideshow,
                 cfg.slideshow,
                 &mut image_store,
+                &db_repo,
             ),
             gallery_selected_index: None,
-            grid_view: GridView::new(&img_paths, cfg.grid_view),
+            grid_view: GridView::new(&img_paths, cfg.grid_view, &db_repo),
             perf_metrics_visible: false,
             grid_view_visible: false,
             top_menu_visible: false,
@@ -147,17 +206,21 @@ impl App {
             image_store,
             thumbnail_store,
             config: cfg.general,
-            filters: Filters::new(
-                cfg.filter,
-                base_path.to_str().unwrap_or(""),
-                worker.clone(),
-                &db_repo,
-            ),
+            filters,
             paths: img_paths,
             watcher: None,
             watcher_events: Arc::new(Mutex::new(vec![])),
             worker,
             fullscreen,
+            playlist,
+            playlist_restore: None,
+            export_config: cfg.export,
+            contact_sheet_config: cfg.contact_sheet,
+            web_gallery_config: cfg.web_gallery,
+            ratings,
+            tags,
+            albums: Albums::new(&db_repo),
+            worker_busy: false,
         }
     }
 
@@ -209,21 +272,123 @@ impl App {
                 self.flatten_open_dir();
             }
 
-            if i.consume_shortcut(&self.config.sc_toggle_gallery.kbd_shortcut) {
-                self.grid_view_visible = !self.grid_view_visible;
-                self.gallery_selected_index = Some(self.gallery.selected_img_index);
-            }
-
             if i.consume_shortcut(&self.config.sc_menu.kbd_shortcut) {
                 self.top_menu_visible = !self.top_menu_visible;
             }
         });
 
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_toggle_gallery.kbd_shortcut)) {
+            self.toggle_grid_view();
+        }
+
+        self.handle_rating_input(ctx);
+
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_tag_editor.kbd_shortcut)) {
+            self.tags.open_editor(self.action_targets());
+        }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_quick_collection.kbd_shortcut)) {
+            self.albums.toggle_quick_collection(&self.action_targets());
+        }
+
+        //Shift variant first as the plain shortcut also matches with shift held
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_remove_from_album.kbd_shortcut)) {
+            self.albums.remove_from_target(&self.action_targets());
+        }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_add_to_album.kbd_shortcut)) {
+            self.albums.add_to_target(&self.action_targets());
+        }
+
+        self.filters.handle_input(ctx);
+
         if ctx.input(|i| i.viewport().fullscreen.unwrap_or(false)) {
             self.fullscreen = true;
         }
     }
 
+    ///Image View opens on the image under the grid cursor and the cursor starts on the image
+    ///shown in Image View
+    fn toggle_grid_view(&mut self) {
+        if self.grid_view_visible
+            && let Some(name) = self.grid_view.focused_image_name()
+        {
+            self.gallery.select_by_name(name, &mut self.image_store);
+        }
+
+        self.grid_view_visible = !self.grid_view_visible;
+        self.gallery_selected_index = Some(self.gallery.selected_img_index);
+    }
+
+    ///Rates the image shown in Image View or the one hovered in Grid View
+    fn handle_rating_input(&mut self, ctx: &egui::Context) {
+        //Digits are also typed in the jump to image box
+        if ctx.egui_wants_keyboard_input() {
+            return;
+        }
+
+        let paths = self.action_targets();
+        if paths.is_empty() {
+            return;
+        }
+
+        //Shift variants first as the plain shortcuts also match with shift held
+        let config = &self.config;
+        let labels = [
+            (&config.sc_label_purple, ColorLabel::Purple),
+            (&config.sc_label_red, ColorLabel::Red),
+            (&config.sc_label_yellow, ColorLabel::Yellow),
+            (&config.sc_label_green, ColorLabel::Green),
+            (&config.sc_label_blue, ColorLabel::Blue),
+        ];
+        let ratings = [
+            &config.sc_rating_0,
+            &config.sc_rating_1,
+            &config.sc_rating_2,
+            &config.sc_rating_3,
+            &config.sc_rating_4,
+            &config.sc_rating_5,
+        ];
+
+        if ctx.input_mut(|i| config.sc_clear_label.consume_with_exact_alt(i)) {
+            for path in &paths {
+                self.ratings.clear_label(path);
+            }
+        }
+
+        if ctx.input_mut(|i| config.sc_reject.consume_with_exact_alt(i)) {
+            for path in &paths {
+                self.ratings.set_rating(path, REJECTED);
+            }
+        }
+
+        for (shortcut, label) in labels {
+            if ctx.input_mut(|i| shortcut.consume_with_exact_alt(i)) {
+                self.ratings.toggle_label(&paths, label);
+            }
+        }
+
+        for (rating, shortcut) in (0..=MAX_RATING).zip(ratings) {
+            if ctx.input_mut(|i| shortcut.consume_with_exact_alt(i)) {
+                for path in &paths {
+                    self.ratings.set_rating(path, rating);
+                }
+            }
+        }
+    }
+
+    ///Images tags and albums apply to: the grid selection, or else the hovered thumbnail, or the
+    ///image shown in Image View
+    fn action_targets(&self) -> Vec<PathBuf> {
+        if !self.grid_view_visible {
+            self.gallery.get_active_img_path().into_iter().collect()
+        } else if !self.grid_view.selected_paths().is_empty() {
+            self.grid_view.selected_paths()
+        } else {
+            self.grid_view.hovered_image_path().into_iter().collect()
+        }
+    }
+
     //Muter inputs will block all other inputs
     //This is required so typing in text boxes and the like doesn't
     //trigger shortcuts
@@ -307,6 +472,34 @@ impl App {
         self.set_images(&None, true);
     }
 
+    fn refresh_playlist(&mut self) {
+        let playlist = match self.playlist.as_mut() {
+            Some(playlist) => playlist,
+            None => return,
+        };
+
+        if !self.gallery.is_slideshow_running() {
+            //Queried again as soon as the next slideshow starts
+            playlist.reset();
+            if let Some((paths, active_img_path)) = self.playlist_restore.take() {
+                self.paths = paths;
+                self.set_images(&active_img_path, false);
+            }
+            return;
+        }
+
+        if let Some(paths) = playlist.poll() {
+            tracing::info!("Slideshow playlist refreshed with {} images", paths.len());
+            let active_img_path = self.gallery.get_active_img_path();
+            let previous_paths = std::mem::replace(&mut self.paths, paths);
+            self.playlist_restore
+                .get_or_insert((previous_paths, active_img_path.clone()));
+            self.set_images(&active_img_path, false);
+            self.gallery
+                .slideshow_images_replaced(&mut self.image_store);
+        }
+    }
+
     fn set_images(&mut self, selected_img: &Option<PathBuf>, new_dir_opened: bool) {
         if let Ok(worker) = self.worker.try_lock() {
             worker.send_job(crate::worker::Job::CacheMetadataForImages(
@@ -323,6 +516,8 @@ impl App {
             .set_images(&self.paths, selected_img, &mut self.image_store);
         self.grid_view
             .set_images(&self.paths, &mut self.thumbnail_store);
+        self.ratings.load(&self.paths);
+        self.tags.load(&self.paths);
 
         if new_dir_opened {
             self.base_path = Self::get_base_path(&self.paths, &None);
@@ -445,6 +640,7 @@ impl App {
             Callback::Reload(path) => self.reload_galleries_image(path),
             Callback::ReloadAll => self.callback_reload_all(),
             Callback::Advance => self.callback_advance(),
+            Callback::Export(paths) => self.export_images(paths),
             Callback::NoAction => {}
         }
     }
@@ -467,6 +663,74 @@ impl App {
         }
     }
 
+    fn export_images(&mut self, paths: Vec<PathBuf>) {
+        if paths.is_empty() {
+            return;
+        }
+
+        let job = ExportJob {
+            paths,
+            config: self.export_config.clone(),
+            settings: self.image_store.load_settings().clone(),
+        };
+
+        if let Ok(worker) = self.worker.try_lock() {
+            worker.send_job(crate::worker::Job::ExportImages(job));
+        } else {
+            tracing::error!("Failure locking mutex for export job");
+        }
+    }
+
+    fn create_contact_sheet(&mut self) {
+        if self.paths.is_empty() {
+            return;
+        }
+
+        let job = ContactSheetJob {
+            paths: self.paths.clone(),
+            config: self.contact_sheet_config.clone(),
+            settings: self.image_store.load_settings().clone(),
+            title: self
+                .base_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            output: ContactSheetJob::default_output(&self.contact_sheet_config, &self.base_path),
+        };
+
+        if let Ok(worker) = self.worker.try_lock() {
+            worker.send_job(crate::worker::Job::CreateContactSheet(job));
+        } else {
+            tracing::error!("Failure locking mutex for contact sheet job");
+        }
+    }
+
+    fn export_web_gallery(&mut self) {
+        if self.paths.is_empty() {
+            return;
+        }
+
+        let job = WebGalleryJob {
+            paths: self.paths.clone(),
+            config: self.web_gallery_config.clone(),
+            settings: self.image_store.load_settings().clone(),
+            title: self
+                .base_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            output: WebGalleryJob::default_output(&self.web_gallery_config, &self.base_path),
+        };
+
+        if let Ok(worker) = self.worker.try_lock() {
+            worker.send_job(crate::worker::Job::ExportWebGallery(job));
+        } else {
+            tracing::error!("Failure locking mutex for web gallery job");
+        }
+    }
+
     fn callback_reload_all(&mut self) {
         self.set_images_from_path(&self.base_path.clone(), &self.gallery.get_active_img_path());
     }
@@ -483,6 +747,15 @@ impl App {
             return
         };
 
+        //Importing metadata may have brought in ratings and tags from XMP, and the image sizes
+        //and tags used by the Grid View layout and groups
+        if self.worker_busy && msg_to_display.is_none() {
+            self.ratings.load(&self.paths);
+            self.tags.load(&self.paths);
+            self.grid_view.reload_metadata();
+        }
+        self.worker_busy = msg_to_display.is_some();
+
         if let Some(msg) = msg_to_display {
             let max_rect = ui.max_rect();
 
@@ -515,6 +788,11 @@ impl eframe::App for App {
         self.execute_img_store_routines();
         self.handle_input_muters(ui.ctx());
         self.handle_input(ui.ctx()); 
+        self.refresh_playlist();
+
+        if let Some(filtered_paths) = self.filters.poll_query() {
+            self.set_images_from_paths(filtered_paths);
+        }
 
         Panel::top("performance_metrics")
             .show_separator_line(false)
@@ -526,16 +804,45 @@ impl eframe::App for App {
         Panel::top("menu")
             .show_separator_line(false)
             .show_animated_inside(ui, self.top_menu_visible, |ui| {
-                ui.menu_button("File", |ui| {
-                    if ui.button("Open Folder").clicked() {
-                        self.folder_picker();
-                        ui.close();
-                    }
+                ui.horizontal(|ui| {
+                    ui.menu_button("File", |ui| {
+                        if ui.button("Open Folder").clicked() {
+                            self.folder_picker();
+                            ui.close();
+                        }
+
+                        if ui.button("Open Files").clicked() {
+                            self.files_picker();
+                            ui.close();
+                        }
+
+                        if ui.button("Export Shown Images").clicked() {
+                            self.export_images(self.paths.clone());
+                            ui.close();
+                        }
+
+                        if ui.button("Contact Sheet").clicked() {
+                            self.create_contact_sheet();
+                            ui.close();
+                        }
+
+                        if ui.button("Export Web Gallery").clicked() {
+                            self.export_web_gallery();
+                            ui.close();
+                        }
+                    });
 
-                    if ui.button("Open Files").clicked() {
-                        self.files_picker();
-                        ui.close();
-                    }
+                    ui.menu_button("View", |ui| {
+                        if ui.button("Slideshow").clicked() {
+                            self.grid_view_visible = false;
+                            self.gallery.toggle_slideshow(&mut self.image_store);
+                            ui.close();
+                        }
+
+                        ui.menu_button("Group Grid View", |ui| {
+                            self.grid_view.group_by_ui(ui);
+                        });
+                    });
                 });
             });
 
@@ -546,9 +853,7 @@ impl eframe::App for App {
             .min_size(200.)
             .show_animated_inside(ui, self.side_panel_visible, |ui| {
                 egui::ScrollArea::vertical().show(ui, |ui| {
-                    if let Some(filtered_paths) = self.filters.ui(ui) {
-                        self.set_images_from_paths(filtered_paths);
-                    }
+                    self.filters.ui(ui);
                     ui.add_space(20.);
                     ui.separator();
                     ui.add_space(10.);
@@ -556,6 +861,24 @@ impl eframe::App for App {
                     ui.add_space(10.);
                     if let Some(selected_img) = self.gallery.get_active_img_mut() {
                         selected_img.metadata_ui(ui, &self.config.metadata_tags, &self.image_store);
+
+                        let tags = self.tags.get(&selected_img.path);
+                        if !tags.is_empty() {
+                            ui.add_space(5.);
+                            ui.label(tags.join(", "));
+                        }
+                    }
+                    ui.add_space(20.);
+                    ui.separator();
+                    ui.add_space(10.);
+                    if let Some(tagged_paths) = self.tags.browser_ui(ui) {
+                        self.set_images_from_paths(tagged_paths);
+                    }
+                    ui.add_space(20.);
+                    ui.separator();
+                    ui.add_space(10.);
+                    if let Some(album_paths) = self.albums.ui(ui) {
+                        self.set_images_from_paths(album_paths);
                     }
                 });
             });
@@ -580,6 +903,7 @@ impl eframe::App for App {
                 ui,
                 &mut self.gallery_selected_index,
                 &mut self.thumbnail_store,
+                &self.ratings,
             );
 
             if let Some(img_name) = self.grid_view.selected_image_name() {
@@ -587,7 +911,7 @@ impl eframe::App for App {
                 self.grid_view_visible = false;
             }
 
-            if let Some(callback) = self.grid_view.take_callback() {
+            for callback in self.grid_view.take_callbacks() {
                 self.execute_callback(callback);
             }
         } else {
@@ -596,6 +920,7 @@ impl eframe::App for App {
                 self.dir_flattened,
                 self.watcher.is_some(),
                 &mut self.image_store,
+                &mut self.ratings,
             );
 
             if let Some(callback) = self.gallery.take_callback() {
@@ -603,6 +928,12 @@ impl eframe::App for App {
             }
         }
 
+        //Image View goes through the images in the order the groups show them
+        if let Some(paths) = self.grid_view.take_reordered_paths() {
+            self.gallery.reorder(&paths, &mut self.image_store);
+        }
+
+        self.tags.editor_ui(ui.ctx());
         self.show_worker_msg(ui);
 
         if self.watcher.is_some() {
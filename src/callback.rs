@@ -14,6 +14,7 @@ pub enum Callback {
     Reload(Option<PathBuf>),
     Pop(Option<PathBuf>),
     Advance,
+    Export(Vec<PathBuf>),
     NoAction,
 }
 
@@ -24,9 +25,22 @@ impl Callback {
             Callback::Reload(_) => Self::Reload(path),
             Callback::Pop(_) => Self::Pop(path),
             Callback::Advance => Self::Advance,
+            Callback::Export(_) => Self::Export(path.into_iter().collect()),
             Callback::NoAction => Self::NoAction,
         }
     }
+
+    ///Callbacks of an action run on several images. Exports are done in a single job.
+    pub fn for_paths(callback: Callback, paths: &[PathBuf]) -> Vec<Callback> {
+        match callback {
+            Callback::Reload(_) | Callback::Pop(_) => paths
+                .iter()
+                .map(|path| Self::from_callback(callback.clone(), Some(path.clone())))
+                .collect(),
+            Callback::Export(_) => vec![Self::Export(paths.to_vec())],
+            callback => vec![callback],
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for Callback {
@@ -41,6 +55,7 @@ impl<'de> Deserialize<'de> for Callback {
             "Reload" => Callback::Reload(None),
             "Pop" => Callback::Pop(None),
             "Advance" => Callback::Advance,
+            "Export" => Callback::Export(vec![]),
             "" => Callback::NoAction,
             &_ => Callback::NoAction,
         })
@@ -58,6 +73,7 @@ impl Serialize for Callback {
             Callback::Pop(_) => "Pop",
             Callback::Reload(_) => "Reload",
             Callback::Advance => "Advance",
+            Callback::Export(_) => "Export",
         })
     }
 }
@@ -1,6 +1,8 @@
-use crate::{APPLICATION, ORGANIZATION, QUALIFIER, callback::Callback, utils};
-use eframe::egui::{Key, KeyboardShortcut, Modifiers};
-use serde::{Deserialize, Serialize};
+use crate::{
+    APPLICATION, ORGANIZATION, QUALIFIER, callback::Callback, filters::FilterQuery, utils,
+};
+use eframe::egui::{Event, InputState, Key, KeyboardShortcut, Modifiers};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::{fs, io::ErrorKind, path::PathBuf, vec};
 
 const MOD_ALT: &str = "alt";
@@ -16,6 +18,12 @@ pub struct Config {
     pub general: GeneralConfig,
     pub filter: FilterConfig,
     pub slideshow: SlideshowConfig,
+    #[serde(default)]
+    pub export: ExportConfig,
+    #[serde(default)]
+    pub contact_sheet: ContactSheetConfig,
+    #[serde(default)]
+    pub web_gallery: WebGalleryConfig,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -32,6 +40,12 @@ pub struct GeneralConfig {
     pub simultaneous_load: usize,
     #[serde(default = "default_raw_exiftool_preview_ext")]
     pub raw_exiftool_preview_ext: Vec<String>,
+    #[serde(default = "default_rating_xmp")]
+    pub rating_xmp: XmpWrite,
+    #[serde(default = "default_xmp_sidecar_naming")]
+    pub xmp_sidecar_naming: SidecarNaming,
+    #[serde(default = "default_tag_xmp")]
+    pub tag_xmp: XmpWrite,
 
     #[serde(default = "default_sc_toggle_gallery")]
     pub sc_toggle_gallery: Shortcut,
@@ -49,6 +63,57 @@ pub struct GeneralConfig {
     pub sc_watch_directory: Shortcut,
     #[serde(default = "default_sc_toggle_side_panel")]
     pub sc_toggle_side_panel: Shortcut,
+    #[serde(default = "default_sc_rating_0")]
+    pub sc_rating_0: Shortcut,
+    #[serde(default = "default_sc_rating_1")]
+    pub sc_rating_1: Shortcut,
+    #[serde(default = "default_sc_rating_2")]
+    pub sc_rating_2: Shortcut,
+    #[serde(default = "default_sc_rating_3")]
+    pub sc_rating_3: Shortcut,
+    #[serde(default = "default_sc_rating_4")]
+    pub sc_rating_4: Shortcut,
+    #[serde(default = "default_sc_rating_5")]
+    pub sc_rating_5: Shortcut,
+    #[serde(default = "default_sc_reject")]
+    pub sc_reject: Shortcut,
+    #[serde(default = "default_sc_label_red")]
+    pub sc_label_red: Shortcut,
+    #[serde(default = "default_sc_label_yellow")]
+    pub sc_label_yellow: Shortcut,
+    #[serde(default = "default_sc_label_green")]
+    pub sc_label_green: Shortcut,
+    #[serde(default = "default_sc_label_blue")]
+    pub sc_label_blue: Shortcut,
+    #[serde(default = "default_sc_label_purple")]
+    pub sc_label_purple: Shortcut,
+    #[serde(default = "default_sc_clear_label")]
+    pub sc_clear_label: Shortcut,
+    #[serde(default = "default_sc_tag_editor")]
+    pub sc_tag_editor: Shortcut,
+    #[serde(default = "default_sc_quick_collection")]
+    pub sc_quick_collection: Shortcut,
+    #[serde(default = "default_sc_add_to_album")]
+    pub sc_add_to_album: Shortcut,
+    #[serde(default = "default_sc_remove_from_album")]
+    pub sc_remove_from_album: Shortcut,
+}
+
+///Where ratings, color labels and tags are written besides the database
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum XmpWrite {
+    Sidecar,
+    ///Written into the image file itself
+    Embedded,
+    Off,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum SidecarNaming {
+    ///`IMG_0001.RAF.xmp`
+    Darktable,
+    ///`IMG_0001.xmp`
+    Lightroom,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -59,12 +124,43 @@ pub struct ImageViewConfig {
     pub nr_images_shown: usize,
     #[serde(default = "default_should_wait")]
     pub should_wait: bool,
-    #[serde(default = "default_frame_size_relative_to_image")]
-    pub frame_size_relative_to_image: f32,
+    #[serde(
+        default = "default_frame_styles",
+        alias = "frame_size_relative_to_image",
+        deserialize_with = "deserialize_frame_styles"
+    )]
+    pub frame_styles: Vec<FrameStyle>,
+    #[serde(default = "default_frame_export_dir")]
+    pub frame_export_dir: String,
+    #[serde(default = "default_frame_export_format")]
+    pub frame_export_format: ExportFormat,
+    #[serde(default = "default_frame_export_quality")]
+    pub frame_export_quality: u8,
     #[serde(default = "default_scroll_navigation")]
     pub scroll_navigation: bool,
     #[serde(default = "default_name_format")]
     pub name_format: String,
+    #[serde(default = "default_keep_pixels")]
+    pub keep_pixels: bool,
+    #[serde(default = "default_persist_view_transform")]
+    pub persist_view_transform: bool,
+    #[serde(default = "default_crop_ratios")]
+    pub crop_ratios: Vec<String>,
+    #[serde(default = "default_minimap_enabled")]
+    pub minimap_enabled: bool,
+    #[serde(default = "default_minimap_corner")]
+    pub minimap_corner: Corner,
+    #[serde(default = "default_minimap_size")]
+    pub minimap_size: f32,
+    #[serde(default = "default_minimap_hide_after_secs")]
+    pub minimap_hide_after_secs: f32,
+    #[serde(default = "default_focus_peaking_threshold")]
+    pub focus_peaking_threshold: f32,
+    #[serde(default = "default_focus_peaking_color")]
+    pub focus_peaking_color: String,
+    ///Rejects are moved here from the culling summary, the system trash when empty
+    #[serde(default = "default_cull_trash_dir")]
+    pub cull_trash_dir: String,
     #[serde(default = "default_user_actions")]
     pub user_actions: Vec<UserAction>,
     #[serde(default = "default_ctx_menu")]
@@ -74,6 +170,8 @@ pub struct ImageViewConfig {
     pub sc_fit: Shortcut,
     #[serde(default = "default_sc_frame")]
     pub sc_frame: Shortcut,
+    #[serde(default = "default_sc_cycle_frame_styles")]
+    pub sc_cycle_frame_styles: Shortcut,
     #[serde(default = "default_sc_zoom")]
     pub sc_zoom: Shortcut,
     #[serde(default = "default_sc_next")]
@@ -94,6 +192,66 @@ pub struct ImageViewConfig {
     pub sc_more_images_shown: Shortcut,
     #[serde(default = "default_sc_less_images_shown")]
     pub sc_less_images_shown: Shortcut,
+    #[serde(default = "default_sc_pixel_inspector")]
+    pub sc_pixel_inspector: Shortcut,
+    #[serde(default = "default_sc_copy_pixel")]
+    pub sc_copy_pixel: Shortcut,
+    #[serde(default = "default_sc_exposure_up")]
+    pub sc_exposure_up: Shortcut,
+    #[serde(default = "default_sc_exposure_down")]
+    pub sc_exposure_down: Shortcut,
+    #[serde(default = "default_sc_gamma_up")]
+    pub sc_gamma_up: Shortcut,
+    #[serde(default = "default_sc_gamma_down")]
+    pub sc_gamma_down: Shortcut,
+    #[serde(default = "default_sc_contrast_up")]
+    pub sc_contrast_up: Shortcut,
+    #[serde(default = "default_sc_contrast_down")]
+    pub sc_contrast_down: Shortcut,
+    #[serde(default = "default_sc_invert")]
+    pub sc_invert: Shortcut,
+    #[serde(default = "default_sc_grayscale")]
+    pub sc_grayscale: Shortcut,
+    #[serde(default = "default_sc_red_channel")]
+    pub sc_red_channel: Shortcut,
+    #[serde(default = "default_sc_green_channel")]
+    pub sc_green_channel: Shortcut,
+    #[serde(default = "default_sc_blue_channel")]
+    pub sc_blue_channel: Shortcut,
+    #[serde(default = "default_sc_reset_adjustments")]
+    pub sc_reset_adjustments: Shortcut,
+    #[serde(default = "default_sc_rotate_cw")]
+    pub sc_rotate_cw: Shortcut,
+    #[serde(default = "default_sc_rotate_ccw")]
+    pub sc_rotate_ccw: Shortcut,
+    #[serde(default = "default_sc_rotate_180")]
+    pub sc_rotate_180: Shortcut,
+    #[serde(default = "default_sc_flip_horizontal")]
+    pub sc_flip_horizontal: Shortcut,
+    #[serde(default = "default_sc_flip_vertical")]
+    pub sc_flip_vertical: Shortcut,
+    #[serde(default = "default_sc_write_orientation")]
+    pub sc_write_orientation: Shortcut,
+    #[serde(default = "default_sc_cycle_guides")]
+    pub sc_cycle_guides: Shortcut,
+    #[serde(default = "default_sc_cycle_crop_masks")]
+    pub sc_cycle_crop_masks: Shortcut,
+    #[serde(default = "default_sc_focus_peaking")]
+    pub sc_focus_peaking: Shortcut,
+    #[serde(default = "default_sc_peaking_threshold_up")]
+    pub sc_peaking_threshold_up: Shortcut,
+    #[serde(default = "default_sc_peaking_threshold_down")]
+    pub sc_peaking_threshold_down: Shortcut,
+    #[serde(default = "default_sc_culling")]
+    pub sc_culling: Shortcut,
+    #[serde(default = "default_sc_cull_pick")]
+    pub sc_cull_pick: Shortcut,
+    #[serde(default = "default_sc_cull_reject")]
+    pub sc_cull_reject: Shortcut,
+    #[serde(default = "default_sc_cull_unflag")]
+    pub sc_cull_unflag: Shortcut,
+    #[serde(default = "default_sc_cull_summary")]
+    pub sc_cull_summary: Shortcut,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -102,8 +260,14 @@ pub struct GridViewConfig {
     pub images_per_row: usize,
     #[serde(default = "default_preloaded_rows")]
     pub preloaded_rows: usize,
+    #[serde(default = "default_grid_layout")]
+    pub layout: GridLayout,
+    #[serde(default = "default_group_by")]
+    pub group_by: GridGroupBy,
     #[serde(default = "default_ctx_menu")]
     pub context_menu: Vec<ContextMenuEntry>,
+    #[serde(default = "default_user_actions")]
+    pub user_actions: Vec<UserAction>,
 
     #[serde(default = "default_sc_scroll")]
     pub sc_scroll: Shortcut,
@@ -111,12 +275,184 @@ pub struct GridViewConfig {
     pub sc_more_per_row: Shortcut,
     #[serde(default = "default_sc_less_per_row")]
     pub sc_less_per_row: Shortcut,
+    #[serde(default = "default_sc_select_all")]
+    pub sc_select_all: Shortcut,
+    #[serde(default = "default_sc_invert_selection")]
+    pub sc_invert_selection: Shortcut,
+    #[serde(default = "default_sc_toggle_layout")]
+    pub sc_toggle_layout: Shortcut,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum GridLayout {
+    ///Square cells, `images_per_row` per row
+    Square,
+    ///Rows of equal height filled edge to edge, widths following the aspect ratio of each image
+    Justified,
+}
+
+///Sections Grid View splits the images into
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub enum GridGroupBy {
+    None,
+    ///Capture day
+    Day,
+    ///Capture month
+    Month,
+    Directory,
+    ///Camera model
+    Camera,
+    ///Any exif tag, or the tags of the filters like `Star Rating`
+    Tag(String),
 }
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct FilterConfig {
     #[serde(default = "default_exif_tags")]
     pub exif_tags: Vec<FilterableExifTag>,
+    #[serde(default = "default_saved_search_shortcuts")]
+    pub saved_search_shortcuts: Vec<SavedSearchShortcut>,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct FrameStyle {
+    pub name: String,
+    #[serde(default = "default_frame_color")]
+    pub color: String,
+    ///Top, right, bottom and left border widths relative to the shortest side of the image
+    pub borders: [f32; 4],
+    #[serde(default)]
+    pub shadow: bool,
+    ///Caption written inside the bottom border, uses `$(#exif_tag#)` expressions
+    #[serde(default)]
+    pub caption_format: String,
+    #[serde(default = "default_frame_caption_color")]
+    pub caption_color: String,
+}
+
+///Format of the images written by the application
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    Jpeg,
+    Png,
+    ///Lossless only
+    WebP,
+    Jxl,
+    Avif,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Jpeg => "jpg",
+            ExportFormat::Png => "png",
+            ExportFormat::WebP => "webp",
+            ExportFormat::Jxl => "jxl",
+            ExportFormat::Avif => "avif",
+        }
+    }
+
+    ///JPEG XL and AVIF images are written without an ICC profile and read as sRGB
+    pub fn embeds_icc_profile(&self) -> bool {
+        !matches!(self, ExportFormat::Jxl | ExportFormat::Avif)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ExportConfig {
+    #[serde(default = "default_export_dir")]
+    pub dir: String,
+    #[serde(default = "default_export_filename_template")]
+    pub filename_template: String,
+    #[serde(default = "default_export_format")]
+    pub format: ExportFormat,
+    #[serde(default = "default_export_quality")]
+    pub quality: u8,
+    #[serde(default = "default_export_resize")]
+    pub resize: ExportResize,
+    #[serde(default = "default_export_sharpen")]
+    pub sharpen: f32,
+    #[serde(default = "default_export_icc_profile")]
+    pub icc_profile: String,
+    #[serde(default = "default_export_metadata")]
+    pub metadata: ExportMetadata,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum ExportResize {
+    None,
+    ///Longest side in pixels
+    LongEdge(u32),
+    Megapixels(f32),
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ContactSheetConfig {
+    #[serde(default = "default_contact_sheet_dir")]
+    pub dir: String,
+    #[serde(default = "default_contact_sheet_format")]
+    pub format: ContactSheetFormat,
+    #[serde(default = "default_contact_sheet_columns")]
+    pub columns: usize,
+    ///Page size in pixels
+    #[serde(default = "default_contact_sheet_page_size")]
+    pub page_size: [u32; 2],
+    ///Pixels per inch, sets the printed size of the PDF pages
+    #[serde(default = "default_contact_sheet_dpi")]
+    pub dpi: u32,
+    #[serde(default = "default_contact_sheet_margin")]
+    pub margin: u32,
+    ///Uses `$(#exif_tag#)` expressions like `name_format`
+    #[serde(default = "default_contact_sheet_caption_format")]
+    pub caption_format: String,
+    ///`{title}`, `{date}`, `{page}` and `{pages}` are replaced
+    #[serde(default = "default_contact_sheet_header_format")]
+    pub header_format: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum ContactSheetFormat {
+    ///One file per page
+    Png,
+    Pdf,
+}
+
+impl ContactSheetFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ContactSheetFormat::Png => "png",
+            ContactSheetFormat::Pdf => "pdf",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct WebGalleryConfig {
+    #[serde(default = "default_web_gallery_dir")]
+    pub dir: String,
+    ///Shown in the page titles, the opened directory name when empty
+    #[serde(default = "default_web_gallery_title")]
+    pub title: String,
+    #[serde(default = "default_web_gallery_image_long_edge")]
+    pub image_long_edge: u32,
+    #[serde(default = "default_web_gallery_thumbnail_long_edge")]
+    pub thumbnail_long_edge: u32,
+    #[serde(default = "default_web_gallery_quality")]
+    pub quality: u8,
+    ///Exif tags listed in the image pages
+    #[serde(default = "default_web_gallery_metadata_tags")]
+    pub metadata_tags: Vec<String>,
+    ///Metadata kept in the published images, thumbnails never have any
+    #[serde(default = "default_web_gallery_metadata")]
+    pub metadata: ExportMetadata,
+}
+
+///Metadata copied from the original image to the exported one
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum ExportMetadata {
+    Keep,
+    StripGps,
+    StripAll,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -125,6 +461,13 @@ pub struct FilterableExifTag {
     pub fetch_distinct: bool,
 }
 
+///Opens the saved search with this name
+#[derive(Deserialize, Serialize, Clone)]
+pub struct SavedSearchShortcut {
+    pub shortcut: Shortcut,
+    pub search: String,
+}
+
 #[derive(Deserialize, Serialize, Clone)]
 pub struct SlideshowConfig {
     #[serde(default = "default_seconds_per_image")]
@@ -135,6 +478,57 @@ pub struct SlideshowConfig {
     pub start_with_frame_enabled: bool,
     #[serde(default = "default_image_frame_background_color_override")]
     pub image_frame_background_color_override: Option<String>,
+    #[serde(default = "default_ken_burns_target")]
+    pub ken_burns_target: KenBurnsTarget,
+    #[serde(default = "default_transition")]
+    pub transition: SlideshowTransition,
+    #[serde(default = "default_transition_ms")]
+    pub transition_ms: u64,
+    #[serde(default = "default_shuffle")]
+    pub shuffle: bool,
+    #[serde(default = "default_repeat")]
+    pub repeat: bool,
+    #[serde(default = "default_caption_format")]
+    pub caption_format: String,
+    #[serde(default = "default_show_clock")]
+    pub show_clock: bool,
+    #[serde(default = "default_clock_format")]
+    pub clock_format: String,
+    #[serde(default = "default_overlay_text_size")]
+    pub overlay_text_size: f32,
+    #[serde(default = "default_quiet_hours_start")]
+    pub quiet_hours_start: Option<String>,
+    #[serde(default = "default_quiet_hours_end")]
+    pub quiet_hours_end: Option<String>,
+    #[serde(default = "default_playlist")]
+    pub playlist: Option<FilterQuery>,
+    #[serde(default = "default_playlist_refresh_minutes")]
+    pub playlist_refresh_minutes: u64,
+
+    #[serde(default = "default_sc_slideshow_toggle")]
+    pub sc_toggle: Shortcut,
+    #[serde(default = "default_sc_slideshow_pause")]
+    pub sc_pause: Shortcut,
+    #[serde(default = "default_sc_slideshow_faster")]
+    pub sc_faster: Shortcut,
+    #[serde(default = "default_sc_slideshow_slower")]
+    pub sc_slower: Shortcut,
+}
+
+///Where the Ken Burns motion of the slideshow ends
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum KenBurnsTarget {
+    Random,
+    Center,
+    ///Focus point written by the camera, the center if there is none
+    FocusPoint,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum SlideshowTransition {
+    Cut,
+    Crossfade,
+    Slide,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -153,6 +547,14 @@ pub struct ShortcutData {
     pub modifiers: Vec<String>,
 }
 
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
 #[derive(Deserialize, Serialize, Clone)]
 pub struct UserAction {
     pub shortcut: Shortcut,
@@ -176,6 +578,83 @@ impl Shortcut {
             modifiers,
         }
     }
+
+    ///Consumes the shortcut only when alt is held as configured. egui also matches shortcuts
+    ///without alt while it's held, which would steal the alt shortcuts of the same key.
+    pub fn consume_with_exact_alt(&self, input: &mut InputState) -> bool {
+        self.matches_alt(input.modifiers) && input.consume_shortcut(&self.kbd_shortcut)
+    }
+
+    fn matches_alt(&self, pressed: Modifiers) -> bool {
+        pressed.alt == self.kbd_shortcut.modifiers.alt
+    }
+
+    ///Consumes the shortcut, or the copy event when it's the copy command. egui sends ctrl + c
+    ///as a copy event instead of a key press, so the shortcut alone never matches it.
+    pub fn consume_or_copy_event(&self, input: &mut InputState) -> bool {
+        if input.consume_shortcut(&self.kbd_shortcut) {
+            return true;
+        }
+        if !self.is_copy_command() {
+            return false;
+        }
+
+        let events = input.events.len();
+        input.events.retain(|event| !matches!(event, Event::Copy));
+        input.events.len() != events
+    }
+
+    fn is_copy_command(&self) -> bool {
+        let modifiers = self.kbd_shortcut.modifiers;
+        self.kbd_shortcut.logical_key == Key::C
+            && (modifiers.ctrl || modifiers.command || modifiers.mac_cmd)
+            && !modifiers.alt
+            && !modifiers.shift
+    }
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        ExportConfig {
+            dir: default_export_dir(),
+            filename_template: default_export_filename_template(),
+            format: default_export_format(),
+            quality: default_export_quality(),
+            resize: default_export_resize(),
+            sharpen: default_export_sharpen(),
+            icc_profile: default_export_icc_profile(),
+            metadata: default_export_metadata(),
+        }
+    }
+}
+
+impl Default for ContactSheetConfig {
+    fn default() -> Self {
+        ContactSheetConfig {
+            dir: default_contact_sheet_dir(),
+            format: default_contact_sheet_format(),
+            columns: default_contact_sheet_columns(),
+            page_size: default_contact_sheet_page_size(),
+            dpi: default_contact_sheet_dpi(),
+            margin: default_contact_sheet_margin(),
+            caption_format: default_contact_sheet_caption_format(),
+            header_format: default_contact_sheet_header_format(),
+        }
+    }
+}
+
+impl Default for WebGalleryConfig {
+    fn default() -> Self {
+        WebGalleryConfig {
+            dir: default_web_gallery_dir(),
+            title: default_web_gallery_title(),
+            image_long_edge: default_web_gallery_image_long_edge(),
+            thumbnail_long_edge: default_web_gallery_thumbnail_long_edge(),
+            quality: default_web_gallery_quality(),
+            metadata_tags: default_web_gallery_metadata_tags(),
+            metadata: default_web_gallery_metadata(),
+        }
+    }
 }
 
 impl Default for GeneralConfig {
@@ -187,6 +666,9 @@ impl Default for GeneralConfig {
             metadata_tags: default_metadata_tags(),
             simultaneous_load: default_simultaneous_load(),
             raw_exiftool_preview_ext: default_raw_exiftool_preview_ext(),
+            rating_xmp: default_rating_xmp(),
+            xmp_sidecar_naming: default_xmp_sidecar_naming(),
+            tag_xmp: default_tag_xmp(),
             sc_toggle_gallery: default_sc_toggle_gallery(),
             sc_toggle_side_panel: default_sc_toggle_side_panel(),
             sc_exit: default_sc_exit(),
@@ -195,6 +677,23 @@ impl Default for GeneralConfig {
             sc_dir_tree: default_sc_dir_tree(),
             sc_flatten_dir: default_sc_flatten_dir(),
             sc_watch_directory: default_sc_watch_directory(),
+            sc_rating_0: default_sc_rating_0(),
+            sc_rating_1: default_sc_rating_1(),
+            sc_rating_2: default_sc_rating_2(),
+            sc_rating_3: default_sc_rating_3(),
+            sc_rating_4: default_sc_rating_4(),
+            sc_rating_5: default_sc_rating_5(),
+            sc_reject: default_sc_reject(),
+            sc_label_red: default_sc_label_red(),
+            sc_label_yellow: default_sc_label_yellow(),
+            sc_label_green: default_sc_label_green(),
+            sc_label_blue: default_sc_label_blue(),
+            sc_label_purple: default_sc_label_purple(),
+            sc_clear_label: default_sc_clear_label(),
+            sc_tag_editor: default_sc_tag_editor(),
+            sc_quick_collection: default_sc_quick_collection(),
+            sc_add_to_album: default_sc_add_to_album(),
+            sc_remove_from_album: default_sc_remove_from_album(),
         }
     }
 }
@@ -205,14 +704,28 @@ impl Default for ImageViewConfig {
             nr_loaded_images: default_nr_loaded_images(),
             nr_images_shown: default_nr_images_shown(),
             should_wait: default_should_wait(),
-            frame_size_relative_to_image: default_frame_size_relative_to_image(),
+            frame_styles: default_frame_styles(),
+            frame_export_dir: default_frame_export_dir(),
+            frame_export_format: default_frame_export_format(),
+            frame_export_quality: default_frame_export_quality(),
             scroll_navigation: default_scroll_navigation(),
             user_actions: default_user_actions(),
             context_menu: default_ctx_menu(),
             name_format: default_name_format(),
+            keep_pixels: default_keep_pixels(),
+            persist_view_transform: default_persist_view_transform(),
+            crop_ratios: default_crop_ratios(),
+            minimap_enabled: default_minimap_enabled(),
+            minimap_corner: default_minimap_corner(),
+            minimap_size: default_minimap_size(),
+            minimap_hide_after_secs: default_minimap_hide_after_secs(),
+            focus_peaking_threshold: default_focus_peaking_threshold(),
+            focus_peaking_color: default_focus_peaking_color(),
+            cull_trash_dir: default_cull_trash_dir(),
 
             sc_fit: default_sc_fit(),
             sc_frame: default_sc_frame(),
+            sc_cycle_frame_styles: default_sc_cycle_frame_styles(),
             sc_zoom: default_sc_zoom(),
             sc_next: default_sc_next(),
             sc_prev: default_sc_prev(),
@@ -223,6 +736,36 @@ impl Default for ImageViewConfig {
             sc_latch_fit_maximize: default_sc_latch_fit_maximize(),
             sc_more_images_shown: default_sc_more_images_shown(),
             sc_less_images_shown: default_sc_less_images_shown(),
+            sc_pixel_inspector: default_sc_pixel_inspector(),
+            sc_copy_pixel: default_sc_copy_pixel(),
+            sc_exposure_up: default_sc_exposure_up(),
+            sc_exposure_down: default_sc_exposure_down(),
+            sc_gamma_up: default_sc_gamma_up(),
+            sc_gamma_down: default_sc_gamma_down(),
+            sc_contrast_up: default_sc_contrast_up(),
+            sc_contrast_down: default_sc_contrast_down(),
+            sc_invert: default_sc_invert(),
+            sc_grayscale: default_sc_grayscale(),
+            sc_red_channel: default_sc_red_channel(),
+            sc_green_channel: default_sc_green_channel(),
+            sc_blue_channel: default_sc_blue_channel(),
+            sc_reset_adjustments: default_sc_reset_adjustments(),
+            sc_rotate_cw: default_sc_rotate_cw(),
+            sc_rotate_ccw: default_sc_rotate_ccw(),
+            sc_rotate_180: default_sc_rotate_180(),
+            sc_flip_horizontal: default_sc_flip_horizontal(),
+            sc_flip_vertical: default_sc_flip_vertical(),
+            sc_write_orientation: default_sc_write_orientation(),
+            sc_cycle_guides: default_sc_cycle_guides(),
+            sc_cycle_crop_masks: default_sc_cycle_crop_masks(),
+            sc_focus_peaking: default_sc_focus_peaking(),
+            sc_peaking_threshold_up: default_sc_peaking_threshold_up(),
+            sc_peaking_threshold_down: default_sc_peaking_threshold_down(),
+            sc_culling: default_sc_culling(),
+            sc_cull_pick: default_sc_cull_pick(),
+            sc_cull_reject: default_sc_cull_reject(),
+            sc_cull_unflag: default_sc_cull_unflag(),
+            sc_cull_summary: default_sc_cull_summary(),
         }
     }
 }
@@ -232,11 +775,17 @@ impl Default for GridViewConfig {
         GridViewConfig {
             images_per_row: default_images_per_row(),
             preloaded_rows: default_preloaded_rows(),
+            layout: default_grid_layout(),
+            group_by: default_group_by(),
             context_menu: default_ctx_menu(),
+            user_actions: default_user_actions(),
 
             sc_scroll: default_sc_scroll(),
             sc_more_per_row: default_sc_more_per_row(),
             sc_less_per_row: default_sc_less_per_row(),
+            sc_select_all: default_sc_select_all(),
+            sc_invert_selection: default_sc_invert_selection(),
+            sc_toggle_layout: default_sc_toggle_layout(),
         }
     }
 }
@@ -245,6 +794,7 @@ impl Default for FilterConfig {
     fn default() -> Self {
         FilterConfig {
             exif_tags: default_exif_tags(),
+            saved_search_shortcuts: default_saved_search_shortcuts(),
         }
     }
 }
@@ -256,6 +806,23 @@ impl Default for SlideshowConfig {
             percent_zoom: default_percent_zoom(),
             start_with_frame_enabled: default_start_with_frame_enabled(),
             image_frame_background_color_override: default_image_frame_background_color_override(),
+            ken_burns_target: default_ken_burns_target(),
+            transition: default_transition(),
+            transition_ms: default_transition_ms(),
+            shuffle: default_shuffle(),
+            repeat: default_repeat(),
+            caption_format: default_caption_format(),
+            show_clock: default_show_clock(),
+            clock_format: default_clock_format(),
+            overlay_text_size: default_overlay_text_size(),
+            quiet_hours_start: default_quiet_hours_start(),
+            quiet_hours_end: default_quiet_hours_end(),
+            playlist: default_playlist(),
+            playlist_refresh_minutes: default_playlist_refresh_minutes(),
+            sc_toggle: default_sc_slideshow_toggle(),
+            sc_pause: default_sc_slideshow_pause(),
+            sc_faster: default_sc_slideshow_faster(),
+            sc_slower: default_sc_slideshow_slower(),
         }
     }
 }
@@ -363,6 +930,86 @@ pub fn default_sc_watch_directory() -> Shortcut {
     Shortcut::from("w", &[MOD_CTRL])
 }
 
+pub fn default_rating_xmp() -> XmpWrite {
+    XmpWrite::Sidecar
+}
+
+pub fn default_xmp_sidecar_naming() -> SidecarNaming {
+    SidecarNaming::Darktable
+}
+
+pub fn default_tag_xmp() -> XmpWrite {
+    XmpWrite::Sidecar
+}
+
+pub fn default_sc_rating_0() -> Shortcut {
+    Shortcut::from("0", &[])
+}
+
+pub fn default_sc_rating_1() -> Shortcut {
+    Shortcut::from("1", &[])
+}
+
+pub fn default_sc_rating_2() -> Shortcut {
+    Shortcut::from("2", &[])
+}
+
+pub fn default_sc_rating_3() -> Shortcut {
+    Shortcut::from("3", &[])
+}
+
+pub fn default_sc_rating_4() -> Shortcut {
+    Shortcut::from("4", &[])
+}
+
+pub fn default_sc_rating_5() -> Shortcut {
+    Shortcut::from("5", &[])
+}
+
+pub fn default_sc_reject() -> Shortcut {
+    Shortcut::from("x", &[MOD_SHIFT])
+}
+
+pub fn default_sc_label_red() -> Shortcut {
+    Shortcut::from("6", &[])
+}
+
+pub fn default_sc_label_yellow() -> Shortcut {
+    Shortcut::from("7", &[])
+}
+
+pub fn default_sc_label_green() -> Shortcut {
+    Shortcut::from("8", &[])
+}
+
+pub fn default_sc_label_blue() -> Shortcut {
+    Shortcut::from("9", &[])
+}
+
+pub fn default_sc_label_purple() -> Shortcut {
+    Shortcut::from("9", &[MOD_SHIFT])
+}
+
+pub fn default_sc_clear_label() -> Shortcut {
+    Shortcut::from("0", &[MOD_SHIFT])
+}
+
+pub fn default_sc_tag_editor() -> Shortcut {
+    Shortcut::from("t", &[MOD_CTRL])
+}
+
+pub fn default_sc_quick_collection() -> Shortcut {
+    Shortcut::from("b", &[MOD_CTRL])
+}
+
+pub fn default_sc_add_to_album() -> Shortcut {
+    Shortcut::from("a", &[])
+}
+
+pub fn default_sc_remove_from_album() -> Shortcut {
+    Shortcut::from("a", &[MOD_SHIFT])
+}
+
 //Gallery
 pub fn default_nr_loaded_images() -> usize {
     6
@@ -386,8 +1033,159 @@ pub fn default_metadata_tags() -> Vec<String> {
         "Software".to_string(),
     ]
 }
-pub fn default_frame_size_relative_to_image() -> f32 {
-    0.2
+pub fn default_frame_styles() -> Vec<FrameStyle> {
+    vec![
+        frame_style("White", "#FFFFFF", [0.1; 4], false, ""),
+        frame_style(
+            "Mat",
+            "#F4F1EA",
+            [0.08, 0.08, 0.2, 0.08],
+            true,
+            "$(#Camera Model Name#)$( • #Lens Model#)$( • ƒ#Aperture#)$( • #Shutter Speed#)$( • #ISO# ISO)",
+        ),
+        frame_style(
+            "Print",
+            "#FAFAFA",
+            [0.05, 0.05, 0.24, 0.05],
+            true,
+            "$(#Date/Time Original#)",
+        ),
+        frame_style("Black", "#000000", [0.04; 4], false, ""),
+    ]
+}
+///Also accepts the single white frame size of older configs
+fn deserialize_frame_styles<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<FrameStyle>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum FrameStyles {
+        Styles(Vec<FrameStyle>),
+        Size(f32),
+    }
+
+    Ok(match FrameStyles::deserialize(deserializer)? {
+        FrameStyles::Styles(styles) => styles,
+        FrameStyles::Size(size) => {
+            //The old size covered both sides of the image
+            let mut styles = default_frame_styles();
+            styles[0].borders = [size / 2.; 4];
+            styles
+        }
+    })
+}
+fn frame_style(
+    name: &str,
+    color: &str,
+    borders: [f32; 4],
+    shadow: bool,
+    caption_format: &str,
+) -> FrameStyle {
+    FrameStyle {
+        name: name.to_string(),
+        color: color.to_string(),
+        borders,
+        shadow,
+        caption_format: caption_format.to_string(),
+        caption_color: default_frame_caption_color(),
+    }
+}
+pub fn default_frame_color() -> String {
+    "#FFFFFF".to_string()
+}
+pub fn default_frame_caption_color() -> String {
+    "#404040".to_string()
+}
+pub fn default_frame_export_dir() -> String {
+    String::new()
+}
+pub fn default_frame_export_format() -> ExportFormat {
+    ExportFormat::Jpeg
+}
+pub fn default_frame_export_quality() -> u8 {
+    92
+}
+pub fn default_export_dir() -> String {
+    String::new()
+}
+pub fn default_export_filename_template() -> String {
+    "{stem}_export".to_string()
+}
+pub fn default_export_format() -> ExportFormat {
+    ExportFormat::Jpeg
+}
+pub fn default_export_quality() -> u8 {
+    90
+}
+pub fn default_export_resize() -> ExportResize {
+    ExportResize::None
+}
+pub fn default_export_sharpen() -> f32 {
+    0.
+}
+pub fn default_export_icc_profile() -> String {
+    "sRGB".to_string()
+}
+pub fn default_export_metadata() -> ExportMetadata {
+    ExportMetadata::Keep
+}
+pub fn default_contact_sheet_dir() -> String {
+    String::new()
+}
+pub fn default_contact_sheet_format() -> ContactSheetFormat {
+    ContactSheetFormat::Pdf
+}
+pub fn default_contact_sheet_columns() -> usize {
+    4
+}
+pub fn default_contact_sheet_page_size() -> [u32; 2] {
+    //A4 at 300 dpi
+    [2480, 3508]
+}
+pub fn default_contact_sheet_dpi() -> u32 {
+    300
+}
+pub fn default_contact_sheet_margin() -> u32 {
+    120
+}
+pub fn default_contact_sheet_caption_format() -> String {
+    "$(#File Name#)$( • #Shutter Speed#)$( • ƒ#Aperture#)$( • #ISO# ISO)$( • Rating #Rating#)"
+        .to_string()
+}
+pub fn default_contact_sheet_header_format() -> String {
+    "{title} • {date} • {page}/{pages}".to_string()
+}
+pub fn default_web_gallery_dir() -> String {
+    String::new()
+}
+pub fn default_web_gallery_title() -> String {
+    String::new()
+}
+pub fn default_web_gallery_image_long_edge() -> u32 {
+    2048
+}
+pub fn default_web_gallery_thumbnail_long_edge() -> u32 {
+    400
+}
+pub fn default_web_gallery_quality() -> u8 {
+    85
+}
+pub fn default_web_gallery_metadata_tags() -> Vec<String> {
+    [
+        "Date/Time Original",
+        "Model",
+        "Lens Model",
+        "Focal Length",
+        "Aperture",
+        "Shutter Speed",
+        "ISO",
+    ]
+    .iter()
+    .map(|tag| tag.to_string())
+    .collect()
+}
+pub fn default_web_gallery_metadata() -> ExportMetadata {
+    ExportMetadata::StripGps
 }
 pub fn default_scroll_navigation() -> bool {
     true
@@ -395,11 +1193,50 @@ pub fn default_scroll_navigation() -> bool {
 pub fn default_name_format() -> String {
     "$(#File Name#)$( • ƒ#Aperture#)$( • #Shutter Speed#)$( • #ISO# ISO)".to_string()
 }
+pub fn default_keep_pixels() -> bool {
+    false
+}
+pub fn default_persist_view_transform() -> bool {
+    false
+}
+pub fn default_minimap_enabled() -> bool {
+    true
+}
+pub fn default_minimap_corner() -> Corner {
+    Corner::BottomRight
+}
+pub fn default_minimap_size() -> f32 {
+    180.
+}
+pub fn default_minimap_hide_after_secs() -> f32 {
+    2.
+}
+pub fn default_focus_peaking_threshold() -> f32 {
+    0.2
+}
+pub fn default_focus_peaking_color() -> String {
+    "#FF2020".to_string()
+}
+pub fn default_cull_trash_dir() -> String {
+    String::new()
+}
+pub fn default_crop_ratios() -> Vec<String> {
+    vec![
+        "4:5".to_string(),
+        "1:1".to_string(),
+        "16:9".to_string(),
+        "2:3".to_string(),
+    ]
+}
 pub fn default_user_actions() -> Vec<UserAction> {
     vec![]
 }
 pub fn default_ctx_menu() -> Vec<ContextMenuEntry> {
-    vec![]
+    vec![ContextMenuEntry {
+        description: "Export".to_string(),
+        exec: String::new(),
+        callback: Some(Callback::Export(vec![])),
+    }]
 }
 pub fn default_sc_fit() -> Shortcut {
     Shortcut::from("f", &[])
@@ -407,6 +1244,9 @@ pub fn default_sc_fit() -> Shortcut {
 pub fn default_sc_frame() -> Shortcut {
     Shortcut::from("g", &[])
 }
+pub fn default_sc_cycle_frame_styles() -> Shortcut {
+    Shortcut::from("g", &[MOD_SHIFT])
+}
 pub fn default_sc_toggle_side_panel() -> Shortcut {
     Shortcut::from("i", &[])
 }
@@ -444,6 +1284,96 @@ pub fn default_sc_more_images_shown() -> Shortcut {
 pub fn default_sc_less_images_shown() -> Shortcut {
     Shortcut::from("Minus", &[])
 }
+pub fn default_sc_pixel_inspector() -> Shortcut {
+    Shortcut::from("i", &[MOD_CTRL])
+}
+pub fn default_sc_copy_pixel() -> Shortcut {
+    Shortcut::from("c", &[MOD_CTRL])
+}
+pub fn default_sc_exposure_up() -> Shortcut {
+    Shortcut::from("ArrowUp", &[MOD_ALT])
+}
+pub fn default_sc_exposure_down() -> Shortcut {
+    Shortcut::from("ArrowDown", &[MOD_ALT])
+}
+pub fn default_sc_gamma_up() -> Shortcut {
+    Shortcut::from("ArrowRight", &[MOD_ALT])
+}
+pub fn default_sc_gamma_down() -> Shortcut {
+    Shortcut::from("ArrowLeft", &[MOD_ALT])
+}
+pub fn default_sc_contrast_up() -> Shortcut {
+    Shortcut::from("ArrowUp", &[MOD_CTRL, MOD_ALT])
+}
+pub fn default_sc_contrast_down() -> Shortcut {
+    Shortcut::from("ArrowDown", &[MOD_CTRL, MOD_ALT])
+}
+pub fn default_sc_invert() -> Shortcut {
+    Shortcut::from("n", &[MOD_ALT])
+}
+pub fn default_sc_grayscale() -> Shortcut {
+    Shortcut::from("l", &[MOD_ALT])
+}
+pub fn default_sc_red_channel() -> Shortcut {
+    Shortcut::from("r", &[MOD_ALT])
+}
+pub fn default_sc_green_channel() -> Shortcut {
+    Shortcut::from("g", &[MOD_ALT])
+}
+pub fn default_sc_blue_channel() -> Shortcut {
+    Shortcut::from("b", &[MOD_ALT])
+}
+pub fn default_sc_reset_adjustments() -> Shortcut {
+    Shortcut::from("0", &[MOD_ALT])
+}
+pub fn default_sc_rotate_cw() -> Shortcut {
+    Shortcut::from("r", &[])
+}
+pub fn default_sc_rotate_ccw() -> Shortcut {
+    Shortcut::from("r", &[MOD_SHIFT])
+}
+pub fn default_sc_rotate_180() -> Shortcut {
+    Shortcut::from("r", &[MOD_CTRL])
+}
+pub fn default_sc_flip_horizontal() -> Shortcut {
+    Shortcut::from("x", &[])
+}
+pub fn default_sc_flip_vertical() -> Shortcut {
+    Shortcut::from("y", &[])
+}
+pub fn default_sc_write_orientation() -> Shortcut {
+    Shortcut::from("e", &[MOD_CTRL])
+}
+pub fn default_sc_cycle_guides() -> Shortcut {
+    Shortcut::from("o", &[])
+}
+pub fn default_sc_cycle_crop_masks() -> Shortcut {
+    Shortcut::from("c", &[])
+}
+pub fn default_sc_focus_peaking() -> Shortcut {
+    Shortcut::from("p", &[])
+}
+pub fn default_sc_peaking_threshold_up() -> Shortcut {
+    Shortcut::from("CloseBracket", &[])
+}
+pub fn default_sc_peaking_threshold_down() -> Shortcut {
+    Shortcut::from("OpenBracket", &[])
+}
+pub fn default_sc_culling() -> Shortcut {
+    Shortcut::from("k", &[])
+}
+pub fn default_sc_cull_pick() -> Shortcut {
+    Shortcut::from("s", &[])
+}
+pub fn default_sc_cull_reject() -> Shortcut {
+    Shortcut::from("d", &[])
+}
+pub fn default_sc_cull_unflag() -> Shortcut {
+    Shortcut::from("u", &[])
+}
+pub fn default_sc_cull_summary() -> Shortcut {
+    Shortcut::from("e", &[])
+}
 
 //Multi Gallery
 pub fn default_images_per_row() -> usize {
@@ -452,6 +1382,12 @@ pub fn default_images_per_row() -> usize {
 pub fn default_preloaded_rows() -> usize {
     1
 }
+pub fn default_grid_layout() -> GridLayout {
+    GridLayout::Square
+}
+pub fn default_group_by() -> GridGroupBy {
+    GridGroupBy::None
+}
 pub fn default_simultaneous_load() -> usize {
     8
 }
@@ -467,12 +1403,25 @@ pub fn default_sc_more_per_row() -> Shortcut {
 pub fn default_sc_less_per_row() -> Shortcut {
     Shortcut::from("Minus", &[])
 }
+pub fn default_sc_select_all() -> Shortcut {
+    Shortcut::from("a", &[MOD_CTRL])
+}
+pub fn default_sc_invert_selection() -> Shortcut {
+    Shortcut::from("a", &[MOD_CTRL, MOD_SHIFT])
+}
+pub fn default_sc_toggle_layout() -> Shortcut {
+    Shortcut::from("j", &[])
+}
 
 //Filter
 pub fn default_exif_tags() -> Vec<FilterableExifTag> {
     vec![]
 }
 
+pub fn default_saved_search_shortcuts() -> Vec<SavedSearchShortcut> {
+    vec![]
+}
+
 //Slideshow
 pub fn default_seconds_per_image() -> u64 {
     15
@@ -490,6 +1439,74 @@ pub fn default_image_frame_background_color_override() -> Option<String> {
     None
 }
 
+pub fn default_ken_burns_target() -> KenBurnsTarget {
+    KenBurnsTarget::FocusPoint
+}
+
+pub fn default_transition() -> SlideshowTransition {
+    SlideshowTransition::Crossfade
+}
+
+pub fn default_transition_ms() -> u64 {
+    800
+}
+
+pub fn default_shuffle() -> bool {
+    false
+}
+
+pub fn default_repeat() -> bool {
+    true
+}
+
+pub fn default_caption_format() -> String {
+    String::new()
+}
+
+pub fn default_show_clock() -> bool {
+    false
+}
+
+pub fn default_clock_format() -> String {
+    "%H:%M".to_string()
+}
+
+pub fn default_overlay_text_size() -> f32 {
+    24.
+}
+
+pub fn default_quiet_hours_start() -> Option<String> {
+    None
+}
+
+pub fn default_quiet_hours_end() -> Option<String> {
+    None
+}
+
+pub fn default_playlist() -> Option<FilterQuery> {
+    None
+}
+
+pub fn default_playlist_refresh_minutes() -> u64 {
+    60
+}
+
+pub fn default_sc_slideshow_toggle() -> Shortcut {
+    Shortcut::from("F5", &[])
+}
+
+pub fn default_sc_slideshow_pause() -> Shortcut {
+    Shortcut::from("Enter", &[])
+}
+
+pub fn default_sc_slideshow_faster() -> Shortcut {
+    Shortcut::from("Period", &[])
+}
+
+pub fn default_sc_slideshow_slower() -> Shortcut {
+    Shortcut::from("Comma", &[])
+}
+
 //Shortcuts
 pub fn default_shortcut() -> KeyboardShortcut {
     //Bogus shortcut as default so we don't have to use option
@@ -542,3 +1559,45 @@ pub fn build_keyboard_shortcut(mods: &[String], key: &str) -> KeyboardShortcut {
         } //uses default unreachable shortcut
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_frame_size_loads_as_white_frame_style() {
+        let cfg: ImageViewConfig =
+            serde_json::from_str(r#"{ "frame_size_relative_to_image": 0.3 }"#).unwrap();
+        assert_eq!(cfg.frame_styles[0].name, "White");
+        assert_eq!(cfg.frame_styles[0].borders, [0.15; 4]);
+        assert_eq!(cfg.frame_styles.len(), default_frame_styles().len());
+
+        let cfg: ImageViewConfig = serde_json::from_str(
+            r##"{ "frame_styles": [{ "name": "Thin", "borders": [0.01, 0.01, 0.01, 0.01] }] }"##,
+        )
+        .unwrap();
+        assert_eq!(cfg.frame_styles[0].name, "Thin");
+        assert_eq!(cfg.frame_styles[0].color, "#FFFFFF");
+    }
+
+    #[test]
+    fn plain_shortcuts_do_not_match_with_alt_held() {
+        let rating = default_sc_rating_1();
+        assert!(rating.matches_alt(Modifiers::NONE));
+        assert!(rating.matches_alt(Modifiers::SHIFT));
+        assert!(!rating.matches_alt(Modifiers::ALT));
+        assert!(default_sc_one_to_one().matches_alt(Modifiers::ALT));
+        assert!(!default_sc_one_to_one().matches_alt(Modifiers::NONE));
+    }
+
+    #[test]
+    fn copy_shortcut_consumes_the_copy_event() {
+        let mut input = InputState::default();
+        input.events = vec![Event::Copy, Event::Cut];
+
+        assert!(!default_sc_pixel_inspector().consume_or_copy_event(&mut input));
+        assert!(default_sc_copy_pixel().consume_or_copy_event(&mut input));
+        assert_eq!(input.events, vec![Event::Cut]);
+        assert!(!default_sc_copy_pixel().consume_or_copy_event(&mut input));
+    }
+}
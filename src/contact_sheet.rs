@@ -0,0 +1,425 @@
+use crate::config::{ContactSheetConfig, ContactSheetFormat, ExportFormat};
+use crate::db::DbRepository;
+use crate::export;
+use crate::icc::SRGB_V4;
+use crate::image::{Image, LoadSettings, draw_text};
+use crate::metadata::{METADATA_PROFILE_DESCRIPTION, Metadata};
+use chrono::Local;
+use epaint::Pos2;
+use image::codecs::jpeg::JpegEncoder;
+use image::{ImageEncoder, ImageError, Rgb, RgbImage};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+///Thumbnails are converted to sRGB, the profile embedded in the sheets
+const SHEET_ICC_PROFILE: &str = "sRGB";
+const PAGE_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
+const HEADER_COLOR: Rgb<u8> = Rgb([32, 32, 32]);
+const CAPTION_COLOR: Rgb<u8> = Rgb([64, 64, 64]);
+const PDF_JPEG_QUALITY: u8 = 92;
+///Header text size relative to the printable width
+const HEADER_SIZE_R: f32 = 0.02;
+///Caption text size relative to the thumbnail size
+const CAPTION_SIZE_R: f32 = 0.055;
+///Space between thumbnails relative to the printable width
+const GAP_R: u32 = 60;
+
+///Contact sheet of a collection, written by the worker or the `--contact-sheet` subcommand
+#[derive(Debug)]
+pub struct ContactSheetJob {
+    pub paths: Vec<PathBuf>,
+    pub config: ContactSheetConfig,
+    pub settings: LoadSettings,
+    ///Shown in the page headers
+    pub title: String,
+    ///With more than one page, PNG sheets get the page number appended to the file name
+    pub output: PathBuf,
+}
+
+impl ContactSheetJob {
+    ///Timestamped file in the configured directory, or in `fallback_dir` when none is set
+    pub fn default_output(config: &ContactSheetConfig, fallback_dir: &Path) -> PathBuf {
+        let dir = if config.dir.is_empty() {
+            fallback_dir.to_path_buf()
+        } else {
+            PathBuf::from(&config.dir)
+        };
+
+        dir.join(format!(
+            "contact_sheet_{}.{}",
+            Local::now().format("%Y%m%d_%H%M%S"),
+            config.format.extension()
+        ))
+    }
+
+    ///Renders every page, calling progress with the index of the image about to be drawn.
+    ///Returns the written files.
+    pub fn run(&self, db_repo: &mut DbRepository, progress: impl Fn(usize)) -> Vec<PathBuf> {
+        if self.paths.is_empty() {
+            tracing::error!("No images to create a contact sheet from");
+            return vec![];
+        }
+
+        if let Some(parent) = self.output.parent()
+            && let Err(e) = fs::create_dir_all(parent)
+        {
+            tracing::error!("Failure creating contact sheet directory {parent:?} -> {e}");
+            return vec![];
+        }
+
+        let layout = SheetLayout::new(&self.config);
+        let pages = layout.pages(self.paths.len());
+        let date = Local::now().format("%Y-%m-%d").to_string();
+        let mut pdf = PdfWriter::new(self.config.dpi);
+        let mut written = vec![];
+
+        for (page_index, page_paths) in self.paths.chunks(layout.per_page()).enumerate() {
+            let [width, height] = self.config.page_size;
+            let mut page = RgbImage::from_pixel(width, height, PAGE_COLOR);
+
+            let header = self
+                .config
+                .header_format
+                .replace("{title}", &self.title)
+                .replace("{date}", &date)
+                .replace("{page}", &(page_index + 1).to_string())
+                .replace("{pages}", &pages.to_string());
+            draw_text(
+                &mut page,
+                &header,
+                layout.header_font_size,
+                layout.printable_width as f32,
+                layout.header_center(),
+                HEADER_COLOR,
+            );
+
+            for (slot, path) in page_paths.iter().enumerate() {
+                progress(page_index * layout.per_page() + slot);
+                self.draw_thumbnail(&mut page, &layout, slot, path, db_repo);
+            }
+
+            match self.config.format {
+                ContactSheetFormat::Png => {
+                    let path = page_path(&self.output, page_index, pages);
+                    match export::save_image(&page, &path, ExportFormat::Png, 100, SRGB_V4) {
+                        Ok(()) => written.push(path),
+                        Err(e) => tracing::error!("Failure saving contact sheet {path:?} -> {e}"),
+                    }
+                }
+                ContactSheetFormat::Pdf => {
+                    if let Err(e) = pdf.add_page(&page) {
+                        tracing::error!("Failure encoding contact sheet page -> {e}");
+                        return written;
+                    }
+                }
+            }
+        }
+
+        if self.config.format == ContactSheetFormat::Pdf {
+            match fs::write(&self.output, pdf.to_bytes()) {
+                Ok(()) => written.push(self.output.clone()),
+                Err(e) => tracing::error!("Failure saving contact sheet {:?} -> {e}", self.output),
+            }
+        }
+
+        written
+    }
+
+    fn draw_thumbnail(
+        &self,
+        page: &mut RgbImage,
+        layout: &SheetLayout,
+        slot: usize,
+        path: &Path,
+        db_repo: &mut DbRepository,
+    ) {
+        let metadata =
+            Metadata::get_image_metadata(db_repo, &path.to_string_lossy()).unwrap_or_default();
+        let (x, y) = layout.cell_origin(slot);
+        let size = layout.thumbnail_size;
+
+        if let Some(mut image) = Image::decode_thumbnail(path, size, &self.settings, &metadata) {
+            if self.settings.persisted_view_transform {
+                let view_transform = Image::persisted_view_transform(db_repo, path);
+                image = Image::apply_view_transform(image, &view_transform);
+            }
+
+            let mut thumbnail = image.into_rgb8();
+            let source_profile = metadata
+                .get(METADATA_PROFILE_DESCRIPTION)
+                .map(|p| p.as_str())
+                .unwrap_or(SHEET_ICC_PROFILE);
+            Image::apply_cc(
+                source_profile,
+                &mut thumbnail,
+                &path.to_path_buf(),
+                &SHEET_ICC_PROFILE.to_string(),
+            );
+
+            //Centered in its square
+            let offset_x = size.saturating_sub(thumbnail.width()) / 2;
+            let offset_y = size.saturating_sub(thumbnail.height()) / 2;
+            image::imageops::replace(
+                page,
+                &thumbnail,
+                (x + offset_x) as i64,
+                (y + offset_y) as i64,
+            );
+        }
+
+        let mut caption =
+            Metadata::format_string_with_metadata(&self.config.caption_format, &metadata);
+        if caption.is_empty() {
+            caption = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+        }
+
+        draw_text(
+            page,
+            &caption,
+            layout.caption_font_size,
+            size as f32,
+            Pos2::new(
+                (x + size / 2) as f32,
+                (y + size) as f32 + layout.caption_height as f32 / 2.,
+            ),
+            CAPTION_COLOR,
+        );
+    }
+}
+
+///Position of the header and thumbnails on a page, in pixels
+#[derive(Debug, PartialEq)]
+pub struct SheetLayout {
+    columns: u32,
+    rows: u32,
+    margin: u32,
+    gap: u32,
+    printable_width: u32,
+    header_height: u32,
+    header_font_size: f32,
+    ///Side of the square each thumbnail is fitted in
+    thumbnail_size: u32,
+    caption_height: u32,
+    caption_font_size: f32,
+}
+
+impl SheetLayout {
+    pub fn new(config: &ContactSheetConfig) -> SheetLayout {
+        let [width, height] = config.page_size;
+        let columns = (config.columns as u32).max(1);
+        let margin = config.margin.min(width / 4).min(height / 4);
+        let printable_width = width - 2 * margin;
+        let gap = printable_width / GAP_R;
+
+        let header_font_size = printable_width as f32 * HEADER_SIZE_R;
+        let header_height = (header_font_size * 2.5) as u32;
+
+        let thumbnail_size = (printable_width.saturating_sub(gap * (columns - 1)) / columns).max(1);
+        let caption_font_size = thumbnail_size as f32 * CAPTION_SIZE_R;
+        let caption_height = (caption_font_size * 2.) as u32;
+
+        let printable_height = (height - 2 * margin).saturating_sub(header_height);
+        let rows = ((printable_height + gap) / (thumbnail_size + caption_height + gap)).max(1);
+
+        SheetLayout {
+            columns,
+            rows,
+            margin,
+            gap,
+            printable_width,
+            header_height,
+            header_font_size,
+            thumbnail_size,
+            caption_height,
+            caption_font_size,
+        }
+    }
+
+    pub fn per_page(&self) -> usize {
+        (self.columns * self.rows) as usize
+    }
+
+    pub fn pages(&self, images: usize) -> usize {
+        images.div_ceil(self.per_page()).max(1)
+    }
+
+    ///Top left corner of the square the thumbnail in the given slot of a page is fitted in
+    pub fn cell_origin(&self, slot: usize) -> (u32, u32) {
+        let column = slot as u32 % self.columns;
+        let row = slot as u32 / self.columns;
+
+        (
+            self.margin + column * (self.thumbnail_size + self.gap),
+            self.margin
+                + self.header_height
+                + row * (self.thumbnail_size + self.caption_height + self.gap),
+        )
+    }
+
+    fn header_center(&self) -> Pos2 {
+        Pos2::new(
+            (self.margin + self.printable_width / 2) as f32,
+            (self.margin + self.header_height / 2) as f32,
+        )
+    }
+}
+
+fn page_path(output: &Path, page_index: usize, pages: usize) -> PathBuf {
+    if pages == 1 {
+        return output.to_path_buf();
+    }
+
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output.extension().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!("{stem}_{:03}.{extension}", page_index + 1))
+}
+
+///Minimal PDF made of one JPEG image per page, tagged with the sRGB profile
+struct PdfWriter {
+    dpi: u32,
+    ///Width, height and JPEG data of each page
+    pages: Vec<(u32, u32, Vec<u8>)>,
+}
+
+impl PdfWriter {
+    fn new(dpi: u32) -> PdfWriter {
+        PdfWriter {
+            dpi: dpi.max(1),
+            pages: vec![],
+        }
+    }
+
+    fn add_page(&mut self, page: &RgbImage) -> Result<(), ImageError> {
+        let mut jpeg = vec![];
+        JpegEncoder::new_with_quality(&mut jpeg, PDF_JPEG_QUALITY).write_image(
+            page,
+            page.width(),
+            page.height(),
+            image::ExtendedColorType::Rgb8,
+        )?;
+        self.pages.push((page.width(), page.height(), jpeg));
+
+        Ok(())
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        //Objects 1 and 2 are the catalog and page tree, 3 the ICC profile and then each page
+        //takes three: the page itself, its content and its image
+        let page_id = |index: usize| 4 + index * 3;
+        let kids: Vec<String> = (0..self.pages.len())
+            .map(|index| format!("{} 0 R", page_id(index)))
+            .collect();
+
+        let mut objects: Vec<Vec<u8>> = vec![
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                kids.join(" "),
+                self.pages.len()
+            )
+            .into_bytes(),
+            pdf_stream("/N 3", SRGB_V4),
+        ];
+
+        for (index, (width, height, jpeg)) in self.pages.iter().enumerate() {
+            let id = page_id(index);
+            let points = |pixels: u32| pixels as f32 * 72. / self.dpi as f32;
+            let (page_width, page_height) = (points(*width), points(*height));
+
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {page_width:.2} {page_height:.2}] \
+                     /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+                    id + 2,
+                    id + 1
+                )
+                .into_bytes(),
+            );
+            objects.push(pdf_stream(
+                "",
+                format!("q {page_width:.2} 0 0 {page_height:.2} 0 0 cm /Im0 Do Q").as_bytes(),
+            ));
+            objects.push(pdf_stream(
+                &format!(
+                    "/Type /XObject /Subtype /Image /Width {width} /Height {height} \
+                     /ColorSpace [/ICCBased 3 0 R] /BitsPerComponent 8 /Filter /DCTDecode"
+                ),
+                jpeg,
+            ));
+        }
+
+        let mut pdf = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = vec![];
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+            pdf.extend_from_slice(object);
+            pdf.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref_offset = pdf.len();
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(xref, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            xref,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+            objects.len() + 1
+        );
+        pdf.extend_from_slice(xref.as_bytes());
+
+        pdf
+    }
+}
+
+fn pdf_stream(dictionary: &str, data: &[u8]) -> Vec<u8> {
+    let mut stream = format!("<< {dictionary} /Length {} >>\nstream\n", data.len()).into_bytes();
+    stream.extend_from_slice(data);
+    stream.extend_from_slice(b"\nendstream");
+    stream
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_fits_in_the_page() {
+        let config = ContactSheetConfig::default();
+        let layout = SheetLayout::new(&config);
+        let [width, height] = config.page_size;
+
+        let (x, y) = layout.cell_origin(layout.per_page() - 1);
+        assert!(x + layout.thumbnail_size <= width - layout.margin);
+        assert!(y + layout.thumbnail_size + layout.caption_height <= height - layout.margin);
+        assert_eq!(layout.cell_origin(1).1, layout.cell_origin(0).1);
+        assert_eq!(layout.pages(layout.per_page() + 1), 2);
+    }
+
+    #[test]
+    fn pdf_cross_references_point_to_objects() {
+        let mut pdf = PdfWriter::new(300);
+        pdf.add_page(&RgbImage::from_pixel(30, 40, PAGE_COLOR))
+            .unwrap();
+        pdf.add_page(&RgbImage::from_pixel(30, 40, PAGE_COLOR))
+            .unwrap();
+        let bytes = pdf.to_bytes();
+        let text = String::from_utf8_lossy(&bytes);
+
+        assert!(text.contains("/Count 2"));
+
+        let xref = text.rfind("xref\n").unwrap();
+        let entries: Vec<&str> = text[xref..].lines().skip(3).take(9).collect();
+        for (index, entry) in entries.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            let object = format!("{} 0 obj", index + 1);
+            assert_eq!(&bytes[offset..offset + object.len()], object.as_bytes());
+        }
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::{STARTER_STATE_ARGS, VALID_EXTENSIONS};
+use crate::{STARTER_STATE_ARGS, STARTER_VALUE_ARGS, VALID_EXTENSIONS};
 
 pub fn paths_from_args() -> (Vec<PathBuf>, Option<PathBuf>) {
     let mut args: Vec<String> = env::args().collect();
@@ -13,8 +13,11 @@ pub fn paths_from_args() -> (Vec<PathBuf>, Option<PathBuf>) {
     //This could be a little more elegant, but works for now.
     //We should consume the other args at startup
     let mut crawl_args = vec![];
-    for arg in &args {
-        if !STARTER_STATE_ARGS.contains(&arg.as_str()) {
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        if STARTER_VALUE_ARGS.contains(&arg.as_str()) {
+            args_iter.next();
+        } else if !STARTER_STATE_ARGS.contains(&arg.as_str()) {
             crawl_args.push(arg.to_string());
         }
     }
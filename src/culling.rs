@@ -0,0 +1,339 @@
+use crate::db::DbRepository;
+use crate::rating::{self, REJECTED, Ratings};
+use crate::utils::unique_target;
+use chrono::Local;
+use eframe::egui::{self, Align2, Context};
+use rfd::FileDialog;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+///Filter tag for the culling state, 1 when picked, -1 when rejected and 0 otherwise
+pub const CULL_FLAG_TAG: &str = "Cull Flag";
+///Value of a pick in the cull_flag table, rejects are stored as a rejected rating
+const PICKED: i8 = 1;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CullFlag {
+    Pick,
+    Reject,
+}
+
+impl CullFlag {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CullFlag::Pick => "Picked",
+            CullFlag::Reject => "Rejected",
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct CullCounts {
+    pub picks: usize,
+    pub rejects: usize,
+    pub remaining: usize,
+}
+
+///Picks of the opened collection, saved to the database so a session can be resumed later.
+///Rejecting sets the rejected rating, shared with the rating shortcuts and written to XMP.
+pub struct Culling {
+    pub enabled: bool,
+    summary_visible: bool,
+    picks: HashSet<PathBuf>,
+    db_repo: DbRepository,
+    trash_dir: String,
+}
+
+impl Culling {
+    pub fn new(db_repo: &DbRepository, trash_dir: &str) -> Culling {
+        Culling {
+            enabled: false,
+            summary_visible: false,
+            picks: HashSet::new(),
+            db_repo: db_repo.clone(),
+            trash_dir: trash_dir.to_string(),
+        }
+    }
+
+    ///Picks are fetched when culling starts, picking up any previous session
+    pub fn toggle(&mut self, paths: &[PathBuf]) {
+        self.enabled = !self.enabled;
+        self.summary_visible = false;
+
+        if self.enabled {
+            self.load(paths);
+        }
+    }
+
+    pub fn load(&mut self, paths: &[PathBuf]) {
+        let paths: Vec<String> = paths
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+
+        self.picks = match self.db_repo.get_cull_flags(&paths) {
+            Ok(flags) => flags
+                .into_iter()
+                .filter(|(_, flag)| *flag == PICKED)
+                .map(|(path, _)| PathBuf::from(path))
+                .collect(),
+            Err(e) => {
+                tracing::error!("Failure fetching cull flags -> {e}");
+                HashSet::new()
+            }
+        };
+    }
+
+    pub fn get(&self, path: &Path, ratings: &Ratings) -> Option<CullFlag> {
+        if ratings.get(path).is_rejected() {
+            Some(CullFlag::Reject)
+        } else if self.picks.contains(path) {
+            Some(CullFlag::Pick)
+        } else {
+            None
+        }
+    }
+
+    ///Picking or unflagging a rejected image also clears its rejected rating
+    pub fn set(&mut self, path: &Path, flag: Option<CullFlag>, ratings: &mut Ratings) {
+        self.set_picked(path, flag == Some(CullFlag::Pick));
+
+        let rejected = ratings.get(path).is_rejected();
+        if flag == Some(CullFlag::Reject) && !rejected {
+            ratings.set_rating(path, REJECTED);
+        } else if flag != Some(CullFlag::Reject) && rejected {
+            ratings.set_rating(path, 0);
+        }
+    }
+
+    fn set_picked(&mut self, path: &Path, picked: bool) {
+        if picked == self.picks.contains(path) {
+            return;
+        }
+
+        let path_str = path.to_string_lossy();
+        let result = if picked {
+            self.db_repo.save_cull_flag(&path_str, PICKED)
+        } else {
+            self.db_repo.delete_cull_flag(&path_str)
+        };
+
+        if let Err(e) = result {
+            tracing::error!("Failure saving cull flag for {path_str} -> {e}");
+        }
+
+        if picked {
+            self.picks.insert(path.to_path_buf());
+        } else {
+            self.picks.remove(path);
+        }
+    }
+
+    pub fn counts(&self, paths: &[&Path], ratings: &Ratings) -> CullCounts {
+        paths
+            .iter()
+            .fold(CullCounts::default(), |mut counts, path| {
+                match self.get(path, ratings) {
+                    Some(CullFlag::Pick) => counts.picks += 1,
+                    Some(CullFlag::Reject) => counts.rejects += 1,
+                    None => counts.remaining += 1,
+                }
+                counts
+            })
+    }
+
+    ///Counter shown in Image View's bottom bar
+    pub fn label(&self, paths: &[&Path], ratings: &Ratings) -> String {
+        let counts = self.counts(paths, ratings);
+        format!(
+            "Culling: {} picked • {} rejected • {} left",
+            counts.picks, counts.rejects, counts.remaining
+        )
+    }
+
+    pub fn show_summary(&mut self) {
+        self.summary_visible = true;
+    }
+
+    pub fn is_summary_visible(&self) -> bool {
+        self.summary_visible
+    }
+
+    fn flagged(&self, paths: &[&Path], flag: CullFlag, ratings: &Ratings) -> Vec<PathBuf> {
+        paths
+            .iter()
+            .filter(|path| self.get(path, ratings) == Some(flag))
+            .map(|path| path.to_path_buf())
+            .collect()
+    }
+
+    ///Triage summary, returns true when files were moved and the collection should be reloaded
+    pub fn summary_ui(&mut self, ctx: &Context, paths: &[&Path], ratings: &mut Ratings) -> bool {
+        if !self.summary_visible {
+            return false;
+        }
+
+        let counts = self.counts(paths, ratings);
+        let mut open = true;
+        let mut moved = false;
+
+        egui::Window::new("Culling Summary")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                egui::Grid::new("culling_summary_counts")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Picked");
+                        ui.label(counts.picks.to_string());
+                        ui.end_row();
+                        ui.label("Rejected");
+                        ui.label(counts.rejects.to_string());
+                        ui.end_row();
+                        ui.label("Unflagged");
+                        ui.label(counts.remaining.to_string());
+                        ui.end_row();
+                    });
+
+                ui.separator();
+
+                if ui
+                    .add_enabled(
+                        counts.rejects > 0,
+                        egui::Button::new("Move rejects to trash"),
+                    )
+                    .clicked()
+                {
+                    moved |= self.trash_rejects(paths, ratings);
+                }
+
+                if ui
+                    .add_enabled(counts.picks > 0, egui::Button::new("Move picks to folder"))
+                    .clicked()
+                    && let Some(dir) = file_dialog(paths).pick_folder()
+                {
+                    moved |= self.move_picks(paths, &dir, ratings);
+                }
+
+                if ui
+                    .add_enabled(counts.picks > 0, egui::Button::new("Export pick list"))
+                    .clicked()
+                    && let Some(output) = file_dialog(paths)
+                        .set_file_name(format!(
+                            "picks_{}.txt",
+                            Local::now().format("%Y%m%d_%H%M%S")
+                        ))
+                        .save_file()
+                {
+                    self.export_pick_list(paths, &output, ratings);
+                }
+            });
+
+        self.summary_visible = open;
+        moved
+    }
+
+    fn trash_rejects(&mut self, paths: &[&Path], ratings: &mut Ratings) -> bool {
+        let mut moved = false;
+        for path in self.flagged(paths, CullFlag::Reject, ratings) {
+            let result = if self.trash_dir.is_empty() {
+                trash_file(&path)
+            } else {
+                move_file(&path, Path::new(&self.trash_dir)).map(|_| ())
+            };
+
+            match result {
+                Ok(()) => {
+                    ratings.remove(&path);
+                    moved = true;
+                }
+                Err(e) => tracing::error!("Failure moving {path:?} to the trash -> {e}"),
+            }
+        }
+
+        moved
+    }
+
+    fn move_picks(&mut self, paths: &[&Path], dir: &Path, ratings: &Ratings) -> bool {
+        let mut moved = false;
+        for path in self.flagged(paths, CullFlag::Pick, ratings) {
+            match move_file(&path, dir) {
+                Ok(target) => {
+                    //The flag follows the image so the picks stay picked in their new folder
+                    self.set_picked(&path, false);
+                    self.set_picked(&target, true);
+                    moved = true;
+                }
+                Err(e) => tracing::error!("Failure moving {path:?} to {dir:?} -> {e}"),
+            }
+        }
+
+        moved
+    }
+
+    fn export_pick_list(&self, paths: &[&Path], output: &Path, ratings: &Ratings) {
+        let list: String = self
+            .flagged(paths, CullFlag::Pick, ratings)
+            .iter()
+            .map(|path| format!("{}\n", path.to_string_lossy()))
+            .collect();
+
+        match fs::write(output, list) {
+            Ok(()) => tracing::info!("Exported pick list to {output:?}"),
+            Err(e) => tracing::error!("Failure writing pick list {output:?} -> {e}"),
+        }
+    }
+}
+
+fn file_dialog(paths: &[&Path]) -> FileDialog {
+    match paths.first().and_then(|path| path.parent()) {
+        Some(dir) => FileDialog::new().set_directory(dir),
+        None => FileDialog::new(),
+    }
+}
+
+///Moves the image and its XMP sidecar into dir, returning the new image path
+fn move_file(path: &Path, dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+
+    let sidecar = rating::existing_sidecar(path);
+    let target = unique_target(path, dir);
+    rename(path, &target)?;
+
+    if let Some(sidecar) = sidecar {
+        let sidecar_target = unique_target(&sidecar, dir);
+        if let Err(e) = rename(&sidecar, &sidecar_target) {
+            tracing::error!("Failure moving sidecar {sidecar:?} -> {e}");
+        }
+    }
+
+    Ok(target)
+}
+
+///Copies when renaming isn't possible, as across file systems
+fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+///Moves the file and its XMP sidecar to the system trash
+fn trash_file(path: &Path) -> io::Result<()> {
+    let sidecar = rating::existing_sidecar(path);
+    trash::delete(path).map_err(io::Error::other)?;
+
+    if let Some(sidecar) = sidecar
+        && let Err(e) = trash::delete(&sidecar)
+    {
+        tracing::error!("Failure moving sidecar {sidecar:?} to the trash -> {e}");
+    }
+
+    Ok(())
+}
@@ -1,19 +1,49 @@
 use core::fmt;
 use std::{
+    collections::HashMap,
     error::Error,
     path::{Path, PathBuf},
     time::Instant,
     vec,
 };
 
+use itertools::Itertools;
 use r2d2::{Pool, PooledConnection};
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::Result;
+use serde::{Deserialize, Serialize};
 
-use crate::{APPLICATION, ORGANIZATION, QUALIFIER};
+use crate::{
+    APPLICATION, ORGANIZATION, QUALIFIER,
+    culling::CULL_FLAG_TAG,
+    metadata::{
+        METADATA_HIERARCHICAL_SUBJECT, METADATA_LABEL, METADATA_RATING, METADATA_SUBJECT, XmpImport,
+    },
+    quality::{QUALITY_COLUMNS, QualityMetrics, quality_column},
+    rating::{LABEL_TAG, RATING_TAG},
+    tags::{self, KEYWORDS_TAG},
+};
 
 pub const IN_CHUNKS: &usize = &500;
 
+///Path, star rating and color label
+pub type RatingRow = (String, i8, Option<String>);
+
+///Path, image size and orientation as read by exiftool
+pub type ImageSizeRow = (String, Option<String>, Option<String>);
+
+///Path and the value of a tag
+pub type TagValue = (String, Option<String>);
+
+///Path and content hash of an album entry
+pub type AlbumImage = (String, Option<String>);
+
+///Path and the modification time of the file when its metadata was read
+pub type CachedFile = (String, Option<i64>);
+
+///Path and the XMP tags embedded in the image
+pub type EmbeddedXmp = (String, HashMap<String, String>);
+
 #[derive(Clone)]
 pub struct DbRepository {
     pool: Pool<SqliteConnectionManager>,
@@ -51,36 +81,60 @@ impl DbRepository {
                 .join(","),
         );
 
+        //Files read again after they changed get new quality metrics too, also the ones whose
+        //analysis failed
+        q.push_str(" on conflict(path) do update set metadata = excluded.metadata");
+        q.push_str(", quality_failed = null");
+        for (_, column) in QUALITY_COLUMNS {
+            q.push_str(&format!(", {column} = null"));
+        }
+
         conn.execute(&q, ())?;
+
+        //The sidecar tags merged into the old metadata are gone, merge them again
+        let paths = data
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect::<Vec<String>>();
+        for chunk in paths.chunks(*IN_CHUNKS) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            conn.execute(
+                &format!("delete from xmp_sidecar where path in ({placeholders})"),
+                rusqlite::params_from_iter(chunk),
+            )?;
+        }
+
         conn.execute("commit transaction;", ())?;
         tracing::info!(
             "Spent {}ms inserting {} metadata records into db",
             now.elapsed().as_millis(),
-            data.len()
+            paths.len()
         );
 
         Ok(())
     }
 
+    ///Cached paths with the modification time of the file when its metadata was read, None for
+    ///files cached before it was stored
     pub fn get_cached_images_by_paths(
         &mut self,
         paths: &[String],
-    ) -> Result<Vec<String>, Box<dyn Error>> {
-        let mut existing_files: Vec<String> = vec![];
+    ) -> Result<Vec<CachedFile>, Box<dyn Error>> {
+        let mut existing_files = vec![];
         let conn = self.get_sqlite_conn()?;
 
         //safeguard lest we go over the limit. Although unlikely since metadata processing is done in chunks too.
         let chunks: Vec<&[String]> = paths.chunks(*IN_CHUNKS).collect();
         for chunk in chunks {
             let mut q = conn.prepare(&format!(
-                "SELECT path FROM file where path in ({})",
+                "SELECT path, modified FROM file where path in ({})",
                 DbUtilities::arr_param_from(chunk)
             ))?;
 
             let mut paths = q
-                .query_map([], |row| row.get::<_, String>(0))?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
                 .filter_map(|x| x.ok())
-                .collect::<Vec<String>>();
+                .collect::<Vec<CachedFile>>();
 
             existing_files.append(&mut paths);
         }
@@ -88,6 +142,80 @@ impl DbRepository {
         Ok(existing_files)
     }
 
+    ///Saves the modification time, in milliseconds since the epoch, of the files whose metadata
+    ///was read
+    pub fn set_files_modified(&mut self, files: &[(String, i64)]) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.get_sqlite_conn()?;
+        let tx = conn.transaction()?;
+
+        {
+            let mut q = tx.prepare("update file set modified = ?2 where path = ?1")?;
+            for (path, modified) in files {
+                q.execute(rusqlite::params![path, modified])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_image_sizes(
+        &mut self,
+        paths: &[String],
+    ) -> Result<Vec<ImageSizeRow>, Box<dyn Error>> {
+        let mut sizes = vec![];
+        let conn = self.get_sqlite_conn()?;
+
+        for chunk in paths.chunks(*IN_CHUNKS) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut q = conn.prepare(&format!(
+                "select path, json_extract(metadata, '$.\"Image Size\"'),
+                     json_extract(metadata, '$.Orientation')
+                 from file where path in ({placeholders})"
+            ))?;
+
+            let mut chunk_sizes = q
+                .query_map(rusqlite::params_from_iter(chunk), |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })?
+                .filter_map(|x| x.ok())
+                .collect::<Vec<ImageSizeRow>>();
+
+            sizes.append(&mut chunk_sizes);
+        }
+
+        Ok(sizes)
+    }
+
+    ///Value of a tag for each path, as filtered by [`DbUtilities::tag_expression`]
+    pub fn get_tag_values(
+        &mut self,
+        paths: &[String],
+        tag: &str,
+    ) -> Result<Vec<TagValue>, Box<dyn Error>> {
+        let mut values = vec![];
+        let conn = self.get_sqlite_conn()?;
+        let expression = DbUtilities::tag_expression(tag);
+
+        for chunk in paths.chunks(*IN_CHUNKS) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut q = conn.prepare(&format!(
+                "select path, cast({expression} as text) from file where path in ({placeholders})"
+            ))?;
+
+            let mut chunk_values = q
+                .query_map(rusqlite::params_from_iter(chunk), |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?
+                .filter_map(|x| x.ok())
+                .collect::<Vec<TagValue>>();
+
+            values.append(&mut chunk_values);
+        }
+
+        Ok(values)
+    }
+
     pub fn get_image_metadata(&mut self, path: &str) -> Result<Option<String>, Box<dyn Error>> {
         let conn = self.get_sqlite_conn()?;
         let mut q = conn.prepare("select json(metadata) from file where path = ?1")?;
@@ -110,12 +238,138 @@ impl DbRepository {
                 metadata jsonb not null,
                 ts TIMESTAMP not null DEFAULT CURRENT_TIMESTAMP);",
             "create index if not exists file_ts_IDX on file (ts DESC)",
+            "create table if not exists view_transform (
+                path text not null primary key,
+                quarter_turns integer not null,
+                mirrored integer not null);",
+            "create table if not exists rating (
+                path text not null primary key,
+                rating integer not null,
+                label text);",
+            "create table if not exists cull_flag (
+                path text not null primary key,
+                flag integer not null);",
+            "create table if not exists tag (
+                name text not null primary key);",
+            "create table if not exists image_tag (
+                path text not null,
+                tag text not null,
+                primary key (path, tag));",
+            "create index if not exists image_tag_tag_IDX on image_tag (tag)",
+            "create table if not exists album (
+                name text not null primary key);",
+            "create table if not exists album_image (
+                album text not null,
+                path text not null,
+                position integer not null,
+                hash text,
+                primary key (album, path));",
+            "create table if not exists saved_search (
+                name text not null primary key,
+                query text not null);",
+            "create table if not exists xmp_sidecar (
+                path text not null primary key,
+                modified integer not null);",
         ];
 
         for stm in stms {
             conn.execute(stm, ())?;
         }
 
+        //Quality metrics were added after the file table, older databases lack the columns
+        let mut q = conn.prepare("select name from pragma_table_info('file')")?;
+        let columns = q
+            .query_map([], |row| row.get::<_, String>(0))?
+            .filter_map(|x| x.ok())
+            .collect::<Vec<String>>();
+
+        for (_, column) in QUALITY_COLUMNS {
+            if !columns.iter().any(|c| c == column) {
+                conn.execute(&format!("alter table file add column {column} real"), ())?;
+            }
+        }
+
+        //Same for the modification time, which tells when a file has to be read again, and the
+        //mark of a failed quality analysis
+        for column in ["modified", "quality_failed"] {
+            if !columns.iter().any(|c| c == column) {
+                conn.execute(&format!("alter table file add column {column} integer"), ())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    ///Paths missing quality metrics, imported before they existed or not analysed yet. Images
+    ///which failed to be analysed are left out until they change.
+    pub fn get_paths_without_quality_metrics(
+        &mut self,
+        paths: &[String],
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut missing = vec![];
+        let conn = self.get_sqlite_conn()?;
+
+        for chunk in paths.chunks(*IN_CHUNKS) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut q = conn.prepare(&format!(
+                "select path from file
+                 where sharpness is null and quality_failed is null and path in ({placeholders})"
+            ))?;
+
+            let mut chunk_missing = q
+                .query_map(rusqlite::params_from_iter(chunk), |row| {
+                    row.get::<_, String>(0)
+                })?
+                .filter_map(|x| x.ok())
+                .collect::<Vec<String>>();
+
+            missing.append(&mut chunk_missing);
+        }
+
+        Ok(missing)
+    }
+
+    pub fn update_quality_metrics(
+        &mut self,
+        metrics: &[(String, QualityMetrics)],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.get_sqlite_conn()?;
+        let tx = conn.transaction()?;
+
+        {
+            let columns = QUALITY_COLUMNS
+                .iter()
+                .enumerate()
+                .map(|(i, (_, column))| format!("{column} = ?{}", i + 2))
+                .collect::<Vec<String>>()
+                .join(", ");
+            let mut q = tx.prepare(&format!("update file set {columns} where path = ?1"))?;
+
+            for (path, metrics) in metrics {
+                let [sharpness, luminance, highlights, shadows, noise] = metrics.values();
+                q.execute(rusqlite::params![
+                    path, sharpness, luminance, highlights, shadows, noise
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    ///Marks the images which couldn't be decoded for their quality metrics
+    pub fn set_quality_failed(&mut self, paths: &[String]) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.get_sqlite_conn()?;
+        let tx = conn.transaction()?;
+
+        {
+            let mut q = tx.prepare("update file set quality_failed = 1 where path = ?1")?;
+            for path in paths {
+                q.execute([path])?;
+            }
+        }
+
+        tx.commit()?;
         Ok(())
     }
 
@@ -165,6 +419,7 @@ impl DbRepository {
         exif_tags: &[(String, String, SqlOperator)],
         order_tag: &str,
         order_direction: &SqlOrder,
+        limit: Option<u32>,
     ) -> Result<Vec<PathBuf>, Box<dyn Error>> {
         let mut query = String::from("SELECT distinct(path) FROM file WHERE ");
 
@@ -173,8 +428,8 @@ impl DbRepository {
             .filter(|x| !x.1.is_empty())
             .map(|x| {
                 format!(
-                    "json_extract(metadata,'$.{}') {}",
-                    x.0,
+                    "{} {}",
+                    DbUtilities::tag_expression(&x.0),
                     DbUtilities::where_clause_from_str_and_operator(&x.1, &x.2)
                 )
             })
@@ -183,12 +438,16 @@ impl DbRepository {
 
         if !order_tag.is_empty() {
             query += &format!(
-                " ORDER BY  json_extract(metadata,'$.{}') {}",
-                order_tag,
+                " ORDER BY  {} {}",
+                DbUtilities::tag_expression(order_tag),
                 order_direction.get_sql()
             );
         }
 
+        if let Some(limit) = limit {
+            query += &format!(" LIMIT {limit}");
+        }
+
         let conn = self.get_sqlite_conn()?;
         let mut q = conn.prepare(&query)?;
         let paths = q
@@ -205,7 +464,8 @@ impl DbRepository {
         exif_tag: &str,
     ) -> Result<Vec<String>, Box<dyn Error>> {
         let query = format!(
-            "select distinct(json_extract(metadata,'$.{exif_tag}')) as dist from file where dist is not null"
+            "select distinct(cast({} as text)) as dist from file where dist is not null",
+            DbUtilities::tag_expression(exif_tag)
         );
 
         let conn = self.get_sqlite_conn()?;
@@ -225,11 +485,15 @@ impl DbRepository {
         let conn = self.get_sqlite_conn()?;
         let mut q = conn.prepare(query)?;
 
-        let unique_tags = q
+        let mut unique_tags: Vec<String> = q
             .query_map([], |row| row.get::<_, String>(0))?
             .filter_map(|x| x.ok())
             .collect();
 
+        unique_tags.extend(QUALITY_COLUMNS.iter().map(|(tag, _)| tag.to_string()));
+        unique_tags.extend([RATING_TAG, LABEL_TAG, CULL_FLAG_TAG, KEYWORDS_TAG].map(String::from));
+        unique_tags.sort();
+
         Ok(unique_tags)
     }
 
@@ -243,6 +507,21 @@ impl DbRepository {
         })?)
     }
 
+    ///Replaces a single tag of the cached metadata, leaving the rest of the row as it is
+    pub fn update_metadata_tag(
+        &mut self,
+        path: &Path,
+        tag: &str,
+        value: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        conn.execute(
+            "update file set metadata = jsonb_set(metadata, '$.\"' || ?2 || '\"', ?3) where path = ?1",
+            (path.to_str(), tag, value),
+        )?;
+        Ok(())
+    }
+
     pub fn delete_file_by_path(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
         let conn = self.get_sqlite_conn()?;
         conn.execute("delete from file where path = ?1", [path.to_str()])?;
@@ -271,6 +550,527 @@ impl DbRepository {
         Ok(())
     }
 
+    ///Rotation (clockwise quarter turns) and mirroring set by the user in Image View
+    pub fn get_view_transform(&mut self, path: &str) -> Result<Option<(u8, bool)>, Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        let mut q =
+            conn.prepare("select quarter_turns, mirrored from view_transform where path = ?1")?;
+        match q.query_row([path], |row| {
+            Ok((row.get::<_, u8>(0)?, row.get::<_, bool>(1)?))
+        }) {
+            Ok(transform) => Ok(Some(transform)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save_view_transform(
+        &mut self,
+        path: &str,
+        quarter_turns: u8,
+        mirrored: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        conn.execute(
+            "insert into view_transform (path, quarter_turns, mirrored) values (?1, ?2, ?3)
+             on conflict(path) do update set quarter_turns = ?2, mirrored = ?3",
+            rusqlite::params![path, quarter_turns, mirrored],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_view_transform(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        conn.execute("delete from view_transform where path = ?1", [path])?;
+        Ok(())
+    }
+
+    ///Star rating, -1 when rejected, and color label of each rated path
+    pub fn get_ratings(&mut self, paths: &[String]) -> Result<Vec<RatingRow>, Box<dyn Error>> {
+        let mut ratings = vec![];
+        let conn = self.get_sqlite_conn()?;
+
+        for chunk in paths.chunks(*IN_CHUNKS) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut q = conn.prepare(&format!(
+                "select path, rating, label from rating where path in ({placeholders})"
+            ))?;
+
+            let mut chunk_ratings = q
+                .query_map(rusqlite::params_from_iter(chunk), |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })?
+                .filter_map(|x| x.ok())
+                .collect::<Vec<RatingRow>>();
+
+            ratings.append(&mut chunk_ratings);
+        }
+
+        Ok(ratings)
+    }
+
+    pub fn save_rating(
+        &mut self,
+        path: &str,
+        rating: i8,
+        label: Option<&str>,
+    ) -> Result<(), Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        conn.execute(
+            "insert into rating (path, rating, label) values (?1, ?2, ?3)
+             on conflict(path) do update set rating = ?2, label = ?3",
+            rusqlite::params![path, rating, label],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_rating(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        conn.execute("delete from rating where path = ?1", [path])?;
+        Ok(())
+    }
+
+    ///Culling flag of each flagged path, 1 when picked
+    pub fn get_cull_flags(
+        &mut self,
+        paths: &[String],
+    ) -> Result<Vec<(String, i8)>, Box<dyn Error>> {
+        let mut flags = vec![];
+        let conn = self.get_sqlite_conn()?;
+
+        for chunk in paths.chunks(*IN_CHUNKS) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut q = conn.prepare(&format!(
+                "select path, flag from cull_flag where path in ({placeholders})"
+            ))?;
+
+            let mut chunk_flags = q
+                .query_map(rusqlite::params_from_iter(chunk), |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?
+                .filter_map(|x| x.ok())
+                .collect::<Vec<(String, i8)>>();
+
+            flags.append(&mut chunk_flags);
+        }
+
+        Ok(flags)
+    }
+
+    pub fn save_cull_flag(&mut self, path: &str, flag: i8) -> Result<(), Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        conn.execute(
+            "insert into cull_flag (path, flag) values (?1, ?2)
+             on conflict(path) do update set flag = ?2",
+            rusqlite::params![path, flag],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_cull_flag(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        conn.execute("delete from cull_flag where path = ?1", [path])?;
+        Ok(())
+    }
+
+    ///Every known tag, including the ancestors of hierarchical ones
+    pub fn get_all_tags(&mut self) -> Result<Vec<String>, Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        let mut q = conn.prepare("select name from tag order by name")?;
+
+        let tags = q
+            .query_map([], |row| row.get(0))?
+            .filter_map(|x| x.ok())
+            .collect();
+
+        Ok(tags)
+    }
+
+    ///Path and tag of every tag set on the paths
+    pub fn get_image_tags(
+        &mut self,
+        paths: &[String],
+    ) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let mut tags = vec![];
+        let conn = self.get_sqlite_conn()?;
+
+        for chunk in paths.chunks(*IN_CHUNKS) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut q = conn.prepare(&format!(
+                "select path, tag from image_tag where path in ({placeholders}) order by tag"
+            ))?;
+
+            let mut chunk_tags = q
+                .query_map(rusqlite::params_from_iter(chunk), |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?
+                .filter_map(|x| x.ok())
+                .collect::<Vec<(String, String)>>();
+
+            tags.append(&mut chunk_tags);
+        }
+
+        Ok(tags)
+    }
+
+    ///Sets the tag on every path, registering it and its ancestors as known tags
+    pub fn add_tag(&mut self, paths: &[String], tag: &str) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.get_sqlite_conn()?;
+        let tx = conn.transaction()?;
+
+        {
+            let mut save_tag = tx.prepare("insert or ignore into tag (name) values (?1)")?;
+            for ancestor in tags::ancestors(tag) {
+                save_tag.execute([ancestor])?;
+            }
+
+            let mut save_image_tag =
+                tx.prepare("insert or ignore into image_tag (path, tag) values (?1, ?2)")?;
+            for path in paths {
+                save_image_tag.execute([path, tag])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn remove_tag(&mut self, paths: &[String], tag: &str) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.get_sqlite_conn()?;
+        let tx = conn.transaction()?;
+
+        {
+            let mut q = tx.prepare("delete from image_tag where path = ?1 and tag = ?2")?;
+            for path in paths {
+                q.execute([path, tag])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    ///Paths tagged with the tag or any of its descendants
+    pub fn get_paths_with_tag(&mut self, tag: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        let mut q = conn.prepare(
+            "select distinct path from image_tag
+             where tag = ?1 or substr(tag, 1, length(?1) + 1) = ?1 || '/'
+             order by path",
+        )?;
+
+        let paths = q
+            .query_map([tag], |row| row.get::<_, String>(0))?
+            .filter_map(|x| x.ok())
+            .map(PathBuf::from)
+            .collect();
+
+        Ok(paths)
+    }
+
+    ///Name and image count of every album
+    pub fn get_albums(&mut self) -> Result<Vec<(String, u32)>, Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        let mut q = conn.prepare(
+            "select album.name, count(album_image.path) from album
+             left join album_image on album_image.album = album.name
+             group by album.name order by album.name",
+        )?;
+
+        let albums = q
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|x| x.ok())
+            .collect();
+
+        Ok(albums)
+    }
+
+    pub fn create_album(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        conn.execute("insert or ignore into album (name) values (?1)", [name])?;
+        Ok(())
+    }
+
+    pub fn rename_album(&mut self, name: &str, new_name: &str) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.get_sqlite_conn()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "update album set name = ?2 where name = ?1",
+            [name, new_name],
+        )?;
+        tx.execute(
+            "update album_image set album = ?2 where album = ?1",
+            [name, new_name],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn delete_album(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.get_sqlite_conn()?;
+        let tx = conn.transaction()?;
+        tx.execute("delete from album_image where album = ?1", [name])?;
+        tx.execute("delete from album where name = ?1", [name])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    ///Appends the paths, with their content hash, to the end of the album. Paths already in it
+    ///keep their position.
+    pub fn add_to_album(
+        &mut self,
+        album: &str,
+        images: &[AlbumImage],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.get_sqlite_conn()?;
+        let tx = conn.transaction()?;
+
+        {
+            tx.execute("insert or ignore into album (name) values (?1)", [album])?;
+            let mut q = tx.prepare(
+                "insert or ignore into album_image (album, path, position, hash)
+                 values (?1, ?2,
+                     (select coalesce(max(position), -1) + 1 from album_image where album = ?1), ?3)",
+            )?;
+            for (path, hash) in images {
+                q.execute(rusqlite::params![album, path, hash])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn remove_from_album(
+        &mut self,
+        album: &str,
+        paths: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.get_sqlite_conn()?;
+        let tx = conn.transaction()?;
+
+        {
+            let mut q = tx.prepare("delete from album_image where album = ?1 and path = ?2")?;
+            for path in paths {
+                q.execute([album, path])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    ///Path and content hash of every image in the album, in album order
+    pub fn get_album_images(&mut self, album: &str) -> Result<Vec<AlbumImage>, Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        let mut q =
+            conn.prepare("select path, hash from album_image where album = ?1 order by position")?;
+
+        let images = q
+            .query_map([album], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|x| x.ok())
+            .collect();
+
+        Ok(images)
+    }
+
+    ///Points an album entry at the new location of a moved file
+    pub fn relink_album_image(
+        &mut self,
+        album: &str,
+        path: &str,
+        new_path: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.get_sqlite_conn()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "update or ignore album_image set path = ?3 where album = ?1 and path = ?2",
+            [album, path, new_path],
+        )?;
+        //The new path was already in the album
+        tx.execute(
+            "delete from album_image where album = ?1 and path = ?2",
+            [album, path],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    ///Library paths of the images with this file name, wherever they are
+    pub fn get_paths_by_file_name(&mut self, name: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        let mut q = conn.prepare(
+            "select path from file where json_extract(metadata, '$.\"File Name\"') = ?1",
+        )?;
+
+        let paths = q
+            .query_map([name], |row| row.get(0))?
+            .filter_map(|x| x.ok())
+            .collect();
+
+        Ok(paths)
+    }
+
+    ///Name and json filter query of every saved search
+    pub fn get_saved_searches(&mut self) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        let mut q = conn.prepare("select name, query from saved_search order by name")?;
+
+        let searches = q
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|x| x.ok())
+            .collect();
+
+        Ok(searches)
+    }
+
+    ///Saves the search, replacing the one with the same name
+    pub fn save_search(&mut self, name: &str, query: &str) -> Result<(), Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        conn.execute(
+            "insert or replace into saved_search (name, query) values (?1, ?2)",
+            [name, query],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_saved_search(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        let conn = self.get_sqlite_conn()?;
+        conn.execute("delete from saved_search where name = ?1", [name])?;
+        Ok(())
+    }
+
+    ///Rating, label and keywords in the cached metadata, as exiftool read them from embedded XMP
+    pub fn get_embedded_xmp_tags(
+        &mut self,
+        paths: &[String],
+    ) -> Result<Vec<EmbeddedXmp>, Box<dyn Error>> {
+        let tags = [
+            METADATA_RATING,
+            METADATA_LABEL,
+            METADATA_SUBJECT,
+            METADATA_HIERARCHICAL_SUBJECT,
+        ];
+        let columns = tags
+            .iter()
+            .map(|tag| format!("cast(json_extract(metadata, '$.\"{tag}\"') as text)"))
+            .join(", ");
+
+        let mut embedded = vec![];
+        let conn = self.get_sqlite_conn()?;
+
+        for chunk in paths.chunks(*IN_CHUNKS) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut q = conn.prepare(&format!(
+                "select path, {columns} from file where path in ({placeholders})"
+            ))?;
+
+            let mut chunk_embedded = q
+                .query_map(rusqlite::params_from_iter(chunk), |row| {
+                    let mut values = HashMap::new();
+                    for (i, tag) in tags.iter().enumerate() {
+                        if let Some(value) = row.get::<_, Option<String>>(i + 1)? {
+                            values.insert(tag.to_string(), value);
+                        }
+                    }
+                    Ok((row.get(0)?, values))
+                })?
+                .filter_map(|x| x.ok())
+                .collect::<Vec<EmbeddedXmp>>();
+
+            embedded.append(&mut chunk_embedded);
+        }
+
+        Ok(embedded)
+    }
+
+    ///Modification time of the sidecars of each path when they were last imported
+    pub fn get_imported_sidecars(
+        &mut self,
+        paths: &[String],
+    ) -> Result<Vec<(String, i64)>, Box<dyn Error>> {
+        let mut sidecars = vec![];
+        let conn = self.get_sqlite_conn()?;
+
+        for chunk in paths.chunks(*IN_CHUNKS) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut q = conn.prepare(&format!(
+                "select path, modified from xmp_sidecar where path in ({placeholders})"
+            ))?;
+
+            let mut chunk_sidecars = q
+                .query_map(rusqlite::params_from_iter(chunk), |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?
+                .filter_map(|x| x.ok())
+                .collect::<Vec<(String, i64)>>();
+
+            sidecars.append(&mut chunk_sidecars);
+        }
+
+        Ok(sidecars)
+    }
+
+    ///Replaces the rating, label and keywords of the images. Sidecar tags are also merged into
+    ///the image metadata.
+    pub fn import_xmp(&mut self, imports: &[XmpImport]) -> Result<(), Box<dyn Error>> {
+        let mut conn = self.get_sqlite_conn()?;
+        let tx = conn.transaction()?;
+
+        {
+            let mut update_metadata = tx.prepare(
+                "update file set metadata = jsonb_patch(metadata, jsonb(?2)) where path = ?1",
+            )?;
+            let mut save_rating = tx.prepare(
+                "insert into rating (path, rating, label) values (?1, ?2, ?3)
+                 on conflict(path) do update set rating = ?2, label = ?3",
+            )?;
+            let mut delete_rating = tx.prepare("delete from rating where path = ?1")?;
+            let mut delete_image_tags = tx.prepare("delete from image_tag where path = ?1")?;
+            let mut save_tag = tx.prepare("insert or ignore into tag (name) values (?1)")?;
+            let mut save_image_tag =
+                tx.prepare("insert or ignore into image_tag (path, tag) values (?1, ?2)")?;
+            let mut save_sidecar = tx.prepare(
+                "insert into xmp_sidecar (path, modified) values (?1, ?2)
+                 on conflict(path) do update set modified = ?2",
+            )?;
+
+            for import in imports {
+                let path = &import.image_path;
+
+                if import.sidecar_modified.is_some() && !import.tags.is_empty() {
+                    update_metadata.execute(rusqlite::params![
+                        path,
+                        serde_json::to_string(&import.tags)?
+                    ])?;
+                }
+
+                if import.rating == 0 && import.label.is_none() {
+                    delete_rating.execute([path])?;
+                } else {
+                    save_rating.execute(rusqlite::params![path, import.rating, import.label])?;
+                }
+
+                if let Some(keywords) = import.keywords() {
+                    delete_image_tags.execute([path])?;
+                    for keyword in keywords {
+                        for ancestor in tags::ancestors(&keyword) {
+                            save_tag.execute([ancestor])?;
+                        }
+                        save_image_tag.execute([path, &keyword])?;
+                    }
+                }
+
+                if let Some(modified) = import.sidecar_modified {
+                    save_sidecar.execute(rusqlite::params![path, modified])?;
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
     pub fn get_all_file_paths(&mut self) -> Result<Vec<String>, Box<dyn Error>> {
         let query = "SELECT path FROM file";
 
@@ -293,6 +1093,31 @@ impl DbUtilities {
         format!("\"{}\"", &strings.join("\", \""))
     }
 
+    ///Quality metrics have their own columns and ratings their own table, every other tag
+    ///lives in the metadata json
+    pub fn tag_expression(tag: &str) -> String {
+        match (tag, quality_column(tag)) {
+            (_, Some(column)) => column.to_string(),
+            //Unrated images are filtered as 0 stars
+            (RATING_TAG, None) => {
+                "coalesce((select rating from rating where rating.path = file.path), 0)".to_string()
+            }
+            (LABEL_TAG, None) => {
+                "(select label from rating where rating.path = file.path)".to_string()
+            }
+            (KEYWORDS_TAG, None) => {
+                "(select group_concat(tag, ', ') from image_tag where image_tag.path = file.path)"
+                    .to_string()
+            }
+            (CULL_FLAG_TAG, None) => "coalesce(
+                    (select -1 from rating where rating.path = file.path and rating.rating = -1),
+                    (select flag from cull_flag where cull_flag.path = file.path),
+                    0)"
+            .to_string(),
+            _ => format!("json_extract(metadata,'$.{tag}')"),
+        }
+    }
+
     pub fn where_clause_from_str_and_operator(val: &str, operator: &SqlOperator) -> String {
         let is_numeric = val.parse::<f64>().is_ok();
 
@@ -344,7 +1169,7 @@ impl DbUtilities {
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
 pub enum SqlOperator {
     Like,
     Eq,
@@ -382,7 +1207,7 @@ impl fmt::Display for SqlOperator {
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone, Deserialize, Serialize)]
 pub enum SqlOrder {
     Asc,
     Desc,
@@ -0,0 +1,538 @@
+use crate::config::ImageViewConfig;
+use eframe::egui;
+use eframe::egui_wgpu::{self, CallbackResources, CallbackTrait, RenderState};
+use eframe::wgpu::{self, util::DeviceExt};
+use epaint::{Color32, Pos2, Rect};
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+
+const SHADER: &str = include_str!("../resources/display_adjustments.wgsl");
+const UNIFORM_SIZE: u64 = 96;
+
+const EXPOSURE_STEP: f32 = 0.25;
+const EXPOSURE_RANGE: std::ops::RangeInclusive<f32> = -8.0..=8.0;
+const GAMMA_STEP: f32 = 0.1;
+const GAMMA_RANGE: std::ops::RangeInclusive<f32> = 0.1..=5.0;
+const CONTRAST_STEP: f32 = 0.1;
+const CONTRAST_RANGE: std::ops::RangeInclusive<f32> = 0.0..=5.0;
+const PEAKING_THRESHOLD_STEP: f32 = 0.02;
+const PEAKING_THRESHOLD_RANGE: std::ops::RangeInclusive<f32> = 0.02..=1.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Channel {
+    #[default]
+    All,
+    Red,
+    Green,
+    Blue,
+}
+
+///Non-destructive adjustments applied by the GPU when displaying an image
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DisplayAdjustments {
+    ///Exposure offset in stops
+    pub exposure: f32,
+    pub gamma: f32,
+    pub contrast: f32,
+    pub invert: bool,
+    pub grayscale: bool,
+    pub channel: Channel,
+}
+
+impl Default for DisplayAdjustments {
+    fn default() -> Self {
+        DisplayAdjustments {
+            exposure: 0.,
+            gamma: 1.,
+            contrast: 1.,
+            invert: false,
+            grayscale: false,
+            channel: Channel::All,
+        }
+    }
+}
+
+impl DisplayAdjustments {
+    pub fn is_default(&self) -> bool {
+        *self == DisplayAdjustments::default()
+    }
+
+    pub fn reset(&mut self) {
+        *self = DisplayAdjustments::default();
+    }
+
+    pub fn increase_exposure(&mut self) {
+        self.exposure = clamp_step(self.exposure + EXPOSURE_STEP, EXPOSURE_RANGE);
+    }
+
+    pub fn decrease_exposure(&mut self) {
+        self.exposure = clamp_step(self.exposure - EXPOSURE_STEP, EXPOSURE_RANGE);
+    }
+
+    pub fn increase_gamma(&mut self) {
+        self.gamma = clamp_step(self.gamma + GAMMA_STEP, GAMMA_RANGE);
+    }
+
+    pub fn decrease_gamma(&mut self) {
+        self.gamma = clamp_step(self.gamma - GAMMA_STEP, GAMMA_RANGE);
+    }
+
+    pub fn increase_contrast(&mut self) {
+        self.contrast = clamp_step(self.contrast + CONTRAST_STEP, CONTRAST_RANGE);
+    }
+
+    pub fn decrease_contrast(&mut self) {
+        self.contrast = clamp_step(self.contrast - CONTRAST_STEP, CONTRAST_RANGE);
+    }
+
+    pub fn toggle_invert(&mut self) {
+        self.invert = !self.invert;
+    }
+
+    pub fn toggle_grayscale(&mut self) {
+        self.grayscale = !self.grayscale;
+    }
+
+    ///Shows a single channel, selecting the same channel again goes back to all channels
+    pub fn toggle_channel(&mut self, channel: Channel) {
+        self.channel = if self.channel == channel {
+            Channel::All
+        } else {
+            channel
+        };
+    }
+
+    ///Short description of the adjustments that differ from the default
+    pub fn label(&self) -> String {
+        let mut parts: Vec<String> = vec![];
+
+        if self.exposure != 0. {
+            parts.push(format!("EV {:+.2}", self.exposure));
+        }
+        if self.gamma != 1. {
+            parts.push(format!("γ {:.1}", self.gamma));
+        }
+        if self.contrast != 1. {
+            parts.push(format!("Contrast {:.1}", self.contrast));
+        }
+        match self.channel {
+            Channel::All if self.grayscale => parts.push("Gray".to_string()),
+            Channel::All => {}
+            Channel::Red => parts.push("R".to_string()),
+            Channel::Green => parts.push("G".to_string()),
+            Channel::Blue => parts.push("B".to_string()),
+        }
+        if self.invert {
+            parts.push("Inverted".to_string());
+        }
+
+        parts.join(" ")
+    }
+
+    fn uniform_bytes(
+        &self,
+        uv_corners: &[Pos2; 4],
+        peaking: &FocusPeaking,
+        opacity: f32,
+        linear_output: bool,
+    ) -> Vec<u8> {
+        let [top_left, top_right, bottom_left, bottom_right] = uv_corners;
+        let floats = [
+            top_left.x,
+            top_left.y,
+            top_right.x,
+            top_right.y,
+            bottom_left.x,
+            bottom_left.y,
+            bottom_right.x,
+            bottom_right.y,
+            self.exposure,
+            self.gamma,
+            self.contrast,
+            opacity,
+            peaking.color.r() as f32 / 255.,
+            peaking.color.g() as f32 / 255.,
+            peaking.color.b() as f32 / 255.,
+            peaking.threshold,
+        ];
+        let flags = [
+            self.invert as u32,
+            self.grayscale as u32,
+            self.channel as u32,
+            linear_output as u32,
+            peaking.enabled as u32,
+            0,
+            0,
+            0,
+        ];
+
+        let mut bytes = Vec::with_capacity(UNIFORM_SIZE as usize);
+        for f in floats {
+            bytes.extend_from_slice(&f.to_ne_bytes());
+        }
+        for f in flags {
+            bytes.extend_from_slice(&f.to_ne_bytes());
+        }
+
+        bytes
+    }
+}
+
+///Colors the edges above a threshold of sharpness to spot the areas in focus.
+///The edge strength is the Sobel magnitude of the luminance, computed by the GPU on the
+///full resolution texture.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FocusPeaking {
+    pub enabled: bool,
+    ///Minimum edge strength, between 0 and 1, that gets colored
+    pub threshold: f32,
+    pub color: Color32,
+}
+
+impl Default for FocusPeaking {
+    fn default() -> Self {
+        FocusPeaking {
+            enabled: false,
+            threshold: 0.2,
+            color: Color32::RED,
+        }
+    }
+}
+
+impl FocusPeaking {
+    pub fn new(config: &ImageViewConfig) -> FocusPeaking {
+        let color = Color32::from_hex(&config.focus_peaking_color).unwrap_or_else(|_| {
+            tracing::error!(
+                "Invalid focus peaking color({}) in configuration",
+                config.focus_peaking_color
+            );
+            FocusPeaking::default().color
+        });
+
+        FocusPeaking {
+            enabled: false,
+            threshold: clamp_step(config.focus_peaking_threshold, PEAKING_THRESHOLD_RANGE),
+            color,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    pub fn increase_threshold(&mut self) {
+        self.threshold = clamp_step(
+            self.threshold + PEAKING_THRESHOLD_STEP,
+            PEAKING_THRESHOLD_RANGE,
+        );
+    }
+
+    pub fn decrease_threshold(&mut self) {
+        self.threshold = clamp_step(
+            self.threshold - PEAKING_THRESHOLD_STEP,
+            PEAKING_THRESHOLD_RANGE,
+        );
+    }
+
+    pub fn label(&self) -> String {
+        format!("Peaking {:.2}", self.threshold)
+    }
+}
+
+fn clamp_step(value: f32, range: std::ops::RangeInclusive<f32>) -> f32 {
+    //Rounding avoids accumulating float errors when stepping back and forth
+    ((value * 100.).round() / 100.).clamp(*range.start(), *range.end())
+}
+
+///GPU resources shared by every adjusted image, lives in the egui_wgpu callback resources
+pub struct AdjustmentsPipeline {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    ///egui expects gamma output unless the framebuffer is sRGB aware
+    linear_output: bool,
+    frame_nr: u64,
+    bind_groups: HashMap<egui::Id, wgpu::BindGroup>,
+}
+
+impl AdjustmentsPipeline {
+    pub fn register(render_state: &RenderState) {
+        let device = &render_state.device;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("display_adjustments_shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("display_adjustments_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(UNIFORM_SIZE),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("display_adjustments_pipeline_layout"),
+            bind_group_layouts: &[Some(&bind_group_layout)],
+            immediate_size: 0,
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("display_adjustments_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: render_state.target_format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("display_adjustments_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        render_state
+            .renderer
+            .write()
+            .callback_resources
+            .insert(AdjustmentsPipeline {
+                pipeline,
+                bind_group_layout,
+                sampler,
+                linear_output: render_state.target_format.is_srgb(),
+                frame_nr: 0,
+                bind_groups: HashMap::new(),
+            });
+    }
+}
+
+///Paints an image texture through [`AdjustmentsPipeline`]
+pub struct AdjustedImageCallback {
+    id: egui::Id,
+    frame_nr: u64,
+    texture_view: wgpu::TextureView,
+    uv_corners: [Pos2; 4],
+    adjustments: DisplayAdjustments,
+    peaking: FocusPeaking,
+    ///Opacity of the ui the image is painted in, used to fade images in and out
+    opacity: f32,
+}
+
+impl AdjustedImageCallback {
+    pub fn paint(
+        ui: &egui::Ui,
+        id: egui::Id,
+        rect: Rect,
+        texture_view: wgpu::TextureView,
+        uv_corners: [Pos2; 4],
+        adjustments: DisplayAdjustments,
+        peaking: FocusPeaking,
+    ) {
+        let callback = AdjustedImageCallback {
+            id,
+            frame_nr: ui.ctx().cumulative_frame_nr(),
+            texture_view,
+            uv_corners,
+            adjustments,
+            peaking,
+            opacity: ui.opacity(),
+        };
+
+        ui.painter()
+            .add(egui_wgpu::Callback::new_paint_callback(rect, callback));
+    }
+}
+
+impl CallbackTrait for AdjustedImageCallback {
+    fn prepare(
+        &self,
+        device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+        _screen_descriptor: &egui_wgpu::ScreenDescriptor,
+        _egui_encoder: &mut wgpu::CommandEncoder,
+        callback_resources: &mut CallbackResources,
+    ) -> Vec<wgpu::CommandBuffer> {
+        let pipeline = match callback_resources.get_mut::<AdjustmentsPipeline>() {
+            Some(pipeline) => pipeline,
+            None => {
+                tracing::error!("Display adjustments pipeline was not registered");
+                return vec![];
+            }
+        };
+
+        //Bind groups hold on to the image texture, so they only live for a frame
+        if pipeline.frame_nr != self.frame_nr {
+            pipeline.frame_nr = self.frame_nr;
+            pipeline.bind_groups.clear();
+        }
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("display_adjustments_uniform"),
+            contents: &self.adjustments.uniform_bytes(
+                &self.uv_corners,
+                &self.peaking,
+                self.opacity,
+                pipeline.linear_output,
+            ),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("display_adjustments_bind_group"),
+            layout: &pipeline.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&self.texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&pipeline.sampler),
+                },
+            ],
+        });
+
+        pipeline.bind_groups.insert(self.id, bind_group);
+
+        vec![]
+    }
+
+    fn paint(
+        &self,
+        _info: egui::PaintCallbackInfo,
+        render_pass: &mut wgpu::RenderPass<'static>,
+        callback_resources: &CallbackResources,
+    ) {
+        if let Some(pipeline) = callback_resources.get::<AdjustmentsPipeline>()
+            && let Some(bind_group) = pipeline.bind_groups.get(&self.id)
+        {
+            render_pass.set_pipeline(&pipeline.pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..4, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stepping_is_clamped_and_reversible() {
+        let mut adjustments = DisplayAdjustments::default();
+        for _ in 0..3 {
+            adjustments.increase_gamma();
+        }
+        for _ in 0..3 {
+            adjustments.decrease_gamma();
+        }
+        assert!(adjustments.is_default());
+
+        for _ in 0..100 {
+            adjustments.decrease_exposure();
+        }
+        assert_eq!(adjustments.exposure, *EXPOSURE_RANGE.start());
+    }
+
+    #[test]
+    fn toggling_the_same_channel_shows_all_channels() {
+        let mut adjustments = DisplayAdjustments::default();
+        adjustments.toggle_channel(Channel::Red);
+        assert_eq!(adjustments.channel, Channel::Red);
+        adjustments.toggle_channel(Channel::Blue);
+        assert_eq!(adjustments.channel, Channel::Blue);
+        adjustments.toggle_channel(Channel::Blue);
+        assert_eq!(adjustments.channel, Channel::All);
+    }
+
+    #[test]
+    fn uniform_layout_matches_shader() {
+        let adjustments = DisplayAdjustments::default();
+        let corners = [
+            Pos2::new(0., 0.),
+            Pos2::new(1., 0.),
+            Pos2::new(0., 1.),
+            Pos2::new(1., 1.),
+        ];
+        let peaking = FocusPeaking {
+            enabled: true,
+            ..Default::default()
+        };
+        let bytes = adjustments.uniform_bytes(&corners, &peaking, 0.5, true);
+
+        assert_eq!(bytes.len() as u64, UNIFORM_SIZE);
+        //top right corner
+        assert_eq!(bytes[8..12], 1f32.to_ne_bytes());
+        //opacity
+        assert_eq!(bytes[44..48], 0.5f32.to_ne_bytes());
+        //peaking threshold
+        assert_eq!(bytes[60..64], peaking.threshold.to_ne_bytes());
+        //linear output flag
+        assert_eq!(bytes[76..80], 1u32.to_ne_bytes());
+        //peaking flag
+        assert_eq!(bytes[80..84], 1u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn peaking_threshold_stays_in_range() {
+        let mut peaking = FocusPeaking::default();
+        for _ in 0..100 {
+            peaking.decrease_threshold();
+        }
+        assert_eq!(peaking.threshold, *PEAKING_THRESHOLD_RANGE.start());
+
+        peaking.increase_threshold();
+        assert_eq!(peaking.threshold, 0.04);
+    }
+}
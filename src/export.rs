@@ -0,0 +1,362 @@
+use crate::config::{ExportConfig, ExportFormat, ExportMetadata, ExportResize};
+use crate::db::DbRepository;
+use crate::icc::{SRGB_V4, profile_desc_to_icc};
+use crate::image::{Image, LoadSettings};
+use crate::metadata::{METADATA_PROFILE_DESCRIPTION, Metadata};
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::error::{EncodingError, ImageFormatHint};
+use image::{DynamicImage, ExtendedColorType, ImageEncoder, ImageError, RgbImage};
+use jpegxl_rs::encode::ColorEncoding;
+use jpegxl_rs::{EncodeError, encoder_builder};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+///Profile assumed for images which don't declare one
+const DEFAULT_SOURCE_PROFILE: &str = "sRGB";
+///Speed of the AVIF encoder, from 1(slowest) to 10
+const AVIF_SPEED: u8 = 6;
+///Profile written by the formats which can't embed one
+const SRGB_PROFILE: &str = "sRGB";
+///Unsharp mask threshold, differences below it are not sharpened
+const SHARPEN_THRESHOLD: i32 = 1;
+///Characters replaced in file names built from metadata
+const INVALID_FILE_NAME_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+///Images converted in one go by the worker
+#[derive(Debug)]
+pub struct ExportJob {
+    pub paths: Vec<PathBuf>,
+    pub config: ExportConfig,
+    pub settings: LoadSettings,
+}
+
+impl ExportJob {
+    ///Exports every image, calling progress with the index of the image about to be exported.
+    ///Returns the number of exported images.
+    pub fn run(&self, db_repo: &mut DbRepository, progress: impl Fn(usize)) -> usize {
+        let mut exported = 0;
+
+        for (index, path) in self.paths.iter().enumerate() {
+            progress(index);
+
+            if export_image(path, index, &self.config, &self.settings, db_repo).is_some() {
+                exported += 1;
+            }
+        }
+
+        exported
+    }
+}
+
+///Decodes the image at full size, converts it and writes it. Returns the path of the
+///written file.
+pub fn export_image(
+    path: &Path,
+    index: usize,
+    config: &ExportConfig,
+    settings: &LoadSettings,
+    db_repo: &mut DbRepository,
+) -> Option<PathBuf> {
+    let metadata =
+        Metadata::get_image_metadata(db_repo, &path.to_string_lossy()).unwrap_or_default();
+    let image = decode_for_export(path, settings, &metadata, db_repo)?;
+    export_decoded(image, path, index, config, &metadata)
+}
+
+///Decodes the image at full size, with the saved view transform applied when it's persisted
+pub fn decode_for_export(
+    path: &Path,
+    settings: &LoadSettings,
+    metadata: &HashMap<String, String>,
+    db_repo: &mut DbRepository,
+) -> Option<DynamicImage> {
+    let image = Image::decode_full_size(path, settings, metadata)?;
+
+    if settings.persisted_view_transform {
+        let view_transform = Image::persisted_view_transform(db_repo, path);
+        Some(Image::apply_view_transform(image, &view_transform))
+    } else {
+        Some(image)
+    }
+}
+
+///Resizes, converts and writes an image decoded by `decode_for_export`, so one decode can be
+///exported with several configs. Returns the path of the written file.
+pub fn export_decoded(
+    image: DynamicImage,
+    path: &Path,
+    index: usize,
+    config: &ExportConfig,
+    metadata: &HashMap<String, String>,
+) -> Option<PathBuf> {
+    let now = Instant::now();
+
+    let output_path = output_path(path, index, config, metadata)?;
+    if output_path == path {
+        tracing::error!("{path:?} -> Export would overwrite the original image, skipping");
+        return None;
+    }
+
+    let icc_profile = output_icc_profile(config);
+    let icc = match profile_desc_to_icc(icc_profile) {
+        Some(icc) => icc,
+        None => {
+            tracing::error!("Badly configured export ICC profile -> {icc_profile}");
+            return None;
+        }
+    };
+
+    let mut image = resize(image, config.resize);
+
+    if config.sharpen > 0. {
+        image = image.unsharpen(config.sharpen, SHARPEN_THRESHOLD);
+    }
+
+    let mut image = image.into_rgb8();
+    let source_profile = metadata
+        .get(METADATA_PROFILE_DESCRIPTION)
+        .map(|p| p.as_str())
+        .unwrap_or(DEFAULT_SOURCE_PROFILE);
+    Image::apply_cc(
+        source_profile,
+        &mut image,
+        &path.to_path_buf(),
+        &icc_profile.to_string(),
+    );
+
+    if let Some(parent) = output_path.parent()
+        && let Err(e) = fs::create_dir_all(parent)
+    {
+        tracing::error!("Failure creating export directory {parent:?} -> {e}");
+        return None;
+    }
+
+    if let Err(e) = save_image(&image, &output_path, config.format, config.quality, icc) {
+        tracing::error!("Failure saving exported image to {output_path:?} -> {e}");
+        return None;
+    }
+
+    if config.metadata != ExportMetadata::StripAll {
+        let strip_gps = config.metadata == ExportMetadata::StripGps;
+        Metadata::copy_metadata(path, &output_path, strip_gps);
+    }
+
+    tracing::info!(
+        "{output_path:?} -> Spent {}ms exporting",
+        now.elapsed().as_millis()
+    );
+
+    Some(output_path)
+}
+
+///The configured profile, or sRGB when the format can't embed it as the image would be read as sRGB
+fn output_icc_profile(config: &ExportConfig) -> &str {
+    if config.format.embeds_icc_profile()
+        || profile_desc_to_icc(&config.icc_profile) == Some(SRGB_V4.as_slice())
+    {
+        return &config.icc_profile;
+    }
+
+    tracing::warn!(
+        "{:?} can't embed {}, converting to sRGB instead",
+        config.format,
+        config.icc_profile
+    );
+    SRGB_PROFILE
+}
+
+///Longest side the image should be resized to, None when it's already small enough
+pub fn target_long_edge(resize: ExportResize, width: u32, height: u32) -> Option<u32> {
+    let long_edge = width.max(height);
+
+    let target = match resize {
+        ExportResize::None => return None,
+        ExportResize::LongEdge(target) => target,
+        ExportResize::Megapixels(megapixels) => {
+            let scale = (megapixels as f64 * 1_000_000. / (width as f64 * height as f64)).sqrt();
+            (long_edge as f64 * scale).round() as u32
+        }
+    };
+
+    (target > 0 && target < long_edge).then_some(target)
+}
+
+fn resize(image: DynamicImage, resize: ExportResize) -> DynamicImage {
+    match target_long_edge(resize, image.width(), image.height()) {
+        Some(long_edge) => Image::resize(image, Some(long_edge)),
+        None => image,
+    }
+}
+
+///Builds the output path from the filename template. `{stem}` is replaced by the original file
+///name without extension, `{n}` by the position of the image in the export and `$(#exif_tag#)`
+///expressions by the image metadata.
+pub fn output_path(
+    path: &Path,
+    index: usize,
+    config: &ExportConfig,
+    metadata: &HashMap<String, String>,
+) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let template = config
+        .filename_template
+        .replace("{stem}", &stem)
+        .replace("{n}", &format!("{:04}", index + 1));
+    let file_name = Metadata::format_string_with_metadata(&template, metadata)
+        .replace(INVALID_FILE_NAME_CHARS, "_");
+
+    if file_name.trim().is_empty() {
+        tracing::error!("{path:?} -> Export filename template resulted in an empty name");
+        return None;
+    }
+
+    let dir = if config.dir.is_empty() {
+        path.parent()?.to_path_buf()
+    } else {
+        PathBuf::from(&config.dir)
+    };
+
+    Some(dir.join(format!("{file_name}.{}", config.format.extension())))
+}
+
+///Encodes the image, embedding the ICC profile when the format allows it. Formats without
+///ICC support are read as sRGB, the pixels should already be in sRGB for them.
+pub fn save_image(
+    image: &RgbImage,
+    path: &Path,
+    format: ExportFormat,
+    quality: u8,
+    icc: &[u8],
+) -> Result<(), ImageError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let (width, height) = image.dimensions();
+    let color_type = ExtendedColorType::Rgb8;
+    let quality = quality.clamp(1, 100);
+
+    match format {
+        ExportFormat::Jpeg => {
+            let mut encoder = JpegEncoder::new_with_quality(writer, quality);
+            set_icc_profile(&mut encoder, icc, format);
+            encoder.write_image(image, width, height, color_type)
+        }
+        ExportFormat::Png => {
+            let mut encoder = PngEncoder::new(writer);
+            set_icc_profile(&mut encoder, icc, format);
+            encoder.write_image(image, width, height, color_type)
+        }
+        ExportFormat::WebP => {
+            //Only the lossless encoder is available, quality doesn't apply
+            let mut encoder = WebPEncoder::new_lossless(writer);
+            set_icc_profile(&mut encoder, icc, format);
+            encoder.write_image(image, width, height, color_type)
+        }
+        ExportFormat::Avif => {
+            let mut encoder = AvifEncoder::new_with_speed_quality(writer, AVIF_SPEED, quality);
+            set_icc_profile(&mut encoder, icc, format);
+            encoder.write_image(image, width, height, color_type)
+        }
+        ExportFormat::Jxl => {
+            let mut encoder = encoder_builder()
+                .jpeg_quality(quality as f32)
+                .color_encoding(ColorEncoding::Srgb)
+                .build()
+                .map_err(jxl_encoding_error)?;
+            let encoded = encoder
+                .encode::<u8, u8>(image.as_raw(), width, height)
+                .map_err(jxl_encoding_error)?;
+
+            writer.write_all(&encoded)?;
+            writer.flush()?;
+            Ok(())
+        }
+    }
+}
+
+fn set_icc_profile(encoder: &mut impl ImageEncoder, icc: &[u8], format: ExportFormat) {
+    if encoder.set_icc_profile(icc.to_vec()).is_err() {
+        tracing::warn!(
+            "{format:?} does not support embedding ICC profiles, it will be read as sRGB"
+        );
+    }
+}
+
+fn jxl_encoding_error(e: EncodeError) -> ImageError {
+    ImageError::Encoding(EncodingError::new(
+        ImageFormatHint::Name("JPEG XL".to_string()),
+        e,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resize_targets_the_long_edge() {
+        assert_eq!(target_long_edge(ExportResize::None, 6000, 4000), None);
+        assert_eq!(
+            target_long_edge(ExportResize::LongEdge(2048), 4000, 6000),
+            Some(2048)
+        );
+        assert_eq!(
+            target_long_edge(ExportResize::LongEdge(8000), 6000, 4000),
+            None
+        );
+        assert_eq!(
+            target_long_edge(ExportResize::Megapixels(6.), 6000, 4000),
+            Some(3000)
+        );
+    }
+
+    #[test]
+    fn formats_without_icc_support_are_converted_to_srgb() {
+        let config = ExportConfig {
+            format: ExportFormat::Jxl,
+            icc_profile: "Display P3".to_string(),
+            ..ExportConfig::default()
+        };
+        assert_eq!(output_icc_profile(&config), SRGB_PROFILE);
+
+        let config = ExportConfig {
+            format: ExportFormat::Avif,
+            icc_profile: "srgb".to_string(),
+            ..ExportConfig::default()
+        };
+        assert_eq!(output_icc_profile(&config), "srgb");
+
+        let config = ExportConfig {
+            format: ExportFormat::Png,
+            icc_profile: "Adobe RGB".to_string(),
+            ..ExportConfig::default()
+        };
+        assert_eq!(output_icc_profile(&config), "Adobe RGB");
+    }
+
+    #[test]
+    fn output_path_follows_the_template() {
+        let config = ExportConfig {
+            dir: "/tmp/export".to_string(),
+            filename_template: "{n}_{stem}$(_#Model#)".to_string(),
+            format: ExportFormat::WebP,
+            ..ExportConfig::default()
+        };
+        let mut metadata = HashMap::new();
+        metadata.insert("Model".to_string(), "X100V/T".to_string());
+
+        let path = output_path(Path::new("/photos/DSCF0001.RAF"), 2, &config, &metadata);
+        assert_eq!(
+            path,
+            Some(PathBuf::from("/tmp/export/0003_DSCF0001_X100V_T.webp"))
+        );
+
+        let config = ExportConfig::default();
+        let path = output_path(Path::new("/photos/a.jpg"), 0, &config, &HashMap::new());
+        assert_eq!(path, Some(PathBuf::from("/photos/a_export.jpg")));
+    }
+}
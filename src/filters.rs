@@ -1,10 +1,13 @@
-use crate::config::FilterConfig;
+use crate::config::{FilterConfig, SavedSearchShortcut};
 use crate::db::{DbRepository, SqlOperator, SqlOrder};
 use crate::dropdown::DropDownBox;
 use crate::metadata::{METADATA_DATE, METADATA_DIRECTORY, Metadata};
+use crate::utils;
 use crate::worker::Worker;
+use chrono::{Datelike, Local, NaiveDate};
 use eframe::egui;
 use eframe::egui::{Align, Id, Layout};
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
@@ -22,6 +25,9 @@ pub struct Filters {
     worker: Arc<Mutex<Worker>>,
     group_raw_jpeg: bool,
     db_repo: DbRepository,
+    saved_searches: Vec<(String, FilterQuery)>,
+    saved_search_shortcuts: Vec<SavedSearchShortcut>,
+    search_name: String,
 }
 
 pub struct FilterField {
@@ -72,6 +78,118 @@ impl FilterField {
 pub struct OrderField {
     tag: String,
     order: SqlOrder,
+    ///Maximum number of results, 0 returns every match
+    limit: u32,
+}
+
+///A filter and order query over the library which can be saved in the configuration
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct FilterQuery {
+    pub filters: Vec<QueryFilter>,
+    #[serde(default = "default_query_order_tag")]
+    pub order_tag: String,
+    #[serde(default = "default_query_order")]
+    pub order: SqlOrder,
+    ///Maximum number of results, 0 returns every match
+    #[serde(default)]
+    pub limit: u32,
+    #[serde(default = "default_query_group_raw_jpeg")]
+    pub group_raw_jpeg: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct QueryFilter {
+    pub tag: String,
+    pub operator: SqlOperator,
+    ///`{year}`, `{month}` and `{day}` are replaced with today's date when the query runs
+    pub value: String,
+}
+
+fn default_query_order_tag() -> String {
+    METADATA_DATE.to_string()
+}
+
+fn default_query_order() -> SqlOrder {
+    SqlOrder::Desc
+}
+
+fn default_query_group_raw_jpeg() -> bool {
+    true
+}
+
+impl FilterQuery {
+    ///Runs the saved search with this name, None when it doesn't exist or matches nothing
+    pub fn run_saved(repo: &mut DbRepository, name: &str) -> Option<Vec<PathBuf>> {
+        let query = match saved_searches(repo).into_iter().find(|(n, _)| n == name) {
+            Some((_, query)) => query,
+            None => {
+                tracing::error!("No saved search named {name}");
+                return None;
+            }
+        };
+
+        query.run(repo)
+    }
+
+    pub fn run(&self, repo: &mut DbRepository) -> Option<Vec<PathBuf>> {
+        let today = Local::now().date_naive();
+        let fields: Vec<(String, String, SqlOperator)> = self
+            .filters
+            .iter()
+            .filter(|f| !f.value.is_empty() && !f.tag.is_empty())
+            .map(|f| {
+                (
+                    f.tag.clone(),
+                    expand_date_placeholders(&f.value, today),
+                    f.operator.clone(),
+                )
+            })
+            .collect();
+
+        if fields.is_empty() {
+            return None;
+        }
+
+        let limit = Some(self.limit).filter(|l| *l > 0);
+        let paths = repo
+            .get_paths_filtered_by_metadata(&fields, &self.order_tag, &self.order, limit)
+            .inspect_err(|e| tracing::error!("Failure running filter query: {e}"))
+            .ok()?;
+
+        if self.group_raw_jpeg {
+            Some(Metadata::group_raw_jpg_paths(&paths))
+        } else {
+            Some(paths)
+        }
+    }
+}
+
+fn saved_searches(repo: &mut DbRepository) -> Vec<(String, FilterQuery)> {
+    match repo.get_saved_searches() {
+        Ok(searches) => searches
+            .into_iter()
+            .filter_map(|(name, query)| match serde_json::from_str(&query) {
+                Ok(query) => Some((name, query)),
+                Err(e) => {
+                    tracing::error!("Failure parsing saved search {name} -> {e}");
+                    None
+                }
+            })
+            .collect(),
+        Err(e) => {
+            tracing::error!("Failure fetching saved searches -> {e}");
+            vec![]
+        }
+    }
+}
+
+///Dates are stored by exiftool as `YYYY:MM:DD HH:MM:SS`, so `:{month}:{day} ` with the `In`
+///operator matches the photos taken on this day of any year
+fn expand_date_placeholders(value: &str, today: NaiveDate) -> String {
+    value
+        .replace("{year}", &format!("{:04}", today.year()))
+        .replace("{month}", &format!("{:02}", today.month()))
+        .replace("{day}", &format!("{:02}", today.day()))
 }
 
 impl Filters {
@@ -88,6 +206,9 @@ impl Filters {
         let unique_exif_tags_job =
             Some(thread::spawn(move || job_repo.get_unique_exif_tags().ok()));
 
+        let mut repo = db_repo.clone();
+        let saved_searches = saved_searches(&mut repo);
+
         let mut ffs: Vec<FilterField> = filter_config
             .exif_tags
             .iter()
@@ -100,6 +221,7 @@ impl Filters {
             order_field: OrderField {
                 tag: String::from(METADATA_DATE),
                 order: SqlOrder::Desc,
+                limit: 0,
             },
             imgs_in_db: 0,
             imgs_in_db_job,
@@ -110,6 +232,9 @@ impl Filters {
             worker,
             group_raw_jpeg: true,
             db_repo: db_repo.clone(),
+            saved_searches,
+            saved_search_shortcuts: filter_config.saved_search_shortcuts,
+            search_name: String::new(),
         }
     }
 
@@ -123,9 +248,7 @@ impl Filters {
         }
     }
 
-    pub fn ui(&mut self, ui: &mut egui::Ui) -> Option<Vec<PathBuf>> {
-        let mut return_paths: Option<Vec<PathBuf>> = None;
-
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
         self.finish_imgs_in_db_job();
         self.finish_unique_filter_tags_job();
 
@@ -240,6 +363,12 @@ impl Filters {
                     });
             });
 
+            ui.horizontal(|ui| {
+                ui.label("Limit");
+                ui.add(egui::DragValue::new(&mut self.order_field.limit).speed(1.))
+                    .on_hover_text("0 returns every match");
+            });
+
             ui.add_space(10.);
 
             ui.checkbox(&mut self.group_raw_jpeg, "Group RAW + JPEG");
@@ -249,58 +378,11 @@ impl Filters {
             ui.horizontal(|ui| {
                 ui.with_layout(egui::Layout::left_to_right(egui::Align::Min), |ui| {
                     if ui.button("Filter").clicked() {
-                        let fields: Vec<(String, String, SqlOperator)> = self
-                            .filter_fields
-                            .iter()
-                            .filter(|x| !x.value.is_empty() && !x.name.is_empty())
-                            .map(|x| (x.name.clone(), x.value.clone(), x.operator.clone()))
-                            .collect();
-                        if !fields.is_empty() {
-                            let order_tag = self.order_field.tag.clone();
-                            let order_direction = self.order_field.order.clone();
-                            let group_raw_jpeg = self.group_raw_jpeg;
-                            let mut repo = self.db_repo.clone();
-                            let worker_mutex = self.worker.clone();
-                            self.query_handle = Some(thread::spawn(move || {
-                                let mut filtered_paths = repo
-                                    .get_paths_filtered_by_metadata(
-                                        &fields,
-                                        &order_tag,
-                                        &order_direction,
-                                    )
-                                    .ok();
-
-                                if let Some(paths) = filtered_paths.clone() {
-                                    if let Ok(worker) = worker_mutex.try_lock() {
-                                        worker.send_job(crate::worker::Job::ClearMovedFiles(
-                                            paths.clone(),
-                                        ));
-                                    } else {
-                                        tracing::error!("Failure locking worker mutex to clear moved files");
-                                    }
-
-                                    if group_raw_jpeg {
-                                        filtered_paths =
-                                            Some(Metadata::group_raw_jpg_paths(&paths));
-                                    }
-                                }
-
-                                filtered_paths
-                            }));
-                        }
+                        self.start_query(self.query());
                     }
 
                     if self.query_handle.is_some() {
-                        let qh = self.query_handle.take().unwrap();
-                        if qh.is_finished() {
-                            if let Ok(Some(paths)) = qh.join() {
-                                self.last_query_count = Some(paths.len() as u32);
-                                return_paths = Some(paths.clone());
-                            }
-                        } else {
-                            self.query_handle = Some(qh);
-                            ui.spinner();
-                        }
+                        ui.spinner();
                     }
                 });
 
@@ -312,9 +394,186 @@ impl Filters {
                     }
                 });
             });
+
+            ui.add_space(10.);
+            self.saved_searches_ui(ui);
         });
+    }
+
+    fn saved_searches_ui(&mut self, ui: &mut egui::Ui) {
+        ui.strong("Saved Searches");
 
-        return_paths
+        let mut opened: Option<String> = None;
+        let mut deleted: Option<String> = None;
+        for (name, _) in &self.saved_searches {
+            let resp = ui.selectable_label(false, name);
+            if resp.clicked() {
+                opened = Some(name.clone());
+            }
+            resp.context_menu(|ui| {
+                if ui.button("Delete").clicked() {
+                    deleted = Some(name.clone());
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.search_name)
+                    .hint_text("Name")
+                    .desired_width(120.),
+            );
+
+            let name = self.search_name.trim().to_string();
+            let query = self.query();
+            if ui
+                .add_enabled(
+                    !name.is_empty() && !query.filters.is_empty(),
+                    egui::Button::new("Save"),
+                )
+                .on_hover_text("Saves the filters and order, replacing a search with the same name")
+                .clicked()
+            {
+                self.save_search(&name, &query);
+                self.search_name.clear();
+            }
+        });
+
+        if let Some(name) = deleted {
+            self.delete_search(&name);
+        }
+
+        if let Some(name) = opened {
+            self.open_saved_search(&name);
+        }
+    }
+
+    ///Opens the saved searches bound to a shortcut
+    pub fn handle_input(&mut self, ctx: &egui::Context) {
+        if utils::are_inputs_muted(ctx) {
+            return;
+        }
+
+        let opened = self
+            .saved_search_shortcuts
+            .iter()
+            .find(|sc| ctx.input_mut(|i| i.consume_shortcut(&sc.shortcut.kbd_shortcut)))
+            .map(|sc| sc.search.clone());
+
+        if let Some(name) = opened {
+            self.open_saved_search(&name);
+        }
+    }
+
+    ///Runs the query in the background, its results are taken with `poll_query`
+    fn start_query(&mut self, query: FilterQuery) {
+        if query.filters.is_empty() {
+            return;
+        }
+
+        let mut repo = self.db_repo.clone();
+        let worker_mutex = self.worker.clone();
+        self.query_handle = Some(thread::spawn(move || {
+            let filtered_paths = query.run(&mut repo);
+
+            if let Some(paths) = &filtered_paths {
+                if let Ok(worker) = worker_mutex.try_lock() {
+                    worker.send_job(crate::worker::Job::ClearMovedFiles(paths.clone()));
+                } else {
+                    tracing::error!("Failure locking worker mutex to clear moved files");
+                }
+            }
+
+            filtered_paths
+        }));
+    }
+
+    ///Paths of the last query once it finishes
+    pub fn poll_query(&mut self) -> Option<Vec<PathBuf>> {
+        let qh = self.query_handle.take()?;
+        if !qh.is_finished() {
+            self.query_handle = Some(qh);
+            return None;
+        }
+
+        let paths = qh.join().ok().flatten()?;
+        self.last_query_count = Some(paths.len() as u32);
+        Some(paths)
+    }
+
+    ///Fills the panel with the saved search and runs it again
+    pub fn open_saved_search(&mut self, name: &str) {
+        if let Some(query) = self.fill_saved_search(name) {
+            self.start_query(query);
+        }
+    }
+
+    ///Fills the panel with the saved search without running it
+    pub fn fill_saved_search(&mut self, name: &str) -> Option<FilterQuery> {
+        let Some((_, query)) = self.saved_searches.iter().find(|(n, _)| n == name) else {
+            tracing::error!("No saved search named {name}");
+            return None;
+        };
+
+        let query = query.clone();
+        self.set_query(&query);
+        Some(query)
+    }
+
+    fn set_query(&mut self, query: &FilterQuery) {
+        self.filter_fields = query
+            .filters
+            .iter()
+            .map(|f| {
+                let mut field = FilterField::new(&f.tag, &f.value, &self.db_repo);
+                field.operator = f.operator.clone();
+                field
+            })
+            .collect();
+        self.order_field = OrderField {
+            tag: query.order_tag.clone(),
+            order: query.order.clone(),
+            limit: query.limit,
+        };
+        self.group_raw_jpeg = query.group_raw_jpeg;
+    }
+
+    fn save_search(&mut self, name: &str, query: &FilterQuery) {
+        let saved = serde_json::to_string(query)
+            .map_err(|e| e.into())
+            .and_then(|json| self.db_repo.save_search(name, &json));
+
+        match saved {
+            Ok(_) => self.saved_searches = saved_searches(&mut self.db_repo),
+            Err(e) => tracing::error!("Failure saving search {name} -> {e}"),
+        }
+    }
+
+    fn delete_search(&mut self, name: &str) {
+        match self.db_repo.delete_saved_search(name) {
+            Ok(_) => self.saved_searches.retain(|(n, _)| n != name),
+            Err(e) => tracing::error!("Failure deleting saved search {name} -> {e}"),
+        }
+    }
+
+    ///Query built from the filters currently set in the panel
+    pub fn query(&self) -> FilterQuery {
+        FilterQuery {
+            filters: self
+                .filter_fields
+                .iter()
+                .filter(|x| !x.value.is_empty() && !x.name.is_empty())
+                .map(|x| QueryFilter {
+                    tag: x.name.clone(),
+                    operator: x.operator.clone(),
+                    value: x.value.clone(),
+                })
+                .collect(),
+            order_tag: self.order_field.tag.clone(),
+            order: self.order_field.order.clone(),
+            limit: self.order_field.limit,
+            group_raw_jpeg: self.group_raw_jpeg,
+        }
     }
 
     pub fn finish_imgs_in_db_job(&mut self) {
@@ -343,3 +602,50 @@ impl Filters {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_date_placeholders() {
+        let today = NaiveDate::from_ymd_opt(2026, 5, 7).unwrap();
+        assert_eq!(
+            expand_date_placeholders(":{month}:{day} ", today),
+            ":05:07 "
+        );
+        assert_eq!(expand_date_placeholders("{year}", today), "2026");
+        assert_eq!(expand_date_placeholders("Fujifilm", today), "Fujifilm");
+    }
+
+    #[test]
+    fn parses_saved_query() {
+        let query: FilterQuery = serde_json::from_str(
+            r#"{"filters": [{"tag": "Rating", "operator": "EqBiggerThan", "value": "4"}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(query.filters[0].operator, SqlOperator::EqBiggerThan);
+        assert_eq!(query.order_tag, METADATA_DATE);
+        assert_eq!(query.limit, 0);
+        assert!(query.group_raw_jpeg);
+    }
+
+    #[test]
+    fn saved_query_round_trips() {
+        let query = FilterQuery {
+            filters: vec![QueryFilter {
+                tag: "Keywords".to_string(),
+                operator: SqlOperator::Like,
+                value: "Places/Lisbon".to_string(),
+            }],
+            order_tag: "Rating".to_string(),
+            order: SqlOrder::Asc,
+            limit: 20,
+            group_raw_jpeg: false,
+        };
+
+        let json = serde_json::to_string(&query).unwrap();
+        assert_eq!(serde_json::from_str::<FilterQuery>(&json).unwrap(), query);
+    }
+}
@@ -1,10 +1,15 @@
+use crate::config::{FrameStyle, ImageViewConfig};
+use crate::display_adjustments::{AdjustedImageCallback, DisplayAdjustments, FocusPeaking};
 use crate::image_store::ImageStore;
 use crate::metadata;
+use crate::overlay::{MinimapSettings, Overlays};
+use crate::view_transform::ViewTransform;
 use eframe::egui::load::SizedTexture;
-use eframe::egui::{self, Rect, RichText, Widget, vec2};
-use eframe::epaint::{Pos2, Vec2};
+use eframe::egui::{self, Color32, FontId, Rect, RichText, Sense, vec2};
+use eframe::epaint::{Pos2, Shadow, Vec2};
 use std;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 pub struct GalleryImageSizing {
     pub zoom_factor: f32,
@@ -13,9 +18,79 @@ pub struct GalleryImageSizing {
     pub has_maximized: bool,
 }
 
+///Caption text size relative to the bottom border
+pub const CAPTION_SIZE_R: f32 = 0.3;
+
+///Border drawn around the image, like a print
 pub struct GalleryImageFrame {
     pub enabled: bool,
-    pub size_r: f32,
+    styles: Vec<FramePreset>,
+    selected: usize,
+}
+
+impl GalleryImageFrame {
+    pub fn new(config: &ImageViewConfig) -> GalleryImageFrame {
+        GalleryImageFrame {
+            enabled: false,
+            styles: config.frame_styles.iter().map(FramePreset::new).collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn style(&self) -> Option<&FramePreset> {
+        self.styles.get(self.selected)
+    }
+
+    pub fn cycle_style(&mut self) {
+        if !self.styles.is_empty() {
+            self.selected = (self.selected + 1) % self.styles.len();
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FramePreset {
+    pub name: String,
+    pub color: Color32,
+    ///Top, right, bottom and left, relative to the shortest side of the image
+    borders: [f32; 4],
+    shadow: bool,
+    pub caption_format: String,
+    pub caption_color: Color32,
+}
+
+impl FramePreset {
+    pub fn new(style: &FrameStyle) -> FramePreset {
+        let parse_color = |hex: &str, fallback: Color32| {
+            Color32::from_hex(hex).unwrap_or_else(|_| {
+                tracing::error!("Invalid color({hex}) in frame style {}", style.name);
+                fallback
+            })
+        };
+
+        FramePreset {
+            name: style.name.clone(),
+            color: parse_color(&style.color, Color32::WHITE),
+            borders: style.borders.map(|b| b.max(0.)),
+            shadow: style.shadow,
+            caption_format: style.caption_format.clone(),
+            caption_color: parse_color(&style.caption_color, Color32::DARK_GRAY),
+        }
+    }
+
+    ///Scales the image down so that it fits in the available space along with its borders.
+    ///Returns the image size and the top, right, bottom and left borders in points.
+    pub fn layout(&self, image_size: Vec2, available: Vec2) -> (Vec2, [f32; 4]) {
+        let [top, right, bottom, left] = self.borders;
+        let shortest = image_size.min_elem();
+        let framed_size = image_size + Vec2::new(left + right, top + bottom) * shortest;
+        let scale = (available / framed_size).min_elem().min(1.);
+
+        (
+            image_size * scale,
+            self.borders.map(|b| b * shortest * scale),
+        )
+    }
 }
 
 pub struct GalleryImage {
@@ -23,12 +98,23 @@ pub struct GalleryImage {
     pub name: String,
     pub display_name: Option<String>,
     scroll_pos: Pos2,
+    ///Normalized point to center the view on in the next frame
+    scroll_center: Option<Pos2>,
     display_metadata: Option<Vec<(String, String)>>,
+    ///Frame caption and the format it was built from
+    frame_caption: Option<(String, String)>,
     pub prev_percentage_zoom: f32,
     pub prev_available_size: Vec2,
     ///prev target size before zoom
     pub prev_target_size: Vec2,
     pub prev_cursor_pos_normalized: Vec2,
+    ///Pointer position over the image in normalized image coordinates
+    hovered_uv: Option<Pos2>,
+    ///None until the image is loaded and the persisted transform is known
+    view_transform: Option<ViewTransform>,
+    minimap_last_activity: Instant,
+    minimap_prev_visible_rect: Option<Rect>,
+    minimap_hovered: bool,
     is_loaded: bool,
 }
 
@@ -44,12 +130,19 @@ impl GalleryImage {
                     .to_string_lossy()
                     .to_string(),
                 scroll_pos: Pos2::new(0.0, 0.0),
+                scroll_center: None,
                 display_metadata: None,
+                frame_caption: None,
                 display_name: None,
                 prev_percentage_zoom: 0.,
                 prev_available_size: vec2(0., 0.),
                 prev_target_size: vec2(0., 0.),
                 prev_cursor_pos_normalized: vec2(0., 0.),
+                hovered_uv: None,
+                view_transform: None,
+                minimap_last_activity: Instant::now(),
+                minimap_prev_visible_rect: None,
+                minimap_hovered: false,
                 is_loaded: false,
             })
             .collect()
@@ -60,8 +153,12 @@ impl GalleryImage {
         ui: &mut egui::Ui,
         frame: &GalleryImageFrame,
         sizing: &mut GalleryImageSizing,
+        adjustments: &DisplayAdjustments,
+        overlays: &Overlays,
         image_store: &ImageStore,
     ) {
+        self.hovered_uv = None;
+
         let image_size = match image_store.get_image_size(&self.path) {
             Some(is) => is,
             None => {
@@ -80,6 +177,13 @@ impl GalleryImage {
 
         self.is_loaded = true;
 
+        if self.view_transform.is_none() {
+            self.view_transform = image_store.get_view_transform(&self.path);
+        }
+
+        let texture = SizedTexture::new(texture_id, image_size);
+        let image_size = self.view_transform().apply_to_size(image_size);
+
         let original_size = image_size;
         let mut target_size = image_size;
         let aspect_ratio = image_size.x / image_size.y;
@@ -155,6 +259,10 @@ impl GalleryImage {
             visible_rect.max.x = target_size.x - remain_x;
         }
 
+        if let Some(center) = self.scroll_center.take() {
+            self.scroll_pos = ((center.to_vec2() - Vec2::splat(0.5)) * target_size).to_pos2();
+        }
+
         Self::update_panning_pos(
             &mut self.scroll_pos,
             &mut visible_rect,
@@ -173,50 +281,316 @@ impl GalleryImage {
             },
         };
 
-        if frame.enabled {
-            //we use the shortest side
-            let stroke = if display_size[0] > display_size[1] {
-                display_size[1] * frame.size_r
-            } else {
-                display_size[0] * frame.size_r
+        let (rect, response) = match frame.style().filter(|_| frame.enabled) {
+            Some(style) => {
+                let (image_size, [top, right, bottom, left]) =
+                    style.layout(display_size, ui.available_size());
+                let (frame_rect, _) = ui.allocate_exact_size(
+                    image_size + Vec2::new(left + right, top + bottom),
+                    Sense::hover(),
+                );
+                let rect = Rect::from_min_size(frame_rect.min + Vec2::new(left, top), image_size);
+                self.paint_frame(ui, style, frame_rect, rect, image_store);
+
+                let id = ui.id().with(&self.path).with("framed");
+                (rect, ui.interact(rect, id, Sense::hover()))
+            }
+            None => ui.allocate_exact_size(display_size, Sense::hover()),
+        };
+
+        self.paint_texture(
+            ui,
+            rect,
+            texture,
+            visible_rect_normalized,
+            adjustments,
+            &overlays.focus_peaking,
+            "image",
+            image_store,
+        );
+        self.set_hovered_uv(&response, &visible_rect_normalized);
+        overlays.paint(ui.painter(), rect, &visible_rect_normalized, image_size);
+        self.minimap_ui(
+            ui,
+            rect,
+            texture,
+            image_size,
+            target_size,
+            &visible_rect_normalized,
+            adjustments,
+            &overlays.minimap,
+            image_store,
+        );
+    }
+
+    fn paint_frame(
+        &mut self,
+        ui: &egui::Ui,
+        style: &FramePreset,
+        frame_rect: Rect,
+        image_rect: Rect,
+        image_store: &ImageStore,
+    ) {
+        let painter = ui.painter();
+
+        if style.shadow {
+            let shadow = Shadow {
+                offset: [0, 6],
+                blur: 24,
+                spread: 0,
+                color: Color32::from_black_alpha(140),
             };
+            painter.add(shadow.as_shape(frame_rect, 0.));
+        }
+        painter.rect_filled(frame_rect, 0., style.color);
+
+        let bottom_border = frame_rect.max.y - image_rect.max.y;
+        if style.caption_format.is_empty() || bottom_border <= 0. {
+            return;
+        }
 
-            let aspect_ratio = display_size[0] / display_size[1];
-
-            //Need to do some more debugging here, behaves differently when
-            //No title bar is present
-            display_size[0] -= stroke;
-            display_size[1] -= stroke / aspect_ratio;
-
-            let image = egui::Image::new(SizedTexture::new(texture_id, image_size))
-                .fit_to_exact_size(vec2(display_size[0], display_size[1]))
-                .maintain_aspect_ratio(false)
-                .uv(visible_rect_normalized);
-
-            let available = ui.available_rect_before_wrap();
-            let offset_x = available.center().x - (display_size[0] + stroke) / 2.0;
-            let offset_y = available.center().y - (display_size[1] + stroke) / 2.0;
-
-            ui.painter().rect_filled(
-                Rect {
-                    min: Pos2::new(offset_x, offset_y),
-                    max: Pos2::new(
-                        offset_x + display_size[0] + stroke,
-                        offset_y + display_size[1] + stroke,
-                    ),
-                },
-                1.,
-                egui::Color32::WHITE,
+        let caption = self.frame_caption(&style.caption_format, image_store);
+        if caption.is_empty() {
+            return;
+        }
+
+        let mut font_size = bottom_border * CAPTION_SIZE_R;
+        let mut galley = painter.layout_no_wrap(
+            caption.clone(),
+            FontId::proportional(font_size),
+            style.caption_color,
+        );
+
+        //Long captions are shrunk to fit in the frame
+        let max_width = image_rect.width();
+        if galley.size().x > max_width {
+            font_size *= max_width / galley.size().x;
+            galley = painter.layout_no_wrap(
+                caption,
+                FontId::proportional(font_size),
+                style.caption_color,
             );
+        }
 
-            ui.add(image);
-        } else {
-            egui::Image::new(SizedTexture::new(texture_id, image_size))
-                .uv(visible_rect_normalized)
-                .fit_to_exact_size(vec2(display_size[0], display_size[1]))
-                .maintain_aspect_ratio(false)
-                .ui(ui);
+        let center = Pos2::new(image_rect.center().x, image_rect.max.y + bottom_border / 2.);
+        painter.galley(center - galley.size() / 2., galley, style.caption_color);
+    }
+
+    fn frame_caption(&mut self, format: &str, image_store: &ImageStore) -> String {
+        if let Some((caption_format, caption)) = &self.frame_caption
+            && caption_format == format
+        {
+            return caption.clone();
+        }
+
+        //Metadata may still be loading
+        let metadata = match image_store.get_image_metadata(&self.path) {
+            Some(metadata) => metadata,
+            None => return String::new(),
+        };
+
+        let caption = metadata::Metadata::format_string_with_metadata(format, metadata);
+        self.frame_caption = Some((format.to_string(), caption.clone()));
+
+        caption
+    }
+
+    ///Uses a plain egui image unless there are adjustments or a rotation to apply, in which case
+    ///the texture is drawn by our own shader
+    #[allow(clippy::too_many_arguments)]
+    fn paint_texture(
+        &self,
+        ui: &egui::Ui,
+        rect: Rect,
+        texture: SizedTexture,
+        uv: Rect,
+        adjustments: &DisplayAdjustments,
+        peaking: &FocusPeaking,
+        id_salt: &str,
+        image_store: &ImageStore,
+    ) {
+        if !ui.is_rect_visible(rect) {
+            return;
+        }
+
+        let view_transform = self.view_transform();
+        match image_store.get_texture_view(&self.path) {
+            Some(texture_view)
+                if !adjustments.is_default()
+                    || !view_transform.is_identity()
+                    || peaking.enabled =>
+            {
+                AdjustedImageCallback::paint(
+                    ui,
+                    ui.id().with(&self.path).with(id_salt),
+                    rect,
+                    texture_view,
+                    view_transform.uv_corners(&uv),
+                    *adjustments,
+                    *peaking,
+                );
+            }
+            _ => egui::Image::new(texture).uv(uv).paint_at(ui, rect),
+        }
+    }
+
+    ///Thumbnail of the whole image with the visible region marked, shown while zoomed in.
+    ///Pressing or dragging on it pans the image.
+    #[allow(clippy::too_many_arguments)]
+    fn minimap_ui(
+        &mut self,
+        ui: &egui::Ui,
+        image_rect: Rect,
+        texture: SizedTexture,
+        image_size: Vec2,
+        target_size: Vec2,
+        visible_rect_normalized: &Rect,
+        adjustments: &DisplayAdjustments,
+        minimap: &MinimapSettings,
+        image_store: &ImageStore,
+    ) {
+        self.minimap_hovered = false;
+
+        let is_cropped =
+            visible_rect_normalized.width() < 0.999 || visible_rect_normalized.height() < 0.999;
+        if !minimap.enabled || !is_cropped {
+            self.minimap_prev_visible_rect = None;
+            return;
+        }
+
+        let rect = minimap.rect(image_rect, image_size);
+
+        //Panning or hovering the minimap counts as activity
+        if self.minimap_prev_visible_rect != Some(*visible_rect_normalized)
+            || ui.rect_contains_pointer(rect)
+        {
+            self.minimap_prev_visible_rect = Some(*visible_rect_normalized);
+            self.minimap_last_activity = Instant::now();
+        }
+
+        if minimap.hide_after_secs > 0. {
+            let elapsed = self.minimap_last_activity.elapsed().as_secs_f32();
+            if elapsed > minimap.hide_after_secs {
+                return;
+            }
+            ui.ctx()
+                .request_repaint_after(Duration::from_secs_f32(minimap.hide_after_secs - elapsed));
+        }
+
+        let response = ui.interact(
+            rect,
+            ui.id().with(&self.path).with("minimap"),
+            Sense::drag(),
+        );
+        self.minimap_hovered = response.hovered() || response.dragged();
+
+        if response.is_pointer_button_down_on()
+            && let Some(pos) = response.interact_pointer_pos()
+        {
+            //Centers the visible region on the pointer
+            let center = ((pos - rect.min) / rect.size()).clamp(Vec2::ZERO, Vec2::splat(1.));
+            let free_space = target_size - visible_rect_normalized.size() * target_size;
+            let scroll_pos = (center - Vec2::splat(0.5)) * target_size;
+            self.scroll_pos = Pos2::new(
+                scroll_pos.x.clamp(-free_space.x / 2., free_space.x / 2.),
+                scroll_pos.y.clamp(-free_space.y / 2., free_space.y / 2.),
+            );
+            ui.ctx().request_repaint();
+        }
+
+        ui.painter()
+            .rect_filled(rect.expand(2.), 2., egui::Color32::from_black_alpha(180));
+        self.paint_texture(
+            ui,
+            rect,
+            texture,
+            Rect::from_min_max(Pos2::ZERO, Pos2::new(1., 1.)),
+            adjustments,
+            &FocusPeaking::default(),
+            "minimap",
+            image_store,
+        );
+
+        let visible = Rect::from_min_max(
+            rect.min + visible_rect_normalized.min.to_vec2() * rect.size(),
+            rect.min + visible_rect_normalized.max.to_vec2() * rect.size(),
+        );
+        ui.painter().rect_stroke(
+            visible.intersect(rect),
+            0.,
+            egui::Stroke::new(1.5, egui::Color32::WHITE),
+            egui::StrokeKind::Inside,
+        );
+    }
+
+    ///Pointer is over the minimap or dragging it, image panning should be ignored
+    pub fn is_minimap_hovered(&self) -> bool {
+        self.minimap_hovered
+    }
+
+    ///Maps the pointer position on screen back to normalized image coordinates through the
+    ///visible part of the image
+    fn set_hovered_uv(&mut self, response: &egui::Response, visible_rect_normalized: &Rect) {
+        let view_transform = self.view_transform();
+        self.hovered_uv = response.hover_pos().map(|pos| {
+            let rel = (pos - response.rect.min) / response.rect.size();
+            view_transform.to_texture_uv(Pos2::new(
+                visible_rect_normalized.min.x + rel.x * visible_rect_normalized.width(),
+                visible_rect_normalized.min.y + rel.y * visible_rect_normalized.height(),
+            ))
+        });
+    }
+
+    pub fn view_transform(&self) -> ViewTransform {
+        self.view_transform.unwrap_or_default()
+    }
+
+    pub fn set_view_transform(&mut self, view_transform: ViewTransform) {
+        self.view_transform = Some(view_transform);
+    }
+
+    ///Image size as displayed, after rotation
+    pub fn oriented_image_size(&self, image_store: &ImageStore) -> Option<Vec2> {
+        image_store
+            .get_image_size(&self.path)
+            .map(|size| self.view_transform().apply_to_size(size))
+    }
+
+    pub fn hovered_uv(&self) -> Option<Pos2> {
+        self.hovered_uv
+    }
+
+    ///Centers the view on normalized coordinates of the displayed image, as far as the zoom
+    ///allows it
+    pub fn set_scroll_center(&mut self, center: Pos2) {
+        self.scroll_center = Some(center);
+    }
+
+    ///Size of the image fitted to the available space, before any zoom
+    fn fitted_size(&self, available: Vec2, image_store: &ImageStore) -> Option<Vec2> {
+        let size = self.oriented_image_size(image_store)?;
+        if size.min_elem() <= 0. || available.min_elem() <= 0. {
+            return None;
         }
+
+        Some(size * (available / size).min_elem().min(1.))
+    }
+
+    ///Zoom factor at which the image fills the available space without being cropped
+    pub fn maximized_zoom_factor(&self, available: Vec2, image_store: &ImageStore) -> Option<f32> {
+        self.fitted_size(available, image_store)
+            .map(|fitted| (available / fitted).min_elem())
+    }
+
+    ///Fraction of the image visible on each axis at a zoom factor
+    pub fn visible_fraction(
+        &self,
+        available: Vec2,
+        zoom_factor: f32,
+        image_store: &ImageStore,
+    ) -> Option<Vec2> {
+        self.fitted_size(available, image_store)
+            .map(|fitted| (available / (fitted * zoom_factor)).min(Vec2::splat(1.)))
     }
 
     ///If there is free space, the scroll position will be moved
@@ -342,3 +716,29 @@ impl GalleryImage {
         self.is_loaded
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn framed_image_fits_in_the_available_space() {
+        let style = FramePreset::new(&FrameStyle {
+            name: "Mat".to_string(),
+            color: "#FFFFFF".to_string(),
+            borders: [0.1, 0.1, 0.3, 0.1],
+            shadow: false,
+            caption_format: String::new(),
+            caption_color: "#000000".to_string(),
+        });
+
+        let (image_size, [top, right, bottom, left]) =
+            style.layout(Vec2::new(300., 200.), Vec2::new(300., 200.));
+        let framed_size = image_size + Vec2::new(left + right, top + bottom);
+
+        assert_eq!(framed_size.y, 200.);
+        assert!(framed_size.x <= 300.);
+        assert_eq!(image_size.x / image_size.y, 1.5);
+        assert_eq!(bottom, top * 3.);
+    }
+}
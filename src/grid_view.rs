@@ -1,45 +1,138 @@
 use crate::{
-    callback::Callback, config::GridViewConfig, image_store::ImageStore,
-    thumbnail_image::ThumbnailImage, user_action::show_context_menu, utils,
+    callback::Callback,
+    config::{GridGroupBy, GridLayout, GridViewConfig},
+    db::DbRepository,
+    image_store::ImageStore,
+    metadata::{METADATA_CAMERA_MODEL, METADATA_DATE, Metadata},
+    rating::Ratings,
+    thumbnail_image::ThumbnailImage,
+    user_action::{self, show_context_menu},
+    utils,
 };
 use eframe::{
-    egui::{self, Ui, scroll_area::ScrollSource},
+    egui::{
+        self, Align, Align2, Id, Key, Layout, Modifiers, Order, Pos2, Rect, RichText, Sense,
+        Stroke, StrokeKind, Ui, UiBuilder, collapsing_header::paint_default_icon,
+        scroll_area::ScrollSource,
+    },
     epaint::Vec2,
 };
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+///Pointer travel before a ctrl or shift drag becomes a rubber band instead of a click
+const RUBBER_BAND_MIN_DRAG: f32 = 6.;
+
+enum GridRow {
+    Header(usize),
+    Images(Range<usize>),
+}
+
+///Images sharing a date, directory, camera or tag value
+#[derive(Debug, PartialEq)]
+struct Group {
+    label: String,
+    images: Range<usize>,
+}
+
+enum HeaderAction {
+    ToggleCollapsed,
+    ToggleSelection,
+}
 
 pub struct GridView {
     imgs: Vec<ThumbnailImage>,
     config: GridViewConfig,
     selected_image_name: Option<String>,
+    hovered_image_path: Option<PathBuf>,
+    ///Last image toggled with ctrl + click, where shift + click ranges start
+    selection_anchor: Option<usize>,
+    rubber_band_start: Option<Pos2>,
+    rubber_band: Option<Rect>,
+    ///Keyboard cursor, moved with the arrow keys and opened with Enter
+    focused: Option<usize>,
+    scroll_to_focused: bool,
     prev_img_size: f32,
     prev_scroll_offset: f32,
-    total_rows: usize,
+    prev_viewport_height: f32,
+    visible_rows: usize,
+    rows: Vec<GridRow>,
+    ///Top of each row and the bottom of the last one, rows are as high as the images or headers
+    row_tops: Vec<f32>,
+    ///Row showing each image, the header of its group when collapsed
+    image_row: Vec<usize>,
+    header_height: f32,
+    layout: GridLayout,
+    group_by: GridGroupBy,
+    ///Tag typed in the group menu
+    group_tag: String,
+    groups: Vec<Group>,
+    collapsed: HashSet<String>,
+    ///Set when grouping changed the display order
+    reordered: bool,
+    ///Sum of the aspect ratios filling a justified row, its width over its height
+    row_aspect_ratio: f32,
+    aspect_ratios_loaded: bool,
     images_per_row: usize,
-    prev_images_per_row: usize,
-    prev_row_range_start: usize,
+    ///First image of the first visible row, kept in view when the rows change
+    first_visible: usize,
+    ///Scroll offset past the top of the row of the first visible image, in row heights
+    first_visible_offset: f32,
+    anchor_scroll: bool,
     reset_scroll: bool,
-    callback: Option<Callback>,
+    callbacks: Vec<Callback>,
+    db_repo: DbRepository,
 }
 
 impl GridView {
-    pub fn new(image_paths: &[PathBuf], config: GridViewConfig) -> GridView {
+    pub fn new(
+        image_paths: &[PathBuf],
+        config: GridViewConfig,
+        db_repo: &DbRepository,
+    ) -> GridView {
         let imgs = ThumbnailImage::from_paths(image_paths);
         let mut mg = GridView {
-            total_rows: 0,
+            rows: vec![],
+            row_tops: vec![],
+            image_row: vec![],
+            header_height: 0.,
+            layout: config.layout,
+            group_tag: match &config.group_by {
+                GridGroupBy::Tag(tag) => tag.clone(),
+                _ => String::new(),
+            },
+            group_by: config.group_by.clone(),
+            groups: vec![],
+            collapsed: HashSet::new(),
+            reordered: false,
+            row_aspect_ratio: config.images_per_row as f32,
+            aspect_ratios_loaded: false,
             imgs,
             selected_image_name: None,
+            hovered_image_path: None,
+            selection_anchor: None,
+            rubber_band_start: None,
+            rubber_band: None,
+            focused: None,
+            scroll_to_focused: false,
             images_per_row: config.images_per_row,
-            prev_images_per_row: config.images_per_row,
             config,
             prev_img_size: 0.,
             prev_scroll_offset: 0.,
-            prev_row_range_start: 0,
+            prev_viewport_height: 0.,
+            visible_rows: 0,
+            first_visible: 0,
+            first_visible_offset: 0.,
+            anchor_scroll: false,
             reset_scroll: false,
-            callback: None,
+            callbacks: vec![],
+            db_repo: db_repo.clone(),
         };
 
-        mg.set_total_rows();
+        mg.regroup();
 
         mg
     }
@@ -49,8 +142,11 @@ impl GridView {
             image_store.deregister_img(&img.path);
         }
         self.imgs = ThumbnailImage::from_paths(img_paths);
+        self.selection_anchor = None;
+        self.focused = None;
         self.reset_scroll = true;
-        self.set_total_rows();
+        self.aspect_ratios_loaded = false;
+        self.regroup();
     }
 
     pub fn ui(
@@ -58,8 +154,10 @@ impl GridView {
         ui: &mut Ui,
         jump_to_index: &mut Option<usize>,
         image_store: &mut ImageStore,
+        ratings: &Ratings,
     ) {
         self.handle_input(ui);
+        self.hovered_image_path = None;
 
         egui::CentralPanel::default().show_inside(ui, |ui| {
             ui.spacing_mut().item_spacing = Vec2::new(0., 0.);
@@ -74,138 +172,297 @@ impl GridView {
 
             let remainder = (prev_img_size - img_size) * self.images_per_row as f32;
 
-            let mut scroll_area = egui::ScrollArea::vertical().scroll_source(ScrollSource::ALL);
+            let row_aspect_ratio = ui.available_width() / img_size;
+            if self.layout == GridLayout::Justified
+                && row_aspect_ratio.is_finite()
+                && row_aspect_ratio != self.row_aspect_ratio
+            {
+                self.row_aspect_ratio = row_aspect_ratio;
+                self.relayout();
+            }
 
-            //Since image size changes when we resize the window, we need to compensate the scroll
-            //offset as show_rows assumes fixed widget sizes
-            if img_size != self.prev_img_size {
-                scroll_area = scroll_area.scroll_offset(Vec2 {
-                    x: 0.,
-                    y: img_size * self.prev_scroll_offset / self.prev_img_size,
-                });
+            let header_height = ui.spacing().interact_size.y + 12.;
+            let rows_resized =
+                img_size != self.prev_img_size || header_height != self.header_height;
+            if rows_resized || self.row_tops.len() != self.rows.len() + 1 {
+                self.header_height = header_height;
+                self.set_row_tops(img_size);
             }
 
-            if self.images_per_row != self.prev_images_per_row {
-                let target_row =
-                    (self.prev_row_range_start * self.prev_images_per_row) / self.images_per_row;
+            self.update_rubber_band(ui);
 
+            //Ctrl and shift drags draw the rubber band
+            let rubber_band_modifiers = ui.input(|i| i.modifiers.command || i.modifiers.shift);
+            let mut scroll_area = egui::ScrollArea::vertical().scroll_source(ScrollSource {
+                drag: self.rubber_band_start.is_none() && !rubber_band_modifiers,
+                ..ScrollSource::ALL
+            });
+
+            //Since image size changes when we resize the window and rows change with the number
+            //of images per row, grouping and collapsed groups, we need to compensate the scroll
+            //offset. The first visible image stays in view.
+            if (rows_resized || self.anchor_scroll) && self.prev_img_size > 0. {
+                let row = self.row_of(self.first_visible);
                 scroll_area = scroll_area.scroll_offset(Vec2 {
                     x: 0.,
-                    y: img_size * target_row as f32,
+                    y: self.row_top(row) + self.first_visible_offset * self.row_height(row),
                 });
             }
+            self.anchor_scroll = false;
 
-            if let Some(mut i) = jump_to_index.take() {
-                //Get start of the row index so it's easier to calculate the offset
-                i = i - (i % self.images_per_row);
-                let scroll_offset = ((i as f32) / self.images_per_row as f32) * img_size;
-                scroll_area = scroll_area.scroll_offset(Vec2 {
-                    x: 0.,
-                    y: scroll_offset,
-                })
+            //Group headers stick to the top, covering the first row
+            let sticky_height = if self.groups.is_empty() {
+                0.
+            } else {
+                self.header_height
+            };
+
+            if let Some(position) = jump_to_index.take() {
+                self.focused = self.imgs.iter().position(|img| img.position == position);
+                if let Some(i) = self.focused {
+                    let scroll_offset = self.row_top(self.row_of(i)) - sticky_height;
+                    scroll_area = scroll_area.scroll_offset(Vec2 {
+                        x: 0.,
+                        y: scroll_offset.max(0.),
+                    })
+                }
             };
 
+            if self.scroll_to_focused
+                && let Some(focused) = self.focused
+            {
+                self.scroll_to_focused = false;
+                let row = self.row_of(focused);
+                let row_top = self.row_top(row) - sticky_height;
+                let row_bottom = self.row_top(row) + self.row_height(row);
+
+                if row_top < self.prev_scroll_offset {
+                    scroll_area = scroll_area.scroll_offset(Vec2 {
+                        x: 0.,
+                        y: row_top.max(0.),
+                    });
+                } else if row_bottom > self.prev_scroll_offset + self.prev_viewport_height {
+                    scroll_area = scroll_area.scroll_offset(Vec2 {
+                        x: 0.,
+                        y: row_bottom - self.prev_viewport_height,
+                    });
+                }
+            }
+
             if self.reset_scroll {
                 scroll_area = scroll_area.scroll_offset(Vec2 { x: 0., y: 0. });
                 self.reset_scroll = false;
             }
 
-            let scroll_area_response =
-                scroll_area.show_rows(ui, img_size, self.total_rows, |ui, row_range| {
-                    ui.spacing_mut().item_spacing = Vec2::new(0., 0.);
+            let mut header_action = None;
+            let total_rows = self.rows.len();
+            let scroll_area_response = scroll_area.show_viewport(ui, |ui, viewport| {
+                ui.spacing_mut().item_spacing = Vec2::new(0., 0.);
+                ui.set_height(self.row_tops.last().copied().unwrap_or_default());
 
-                    let preload_from = row_range.start.saturating_sub(self.config.preloaded_rows);
+                //Same virtualization as show_rows, with rows of different heights
+                let row_range =
+                    self.row_at(viewport.min.y)..(self.row_at(viewport.max.y) + 1).min(total_rows);
 
-                    let preload_to = if row_range.end + self.config.preloaded_rows > self.total_rows
-                    {
-                        self.total_rows
-                    } else {
-                        row_range.end + self.config.preloaded_rows
-                    };
+                let preload_from = row_range.start.saturating_sub(self.config.preloaded_rows);
+                let preload_to = (row_range.end + self.config.preloaded_rows).min(total_rows);
+                let preload = preload_from..preload_to;
 
-                    //first we go over the visible ones
-                    for r in row_range.start..row_range.end {
-                        for i in r * self.images_per_row..(r + 1) * self.images_per_row {
-                            self.load_unload_image(
-                                i,
-                                row_range.start,
-                                row_range.end,
-                                img_size,
-                                image_store,
-                            );
-                        }
-                    }
+                let visible = self.image_range(row_range.clone());
 
-                    //then in the down direction as the user is most likely to scroll down
-                    for r in row_range.end..self.total_rows {
-                        for i in r * self.images_per_row..(r + 1) * self.images_per_row {
-                            self.load_unload_image(
-                                i,
-                                preload_from,
-                                preload_to,
-                                img_size,
-                                image_store,
-                            );
-                        }
-                    }
+                //first we go over the visible ones
+                for i in visible.clone() {
+                    self.load_unload_image(i, &row_range, img_size, image_store);
+                }
 
-                    //then up
-                    for r in 0..row_range.start {
-                        for i in r * self.images_per_row..(r + 1) * self.images_per_row {
-                            self.load_unload_image(
-                                i,
-                                preload_from,
-                                preload_to,
-                                img_size,
-                                image_store,
-                            );
+                //then in the down direction as the user is most likely to scroll down
+                for i in visible.end..self.imgs.len() {
+                    self.load_unload_image(i, &preload, img_size, image_store);
+                }
+
+                //then up
+                for i in 0..visible.start {
+                    self.load_unload_image(i, &preload, img_size, image_store);
+                }
+
+                let content = ui.max_rect();
+                let row_rect = |top: f32, height: f32| {
+                    Rect::from_min_size(
+                        Pos2::new(content.left(), content.top() + top),
+                        Vec2::new(content.width(), height),
+                    )
+                };
+
+                for r in row_range.clone() {
+                    let rect = row_rect(self.row_top(r), self.row_height(r));
+                    let mut ui = ui.new_child(
+                        UiBuilder::new()
+                            .max_rect(rect)
+                            .id_salt(("grid_row", r))
+                            .layout(Layout::left_to_right(Align::Min)),
+                    );
+
+                    let images = match &self.rows[r] {
+                        GridRow::Header(group) => {
+                            if let Some(action) = self.group_header_ui(&mut ui, *group) {
+                                header_action = Some((*group, action));
+                            }
+                            continue;
                         }
-                    }
+                        GridRow::Images(images) => images.clone(),
+                    };
 
-                    for r in row_range.clone() {
-                        ui.horizontal(|ui| {
-                            ui.spacing_mut().item_spacing = Vec2::new(0., 0.);
+                    match self.layout {
+                        GridLayout::Square => {
                             ui.add_space(remainder / 2.0);
-
-                            for j in r * self.images_per_row..(r + 1) * self.images_per_row {
-                                self.show_image_at(ui, j, img_size, image_store);
+                            for j in images {
+                                let size = Vec2::splat(img_size);
+                                self.show_image_at(&mut ui, j, size, image_store, ratings);
+                            }
+                        }
+                        GridLayout::Justified => {
+                            for (j, width) in self.justified_widths(r, img_size) {
+                                let size = Vec2::new(width, img_size);
+                                self.show_image_at(&mut ui, j, size, image_store, ratings);
                             }
-                        });
+                        }
                     }
+                }
 
-                    if !utils::are_inputs_muted(ui)
-                        && ui.input_mut(|i| i.consume_shortcut(&self.config.sc_scroll.kbd_shortcut))
+                //Header of the group at the top, pushed up by the header of the next one
+                if !self.groups.is_empty()
+                    && let Some(group) = self.group_of_row(row_range.start)
+                {
+                    let mut top = viewport.min.y;
+                    if let Some(next) = row_range
+                        .clone()
+                        .skip(1)
+                        .find(|r| matches!(self.rows[*r], GridRow::Header(_)))
                     {
-                        ui.scroll_with_delta(Vec2::new(0., -(img_size * 0.5)));
+                        top = top.min(self.row_top(next) - self.header_height);
                     }
 
-                    self.prev_row_range_start = row_range.start;
-                });
+                    let mut ui = ui.new_child(
+                        UiBuilder::new()
+                            .max_rect(row_rect(top, self.header_height))
+                            .id_salt("grid_sticky_header")
+                            .layout(Layout::left_to_right(Align::Min)),
+                    );
+                    if let Some(action) = self.group_header_ui(&mut ui, group) {
+                        header_action = Some((group, action));
+                    }
+                }
+
+                if !utils::are_inputs_muted(ui)
+                    && ui.input_mut(|i| i.consume_shortcut(&self.config.sc_scroll.kbd_shortcut))
+                {
+                    ui.scroll_with_delta(Vec2::new(0., -(img_size * 0.5)));
+                }
+
+                self.first_visible = visible.start;
+                self.visible_rows = row_range.len();
+            });
 
             self.prev_scroll_offset = scroll_area_response.state.offset.y;
+            self.prev_viewport_height = scroll_area_response.inner_rect.height();
             self.prev_img_size = img_size;
-            self.prev_images_per_row = self.images_per_row;
+
+            let row = self.row_of(self.first_visible);
+            self.first_visible_offset =
+                (self.prev_scroll_offset - self.row_top(row)) / self.row_height(row);
+
+            match header_action {
+                Some((group, HeaderAction::ToggleCollapsed)) => self.toggle_collapsed(group),
+                Some((group, HeaderAction::ToggleSelection)) => self.toggle_group_selection(group),
+                None => {}
+            }
+
+            if let Some(band) = self.rubber_band {
+                let selection = ui.visuals().selection;
+                ui.painter().rect(
+                    band,
+                    0,
+                    selection.bg_fill.gamma_multiply(0.25),
+                    Stroke::new(1., selection.stroke.color),
+                    StrokeKind::Inside,
+                );
+            }
+
+            self.selection_count_ui(ui);
         });
     }
 
+    ///Tracks a drag started with ctrl or shift held, the band is applied when it's released
+    fn update_rubber_band(&mut self, ui: &Ui) {
+        let (pressed, down, pos, modifiers) = ui.input(|i| {
+            (
+                i.pointer.primary_pressed(),
+                i.pointer.primary_down(),
+                i.pointer.interact_pos(),
+                i.modifiers,
+            )
+        });
+
+        if pressed
+            && (modifiers.command || modifiers.shift)
+            && pos.is_some_and(|pos| ui.max_rect().contains(pos))
+        {
+            self.rubber_band_start = pos;
+        }
+
+        self.rubber_band = match (self.rubber_band_start, pos) {
+            (Some(start), Some(pos)) if start.distance(pos) > RUBBER_BAND_MIN_DRAG => {
+                Some(Rect::from_two_pos(start, pos))
+            }
+            _ => None,
+        };
+
+        if !down {
+            self.rubber_band_start = None;
+        }
+    }
+
+    fn selection_count_ui(&self, ui: &Ui) {
+        let count = self.imgs.iter().filter(|img| img.selected).count();
+        if count == 0 {
+            return;
+        }
+
+        egui::Area::new(Id::new("grid_selection_count"))
+            .order(Order::Foreground)
+            .anchor(Align2::RIGHT_BOTTOM, [-12., -12.])
+            .interactable(false)
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(format!("{count} selected"));
+                });
+            });
+    }
+
     fn load_unload_image(
         &mut self,
         i: usize,
-        preload_from: usize,
-        preload_to: usize,
+        preload_rows: &Range<usize>,
         image_size: f32,
         image_store: &mut ImageStore,
     ) {
+        let preloaded = preload_rows.contains(&self.row_of(i)) && !self.is_hidden(i);
         let img = &mut match self.imgs.get_mut(i) {
             Some(img) => img,
             None => return,
         };
 
-        if i >= preload_from * self.images_per_row && i <= preload_to * self.images_per_row {
+        if preloaded {
+            //Wide images are wider than the row is high in justified rows
+            let longest_side = match self.layout {
+                GridLayout::Square => image_size,
+                GridLayout::Justified => image_size * img.aspect_ratio.unwrap_or(1.).max(1.),
+            };
+
             //Double the square size so we have a little downscale going on
             //Looks better than without and won't impact speed much. Possibly add as a config
             if !img.registered {
-                image_store.register_img(&img.path, Some((image_size * 2.) as u32));
+                image_store.register_img(&img.path, Some((longest_side * 2.) as u32));
                 img.registered = true;
             }
         } else {
@@ -218,32 +475,187 @@ impl GridView {
         &mut self,
         ui: &mut Ui,
         index: usize,
-        max_size: f32,
+        size: Vec2,
         image_store: &mut ImageStore,
+        ratings: &Ratings,
     ) {
         let image = match self.imgs.get_mut(index) {
             Some(img) => img,
             None => return,
         };
 
-        if let Some(resp) = image.ui(ui, [max_size, max_size], image_store) {
-            if resp.clicked() {
-                self.selected_image_name = Some(image.name.clone());
-            }
-            if resp.hovered() {
-                ui.set_cursor_icon(egui::CursorIcon::PointingHand);
+        let resp = match self.layout {
+            GridLayout::Square => image.ui(ui, [size.x, size.y], image_store),
+            GridLayout::Justified => image.cover_ui(ui, size, image_store),
+        };
+        let resp = match resp {
+            Some(resp) => resp,
+            None => return,
+        };
+
+        ratings
+            .get(&image.path)
+            .paint_badge(ui.painter(), resp.rect);
+
+        let in_rubber_band = self
+            .rubber_band
+            .is_some_and(|band| band.intersects(resp.rect));
+        if in_rubber_band && self.rubber_band_start.is_none() {
+            image.selected = true;
+        }
+
+        if image.selected || in_rubber_band {
+            ui.painter().rect_stroke(
+                resp.rect,
+                0,
+                Stroke::new(3., ui.visuals().selection.stroke.color),
+                StrokeKind::Inside,
+            );
+        }
+
+        if self.focused == Some(index) {
+            ui.painter().rect_stroke(
+                resp.rect.shrink(4.),
+                0,
+                Stroke::new(2., ui.visuals().strong_text_color()),
+                StrokeKind::Inside,
+            );
+        }
+
+        if resp.hovered() {
+            ui.set_cursor_icon(egui::CursorIcon::PointingHand);
+            self.hovered_image_path = Some(image.path.clone());
+        }
+
+        let path = image.path.clone();
+        let modifiers = ui.input(|i| i.modifiers);
+        if resp.clicked() && modifiers.shift {
+            self.select_range(index, !modifiers.command);
+            self.focused = Some(index);
+        } else if resp.clicked() && modifiers.command {
+            image.selected = !image.selected;
+            self.selection_anchor = Some(index);
+            self.focused = Some(index);
+        } else if resp.clicked() {
+            self.selected_image_name = Some(image.name.clone());
+        }
+
+        //Right clicking a selected image acts on the whole selection
+        let paths = match self.imgs.get(index) {
+            Some(img) if img.selected => self.selected_paths(),
+            _ => vec![path],
+        };
+
+        if let Some(callback) = show_context_menu(&self.config.context_menu, &resp, &paths) {
+            self.callbacks.extend(Callback::for_paths(callback, &paths));
+            tracing::info!("{:?}", self.callbacks);
+        }
+    }
+
+    ///Selects from the last ctrl clicked image to this one, skipping collapsed groups
+    fn select_range(&mut self, to: usize, replace: bool) {
+        let from = self.selection_anchor.unwrap_or(to);
+
+        for i in 0..self.imgs.len() {
+            let in_range = (from.min(to)..=from.max(to)).contains(&i) && !self.is_hidden(i);
+            let img = &mut self.imgs[i];
+            if in_range {
+                img.selected = true;
+            } else if replace {
+                img.selected = false;
             }
+        }
+    }
+
+    ///Header with the group name and its number of images, collapses the group when clicked
+    fn group_header_ui(&self, ui: &mut Ui, group: usize) -> Option<HeaderAction> {
+        let group = &self.groups[group];
+        let collapsed = self.collapsed.contains(&group.label);
+        let mut action = None;
+
+        //Also keeps clicks from reaching the images under the sticky header
+        let header = ui.interact(ui.max_rect(), ui.id().with("group_header"), Sense::click());
+        if header.clicked() {
+            action = Some(HeaderAction::ToggleCollapsed);
+        }
+
+        ui.painter()
+            .rect_filled(ui.max_rect(), 0, ui.visuals().panel_fill);
+        ui.add_space(8.);
+
+        ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+            ui.spacing_mut().item_spacing = Vec2::new(6., 0.);
+
+            let (_, icon) =
+                ui.allocate_exact_size(Vec2::splat(ui.spacing().icon_width), Sense::hover());
+            paint_default_icon(ui, if collapsed { 0. } else { 1. }, &icon);
+
+            ui.add(egui::Label::new(RichText::new(&group.label).strong()).selectable(false));
+            ui.add(
+                egui::Label::new(RichText::new(group.images.len().to_string()).weak())
+                    .selectable(false),
+            );
+
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                ui.add_space(8.);
+                if ui.small_button("Select all").clicked() {
+                    action = Some(HeaderAction::ToggleSelection);
+                }
+            });
+        });
+
+        action
+    }
 
-            let return_callback = show_context_menu(&self.config.context_menu, &resp, &image.path);
+    fn toggle_collapsed(&mut self, group: usize) {
+        let label = &self.groups[group].label;
+        if !self.collapsed.remove(label) {
+            self.collapsed.insert(label.clone());
+        }
+        self.relayout();
+    }
 
-            if let Some(return_callback) = return_callback {
-                self.callback = Some(Callback::from_callback(
-                    return_callback,
-                    Some(image.path.clone()),
-                ));
+    ///Selects the images of the group, or deselects them when they're all selected
+    fn toggle_group_selection(&mut self, group: usize) {
+        let images = &mut self.imgs[self.groups[group].images.clone()];
+        let selected = !images.iter().all(|img| img.selected);
+        images.iter_mut().for_each(|img| img.selected = selected);
+    }
+
+    ///Group menu for the top bar
+    pub fn group_by_ui(&mut self, ui: &mut Ui) {
+        let mut group_by = self.group_by.clone();
+
+        for (option, name) in [
+            (GridGroupBy::None, "None"),
+            (GridGroupBy::Day, "Day"),
+            (GridGroupBy::Month, "Month"),
+            (GridGroupBy::Directory, "Directory"),
+            (GridGroupBy::Camera, "Camera"),
+        ] {
+            ui.radio_value(&mut group_by, option, name);
+        }
 
-                tracing::info!("{:?}", self.callback);
+        ui.horizontal(|ui| {
+            let radio = ui.radio(matches!(group_by, GridGroupBy::Tag(_)), "");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.group_tag)
+                    .hint_text("Exif tag")
+                    .desired_width(120.),
+            );
+
+            let tag = self.group_tag.trim();
+            let group = ui
+                .add_enabled(!tag.is_empty(), egui::Button::new("Group"))
+                .clicked();
+            if (radio.clicked() || group) && !tag.is_empty() {
+                group_by = GridGroupBy::Tag(tag.to_string());
             }
+        });
+
+        if group_by != self.group_by {
+            self.group_by = group_by;
+            self.regroup();
         }
     }
 
@@ -254,12 +666,52 @@ impl GridView {
             return;
         }
 
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.imgs.iter_mut().for_each(|img| img.selected = false);
+        }
+
+        //Shift variant first as the plain shortcut also matches with shift held
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_invert_selection.kbd_shortcut)) {
+            self.imgs
+                .iter_mut()
+                .for_each(|img| img.selected = !img.selected);
+        }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_select_all.kbd_shortcut)) {
+            self.imgs.iter_mut().for_each(|img| img.selected = true);
+        }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_toggle_layout.kbd_shortcut)) {
+            self.layout = match self.layout {
+                GridLayout::Square => GridLayout::Justified,
+                GridLayout::Justified => GridLayout::Square,
+            };
+            self.relayout();
+        }
+
+        self.handle_cursor_input(ctx);
+
+        for action in &self.config.user_actions {
+            if !ctx.input_mut(|i| i.consume_shortcut(&action.shortcut.kbd_shortcut)) {
+                continue;
+            }
+
+            let paths = self.action_paths();
+            if paths.is_empty() {
+                tracing::info!("No selected or hovered image for user action");
+            } else if user_action::execute_for_paths(&action.exec, &paths)
+                && let Some(callback) = action.callback.to_owned()
+            {
+                self.callbacks.extend(Callback::for_paths(callback, &paths));
+            }
+        }
+
         if (ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_more_per_row.kbd_shortcut))
             || (ctx.input(|i| i.smooth_scroll_delta.y) < 0. && ctx.input(|i| i.zoom_delta() != 1.)))
             && self.images_per_row <= 15
         {
             self.images_per_row += 1;
-            self.set_total_rows();
+            self.relayout();
         }
 
         if (ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_less_per_row.kbd_shortcut))
@@ -267,29 +719,440 @@ impl GridView {
             && self.images_per_row != 1
         {
             self.images_per_row -= 1;
-            self.set_total_rows();
+            self.relayout();
         }
     }
 
+    ///Arrows, Page Up/Down, Home and End move the cursor, extending the selection with shift held.
+    ///The first key press shows the cursor on the first visible image.
+    fn handle_cursor_input(&mut self, ctx: &egui::Context) {
+        let len = self.imgs.len();
+        if len == 0 {
+            return;
+        }
+
+        let current = self.focused.unwrap_or(self.first_visible).min(len - 1);
+        let row = self.row_of(current);
+        let last_row = self.rows.len().saturating_sub(1);
+        let page = self.visible_rows.saturating_sub(1).max(1);
+
+        //Headers are skipped
+        let images_of = |row: usize| match self.rows.get(row) {
+            Some(GridRow::Images(images)) => Some(images.clone()),
+            _ => None,
+        };
+        let next_images = |from: usize| (from..self.rows.len()).find_map(images_of);
+        let prev_images = |from: usize| (0..=from.min(last_row)).rev().find_map(images_of);
+
+        //Same column in another row, or its last image when that row is shorter
+        let current_images = images_of(row);
+        let column = current_images
+            .as_ref()
+            .map_or(0, |images| current - images.start);
+        let in_row = |images: Range<usize>| images.start + column.min(images.len() - 1);
+
+        if let Some(focused) = self.focused
+            && ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Enter))
+        {
+            self.selected_image_name = Some(self.imgs[focused].name.clone());
+            return;
+        }
+
+        let (key, extend) = ctx.input_mut(|i| {
+            let key = [
+                Key::ArrowRight,
+                Key::ArrowLeft,
+                Key::ArrowDown,
+                Key::ArrowUp,
+                Key::PageDown,
+                Key::PageUp,
+                Key::Home,
+                Key::End,
+            ]
+            .into_iter()
+            .find(|key| i.consume_key(Modifiers::NONE, *key));
+
+            (key, i.modifiers.shift)
+        });
+
+        let Some(key) = key else {
+            return;
+        };
+
+        let target = match key {
+            Key::ArrowRight => match &current_images {
+                Some(images) if current + 1 < images.end => Some(current + 1),
+                _ => next_images(row + 1).map(|images| images.start),
+            },
+            Key::ArrowLeft => match &current_images {
+                Some(images) if current > images.start => Some(current - 1),
+                _ => row
+                    .checked_sub(1)
+                    .and_then(prev_images)
+                    .map(|images| images.end - 1),
+            },
+            Key::ArrowDown => next_images(row + 1).map(in_row),
+            Key::ArrowUp => row.checked_sub(1).and_then(prev_images).map(in_row),
+            Key::PageDown => next_images((row + page).min(last_row))
+                .or_else(|| prev_images(last_row))
+                .map(in_row),
+            Key::PageUp => prev_images(row.saturating_sub(page))
+                .or_else(|| next_images(0))
+                .map(in_row),
+            Key::Home => next_images(0).map(|images| images.start),
+            _ => prev_images(last_row).map(|images| images.end - 1),
+        };
+
+        //Keys at the edges keep the cursor in place
+        let mut target = target.unwrap_or(current);
+
+        if self.focused.is_none() {
+            target = current;
+        }
+
+        if extend {
+            self.select_range(target, true);
+        } else {
+            self.selection_anchor = Some(target);
+        }
+
+        self.focused = Some(target);
+        self.scroll_to_focused = true;
+    }
+
+    ///Name of the image under the keyboard cursor
+    pub fn focused_image_name(&self) -> Option<String> {
+        self.imgs.get(self.focused?).map(|img| img.name.clone())
+    }
+
     pub fn selected_image_name(&mut self) -> Option<String> {
         //We want it to be consumed
         self.selected_image_name.take()
     }
 
-    pub fn set_total_rows(&mut self) {
-        //div_ceil will be available in the next release. Avoids conversions..
-        self.total_rows = (self.imgs.len() as f32 / self.images_per_row as f32).ceil() as usize
+    ///Thumbnail under the pointer, the target of the rating shortcuts
+    pub fn hovered_image_path(&self) -> Option<PathBuf> {
+        self.hovered_image_path.clone()
+    }
+
+    pub fn selected_paths(&self) -> Vec<PathBuf> {
+        self.imgs
+            .iter()
+            .filter(|img| img.selected)
+            .map(|img| img.path.clone())
+            .collect()
+    }
+
+    ///The selection, or else the hovered thumbnail
+    fn action_paths(&self) -> Vec<PathBuf> {
+        let selected = self.selected_paths();
+        if selected.is_empty() {
+            self.hovered_image_path.clone().into_iter().collect()
+        } else {
+            selected
+        }
+    }
+
+    ///Splits the images into rows for the current layout, under the header of their group
+    fn relayout(&mut self) {
+        let len = self.imgs.len();
+        if self.layout == GridLayout::Justified && !self.aspect_ratios_loaded {
+            self.load_aspect_ratios();
+        }
+
+        let sections: Vec<(Option<usize>, Range<usize>)> = if self.groups.is_empty() {
+            vec![(None, 0..len)]
+        } else {
+            self.groups
+                .iter()
+                .enumerate()
+                .map(|(i, group)| (Some(i), group.images.clone()))
+                .collect()
+        };
+
+        self.rows.clear();
+        self.image_row = vec![0; len];
+
+        for (group, images) in sections {
+            if let Some(group) = group {
+                let header = self.rows.len();
+                self.rows.push(GridRow::Header(group));
+
+                if self.collapsed.contains(&self.groups[group].label) {
+                    self.image_row[images].fill(header);
+                    continue;
+                }
+            }
+
+            let rows = match self.layout {
+                GridLayout::Square => images
+                    .clone()
+                    .step_by(self.images_per_row)
+                    .map(|start| start..(start + self.images_per_row).min(images.end))
+                    .collect(),
+                GridLayout::Justified => {
+                    let aspect_ratios: Vec<f32> = self.imgs[images.clone()]
+                        .iter()
+                        .map(|img| img.aspect_ratio.unwrap_or(1.))
+                        .collect();
+                    justified_rows(&aspect_ratios, self.row_aspect_ratio)
+                        .into_iter()
+                        .map(|row| row.start + images.start..row.end + images.start)
+                        .collect::<Vec<_>>()
+                }
+            };
+
+            for row in rows {
+                self.image_row[row.clone()].fill(self.rows.len());
+                self.rows.push(GridRow::Images(row));
+            }
+        }
+
+        self.row_tops.clear();
+        self.anchor_scroll = true;
+    }
+
+    ///Sorts the images into their groups, keeping the opened order within each one and ordering
+    ///the groups by their first image
+    fn regroup(&mut self) {
+        let focused = self.focused.map(|i| self.imgs[i].position);
+        self.imgs.sort_by_key(|img| img.position);
+
+        let (order, groups) = match self.group_labels() {
+            Some(labels) => sections(labels),
+            None => ((0..self.imgs.len()).collect(), vec![]),
+        };
+
+        let mut imgs: Vec<Option<ThumbnailImage>> = std::mem::take(&mut self.imgs)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.imgs = order.into_iter().filter_map(|i| imgs[i].take()).collect();
+        self.groups = groups;
+
+        self.selection_anchor = None;
+        self.focused =
+            focused.and_then(|position| self.imgs.iter().position(|img| img.position == position));
+        self.reordered = true;
+        self.relayout();
+    }
+
+    ///Group of each image in the opened order, None when not grouping
+    fn group_labels(&mut self) -> Option<Vec<String>> {
+        let tag = match &self.group_by {
+            GridGroupBy::None => return None,
+            GridGroupBy::Directory => None,
+            GridGroupBy::Day | GridGroupBy::Month => Some(METADATA_DATE),
+            GridGroupBy::Camera => Some(METADATA_CAMERA_MODEL),
+            GridGroupBy::Tag(tag) => Some(tag.as_str()),
+        };
+
+        let paths: Vec<String> = self
+            .imgs
+            .iter()
+            .map(|img| img.path.to_string_lossy().to_string())
+            .collect();
+
+        let values: HashMap<String, String> = match tag {
+            Some(tag) => match self.db_repo.get_tag_values(&paths, tag) {
+                Ok(values) => values
+                    .into_iter()
+                    .filter_map(|(path, value)| Some((path, value?)))
+                    .collect(),
+                Err(e) => {
+                    tracing::error!("Failure fetching {tag} to group images -> {e}");
+                    HashMap::new()
+                }
+            },
+            None => HashMap::new(),
+        };
+
+        Some(
+            self.imgs
+                .iter()
+                .zip(&paths)
+                .map(|(img, path)| {
+                    group_label(
+                        &self.group_by,
+                        &img.path,
+                        values.get(path).map(String::as_str),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    ///Aspect ratios come from the image size cached in the library, so rows don't change as
+    ///thumbnails get decoded. Images missing from the library are laid out as squares.
+    fn load_aspect_ratios(&mut self) {
+        self.aspect_ratios_loaded = true;
+
+        let paths: Vec<String> = self
+            .imgs
+            .iter()
+            .map(|img| img.path.to_string_lossy().to_string())
+            .collect();
+        let sizes = match self.db_repo.get_image_sizes(&paths) {
+            Ok(sizes) => sizes,
+            Err(e) => {
+                tracing::error!("Failure fetching image sizes -> {e}");
+                return;
+            }
+        };
+
+        let aspect_ratios: HashMap<String, f32> = sizes
+            .into_iter()
+            .filter_map(|(path, size, orientation)| {
+                Some((
+                    path,
+                    Metadata::aspect_ratio(&size?, orientation.as_deref())?,
+                ))
+            })
+            .collect();
+
+        for (img, path) in self.imgs.iter_mut().zip(&paths) {
+            img.aspect_ratio = aspect_ratios.get(path).copied();
+        }
+    }
+
+    ///Picks up image sizes and tags cached after the images were opened
+    pub fn reload_metadata(&mut self) {
+        self.aspect_ratios_loaded = false;
+        match self.group_by {
+            GridGroupBy::None | GridGroupBy::Directory => self.relayout(),
+            _ => self.regroup(),
+        }
+    }
+
+    fn set_row_tops(&mut self, img_size: f32) {
+        self.row_tops.clear();
+
+        let mut top = 0.;
+        for row in &self.rows {
+            self.row_tops.push(top);
+            top += match row {
+                GridRow::Header(_) => self.header_height,
+                GridRow::Images(_) => img_size,
+            };
+        }
+        self.row_tops.push(top);
+    }
+
+    fn row_top(&self, row: usize) -> f32 {
+        self.row_tops.get(row).copied().unwrap_or_default()
+    }
+
+    fn row_height(&self, row: usize) -> f32 {
+        match (self.row_tops.get(row), self.row_tops.get(row + 1)) {
+            (Some(top), Some(bottom)) if bottom > top => bottom - top,
+            _ => 1.,
+        }
+    }
+
+    ///Row at this offset from the top of the grid
+    fn row_at(&self, y: f32) -> usize {
+        self.row_tops
+            .partition_point(|top| *top <= y)
+            .saturating_sub(1)
+    }
+
+    fn row_of(&self, index: usize) -> usize {
+        self.image_row
+            .get(index)
+            .or(self.image_row.last())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    ///In a collapsed group
+    fn is_hidden(&self, index: usize) -> bool {
+        matches!(self.rows.get(self.row_of(index)), Some(GridRow::Header(_)))
+    }
+
+    fn group_of_row(&self, row: usize) -> Option<usize> {
+        match self.rows.get(row)? {
+            GridRow::Header(group) => Some(*group),
+            GridRow::Images(images) => Some(
+                self.groups
+                    .partition_point(|group| group.images.end <= images.start),
+            ),
+        }
+    }
+
+    ///Images from the first row to the last one
+    fn image_range(&self, rows: Range<usize>) -> Range<usize> {
+        let start = self.image_row.partition_point(|row| *row < rows.start);
+        let end = self.image_row.partition_point(|row| *row < rows.end);
+
+        start..end.max(start)
+    }
+
+    ///Widths of the images of a justified row filling the whole width, the last row of a group
+    ///keeps its natural widths when they fall short of it
+    fn justified_widths(&self, row: usize, height: f32) -> Vec<(usize, f32)> {
+        let range = match &self.rows[row] {
+            GridRow::Images(images) => images.clone(),
+            GridRow::Header(_) => return vec![],
+        };
+        let aspect_ratio = |i: usize| self.imgs[i].aspect_ratio.unwrap_or(1.);
+        let total: f32 = range.clone().map(aspect_ratio).sum();
+
+        let last = !matches!(self.rows.get(row + 1), Some(GridRow::Images(_)));
+        let scale = if last && total < self.row_aspect_ratio {
+            1.
+        } else {
+            self.row_aspect_ratio / total
+        };
+
+        range
+            .map(|i| (i, aspect_ratio(i) * height * scale))
+            .collect()
     }
 
     pub fn pop(&mut self, path: &Path) {
         if let Some(pos) = self.imgs.iter().position(|x| x.path == path) {
-            self.imgs.remove(pos);
-            self.set_total_rows();
+            //The cursor stays on its image, or takes the next one when that's the removed image
+            let focused_position = self
+                .focused
+                .filter(|focused| *focused != pos)
+                .map(|focused| self.imgs[focused].position);
+
+            let removed = self.imgs.remove(pos);
+            for img in self.imgs.iter_mut() {
+                if img.position > removed.position {
+                    img.position -= 1;
+                }
+            }
+
+            self.focused = match focused_position {
+                Some(position) => {
+                    let position = position - usize::from(position > removed.position);
+                    self.imgs.iter().position(|img| img.position == position)
+                }
+                None => self
+                    .focused
+                    .filter(|_| !self.imgs.is_empty())
+                    .map(|focused| focused.min(self.imgs.len() - 1)),
+            };
+            self.selection_anchor = self
+                .selection_anchor
+                .filter(|anchor| *anchor != pos)
+                .map(|anchor| anchor - usize::from(anchor > pos));
+
+            //The other images keep their group, no need to query them again
+            remove_from_groups(&mut self.groups, pos);
+            self.relayout();
         }
     }
 
-    pub fn take_callback(&mut self) -> Option<Callback> {
-        self.callback.take()
+    ///Display order when it changed since the last call, for Image View to follow it
+    pub fn take_reordered_paths(&mut self) -> Option<Vec<PathBuf>> {
+        std::mem::take(&mut self.reordered)
+            .then(|| self.imgs.iter().map(|img| img.path.clone()).collect())
+    }
+
+    pub fn take_callbacks(&mut self) -> Vec<Callback> {
+        std::mem::take(&mut self.callbacks)
     }
 
     pub fn reload_at(&mut self, path: &Path, image_store: &mut ImageStore) {
@@ -300,3 +1163,192 @@ impl GridView {
         }
     }
 }
+
+///Display order of the images, grouped by label in the order the labels first appear, and the
+///groups in that order
+fn sections(labels: Vec<String>) -> (Vec<usize>, Vec<Group>) {
+    let mut sections: Vec<(String, Vec<usize>)> = vec![];
+    let mut section_of: HashMap<String, usize> = HashMap::new();
+
+    for (i, label) in labels.into_iter().enumerate() {
+        match section_of.get(&label) {
+            Some(section) => sections[*section].1.push(i),
+            None => {
+                section_of.insert(label.clone(), sections.len());
+                sections.push((label, vec![i]));
+            }
+        }
+    }
+
+    let mut order = vec![];
+    let mut groups = vec![];
+    for (label, images) in sections {
+        let start = order.len();
+        order.extend(images);
+        groups.push(Group {
+            label,
+            images: start..order.len(),
+        });
+    }
+
+    (order, groups)
+}
+
+///Shrinks the group of a removed image and moves the following ones back, dropping the group
+///when it was its last image
+fn remove_from_groups(groups: &mut Vec<Group>, index: usize) {
+    for group in groups.iter_mut() {
+        if group.images.start > index {
+            group.images.start -= 1;
+        }
+        if group.images.end > index {
+            group.images.end -= 1;
+        }
+    }
+
+    groups.retain(|group| !group.images.is_empty());
+}
+
+///Header of an image's group, from the value of the grouped tag
+fn group_label(group_by: &GridGroupBy, path: &Path, value: Option<&str>) -> String {
+    //Exif dates look like `2024:05:01 10:00:00`
+    let date = |len: usize| match value.and_then(|date| date.get(..len)) {
+        Some(date) => date.replace(':', "-"),
+        None => "Unknown date".to_string(),
+    };
+
+    match group_by {
+        GridGroupBy::None => String::new(),
+        GridGroupBy::Day => date(10),
+        GridGroupBy::Month => date(7),
+        GridGroupBy::Directory => path
+            .parent()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        GridGroupBy::Camera => value.unwrap_or("Unknown camera").to_string(),
+        GridGroupBy::Tag(tag) => match value.filter(|value| !value.is_empty()) {
+            Some(value) => value.to_string(),
+            None => format!("No {tag}"),
+        },
+    }
+}
+
+///Fills rows up to the aspect ratio of a row, closing each one before or after the image that
+///overflows it, whichever needs less scaling to span the row
+fn justified_rows(aspect_ratios: &[f32], row_aspect_ratio: f32) -> Vec<Range<usize>> {
+    let mut rows = vec![];
+    let mut start = 0;
+    let mut total = 0.;
+
+    for (i, aspect_ratio) in aspect_ratios.iter().enumerate() {
+        if total + aspect_ratio < row_aspect_ratio {
+            total += aspect_ratio;
+            continue;
+        }
+
+        if total > 0. && (total + aspect_ratio) * total > row_aspect_ratio * row_aspect_ratio {
+            rows.push(start..i);
+            start = i;
+            total = *aspect_ratio;
+
+            if total < row_aspect_ratio {
+                continue;
+            }
+        }
+
+        rows.push(start..i + 1);
+        start = i + 1;
+        total = 0.;
+    }
+
+    if start < aspect_ratios.len() {
+        rows.push(start..aspect_ratios.len());
+    }
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn justified_rows_span_the_width() {
+        //Three landscapes fill a row of aspect ratio 4.5 exactly
+        assert_eq!(justified_rows(&[1.5, 1.5, 1.5, 1.5], 4.5), vec![0..3, 3..4]);
+        //Closing before the overflowing image leaves the row closer to its width
+        assert_eq!(
+            justified_rows(&[1.5, 1.5, 0.8, 1.5, 1.5], 4.),
+            vec![0..3, 3..5]
+        );
+        //Closing after it does here
+        assert_eq!(justified_rows(&[1.5, 1.5, 1.2, 1.5], 4.), vec![0..3, 3..4]);
+        //Panoramas wider than a row get their own
+        assert_eq!(
+            justified_rows(&[1.5, 1.5, 6., 1.], 4.),
+            vec![0..2, 2..3, 3..4]
+        );
+        assert_eq!(justified_rows(&[], 4.), Vec::<Range<usize>>::new());
+    }
+
+    #[test]
+    fn sections_follow_the_first_image_of_each_group() {
+        let labels = ["b", "a", "b", "c", "a"].map(String::from).to_vec();
+        let (order, groups) = sections(labels);
+
+        assert_eq!(order, vec![0, 2, 1, 4, 3]);
+        assert_eq!(
+            groups,
+            vec![
+                Group {
+                    label: "b".to_string(),
+                    images: 0..2
+                },
+                Group {
+                    label: "a".to_string(),
+                    images: 2..4
+                },
+                Group {
+                    label: "c".to_string(),
+                    images: 4..5
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_images_leave_their_group() {
+        let labels = ["a", "a", "b", "c", "c"].map(String::from).to_vec();
+        let (_, mut groups) = sections(labels);
+
+        remove_from_groups(&mut groups, 2);
+        assert_eq!(
+            groups.iter().map(|g| g.images.clone()).collect::<Vec<_>>(),
+            vec![0..2, 2..4]
+        );
+
+        remove_from_groups(&mut groups, 0);
+        assert_eq!(
+            groups.iter().map(|g| g.images.clone()).collect::<Vec<_>>(),
+            vec![0..1, 1..3]
+        );
+    }
+
+    #[test]
+    fn group_labels_from_tag_values() {
+        let path = Path::new("/photos/lisbon/DSCF0001.RAF");
+        let date = Some("2024:05:01 10:00:00");
+
+        assert_eq!(group_label(&GridGroupBy::Day, path, date), "2024-05-01");
+        assert_eq!(group_label(&GridGroupBy::Month, path, date), "2024-05");
+        assert_eq!(group_label(&GridGroupBy::Day, path, None), "Unknown date");
+        assert_eq!(
+            group_label(&GridGroupBy::Directory, path, None),
+            "/photos/lisbon"
+        );
+        assert_eq!(
+            group_label(&GridGroupBy::Tag("Lens Model".to_string()), path, None),
+            "No Lens Model"
+        );
+    }
+}
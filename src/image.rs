@@ -1,24 +1,31 @@
 use crate::{
     JXL_EXTENSION, RAW_EXTENSIONS, SKIP_ORIENT_EXTENSIONS,
+    config::ExportFormat,
     db::DbRepository,
-    icc::profile_desc_to_icc,
+    export,
+    gallery_image::{CAPTION_SIZE_R, FramePreset},
+    icc::{SRGB_V4, profile_desc_to_icc},
     metadata::{self, METADATA_ORIENTATION, METADATA_PROFILE_DESCRIPTION, Orientation},
+    utils,
+    view_transform::ViewTransform,
 };
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont, point};
 use eframe::{
     egui_wgpu::RenderState,
     wgpu::{self, TextureView},
 };
-use epaint::{TextureId, Vec2};
-use image::{DynamicImage, RgbImage};
+use epaint::{Pos2, TextureId, Vec2};
+use image::{DynamicImage, Rgb, RgbImage};
 use jpegxl_rs::decoder_builder;
 use lcms2::*;
 use rawler::imgop::develop::RawDevelop;
 use std::{
     collections::HashMap,
-    fs::File,
+    fs::{self, File},
     io::Read,
     path::PathBuf,
     process::Command,
+    sync::Arc,
     thread::{self, JoinHandle},
 };
 use std::{path::Path, time::Instant};
@@ -27,6 +34,11 @@ use fast_image_resize::{PixelType, Resizer};
 use fast_image_resize::{ResizeOptions, images::Image as FirImage};
 
 pub const LOAD_FAIL_PNG: &[u8; 95764] = include_bytes!("../resources/load_fail.png");
+///Font used for the text drawn into exported images
+const EXPORT_FONT: &[u8] =
+    include_bytes!("../resources/Atkinson_Hyperlegible_Next/AtkinsonHyperlegibleNext-Regular.ttf");
+///Images are exported in sRGB, which is what most sharing sites expect
+const EXPORT_ICC_PROFILE: &str = "sRGB";
 
 #[derive(PartialEq, Eq, Debug)]
 pub enum DecodeMethod {
@@ -36,43 +48,79 @@ pub enum DecodeMethod {
     Jxl,
 }
 
+impl DecodeMethod {
+    pub fn for_path(path: &Path, raw_exiftool_preview_ext: &[String]) -> DecodeMethod {
+        let ext = path
+            .extension()
+            .unwrap_or_default()
+            .to_str()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        if ext == JXL_EXTENSION {
+            DecodeMethod::Jxl
+        } else if raw_exiftool_preview_ext.contains(&ext) {
+            DecodeMethod::ExtractPreviewExiftool
+        } else if RAW_EXTENSIONS.contains(&ext.as_str()) {
+            DecodeMethod::Rawler
+        } else {
+            DecodeMethod::Regular
+        }
+    }
+}
+
+///CPU side copy of the pixels uploaded to the GPU, kept around for inspection
+pub struct ImagePixels {
+    ///Oriented and resized pixels before color management
+    pub source: RgbImage,
+    ///Pixels after color management, None when no transform was applied
+    pub output: Option<RgbImage>,
+    ///Size of the image as it was decoded, before any resizing
+    pub decoded_size: [u32; 2],
+}
+
+impl ImagePixels {
+    pub fn output(&self) -> &RgbImage {
+        self.output.as_ref().unwrap_or(&self.source)
+    }
+}
+
 #[derive(Clone)]
 pub struct Image {
     pub file_name: String,
     pub size: Vec2,
     pub metadata: HashMap<String, String>,
+    pub pixels: Option<Arc<ImagePixels>>,
+    ///Rotation and mirroring persisted by the user, applied when displaying
+    pub view_transform: ViewTransform,
     texture_view: Option<TextureView>,
     texture_id: Option<TextureId>,
 }
 
+///Settings shared by every image loaded by a store
+#[derive(Clone, Debug)]
+pub struct LoadSettings {
+    pub output_icc_profile: String,
+    pub max_texture_size: u32,
+    pub raw_exiftool_preview_ext: Vec<String>,
+    ///Keeps a CPU copy of the pixels, doubles memory usage per image
+    pub keep_pixels: bool,
+    ///Reads the rotation and mirroring persisted in the database
+    pub persisted_view_transform: bool,
+}
+
 impl Image {
     pub fn load(
         path: PathBuf,
         image_size: Option<u32>,
-        output_icc_profile: String,
+        settings: &LoadSettings,
         render_state: &RenderState,
-        max_texture_size: u32,
         db_repo: &DbRepository,
-        raw_exiftool_preview_ext: &[String],
     ) -> JoinHandle<Option<Image>> {
         let render_state = render_state.clone();
         let mut db_repo = db_repo.clone();
-        let ext = path
-            .extension()
-            .unwrap_or_default()
-            .to_str()
-            .unwrap_or_default()
-            .to_lowercase();
-
-        let decode_method = if ext == JXL_EXTENSION {
-            DecodeMethod::Jxl
-        } else if raw_exiftool_preview_ext.contains(&ext) {
-            DecodeMethod::ExtractPreviewExiftool
-        } else if RAW_EXTENSIONS.contains(&ext.as_str()) {
-            DecodeMethod::Rawler
-        } else {
-            DecodeMethod::Regular
-        };
+        let settings = settings.clone();
+        let decode_method = DecodeMethod::for_path(&path, &settings.raw_exiftool_preview_ext);
 
         thread::spawn(move || {
             let file_name = path
@@ -137,10 +185,11 @@ impl Image {
             );
             now = Instant::now();
 
+            let mut decoded_size = [image.width(), image.height()];
             let image_size = set_image_size(
                 image_size,
                 image.width().max(image.height()),
-                Some(max_texture_size),
+                Some(settings.max_texture_size),
             );
 
             if image_size.is_some() {
@@ -158,6 +207,12 @@ impl Image {
                 metadata::Metadata::get_image_metadata(&mut db_repo, &path.to_string_lossy())
                     .unwrap_or_default();
 
+            let view_transform = if settings.persisted_view_transform {
+                Self::persisted_view_transform(&mut db_repo, &path)
+            } else {
+                ViewTransform::default()
+            };
+
             tracing::info!(
                 "{} -> Spent {}ms reading metadata",
                 file_name,
@@ -165,16 +220,15 @@ impl Image {
             );
             now = Instant::now();
 
-            if !SKIP_ORIENT_EXTENSIONS.contains(
-                &path
-                    .extension()
-                    .unwrap_or_default()
-                    .to_str()
-                    .unwrap_or_default(),
-            ) {
+            if should_orient(&path) {
                 image = Self::orient(image, &metadata);
             }
 
+            //Keep the decoded size in the same orientation as the displayed image
+            if (image.width() > image.height()) != (decoded_size[0] > decoded_size[1]) {
+                decoded_size.swap(0, 1);
+            }
+
             tracing::info!(
                 "{} -> Spent {}ms orienting",
                 file_name,
@@ -183,11 +237,14 @@ impl Image {
             now = Instant::now();
 
             let size: [u32; 2] = [image.width() as _, image.height() as _];
-            let mut flat_samples = image.into_rgb8().into_flat_samples();
-            let pixels = flat_samples.as_mut_slice();
+            let mut rgb_image = image.into_rgb8();
+            let source = settings.keep_pixels.then(|| rgb_image.clone());
 
-            if let Some(cpd) = metadata.get(METADATA_PROFILE_DESCRIPTION) {
-                Self::apply_cc(cpd, pixels, &path, &output_icc_profile);
+            let cc_applied = match metadata.get(METADATA_PROFILE_DESCRIPTION) {
+                Some(cpd) => {
+                    Self::apply_cc(cpd, &mut rgb_image, &path, &settings.output_icc_profile)
+                }
+                None => false,
             };
 
             tracing::info!(
@@ -196,7 +253,7 @@ impl Image {
                 now.elapsed().as_millis()
             );
 
-            match Self::load_wgpu_linear_texture(pixels, size, &file_name, &render_state) {
+            match Self::load_wgpu_linear_texture(&rgb_image, size, &file_name, &render_state) {
                 Some(texture_view) => {
                     tracing::info!(
                         "{} Spent {}ms loading texture with wgpu",
@@ -212,6 +269,14 @@ impl Image {
                             y: size[1] as f32,
                         },
                         metadata,
+                        view_transform,
+                        pixels: source.map(|source| {
+                            Arc::new(ImagePixels {
+                                source,
+                                output: cc_applied.then_some(rgb_image),
+                                decoded_size,
+                            })
+                        }),
                     })
                 }
                 None => None,
@@ -219,6 +284,74 @@ impl Image {
         })
     }
 
+    ///Decodes and orients the image at its original resolution, for exporting
+    pub fn decode_full_size(
+        path: &Path,
+        settings: &LoadSettings,
+        metadata: &HashMap<String, String>,
+    ) -> Option<DynamicImage> {
+        let decode_method = DecodeMethod::for_path(path, &settings.raw_exiftool_preview_ext);
+
+        let mut buffer = if decode_method == DecodeMethod::ExtractPreviewExiftool {
+            extract_preview_from_raw_file(path)?
+        } else {
+            match fs::read(path) {
+                Ok(buffer) => buffer,
+                Err(e) => {
+                    tracing::error!("{path:?} -> Failure reading image: {e}");
+                    return None;
+                }
+            }
+        };
+
+        let image = Self::decode(&mut buffer, path, &decode_method)?;
+
+        if should_orient(path) {
+            Some(Self::orient(image, metadata))
+        } else {
+            Some(image)
+        }
+    }
+
+    ///Decodes and orients the image scaled down to `size`. Raws use their embedded preview when
+    ///they have one instead of being developed.
+    pub fn decode_thumbnail(
+        path: &Path,
+        size: u32,
+        settings: &LoadSettings,
+        metadata: &HashMap<String, String>,
+    ) -> Option<DynamicImage> {
+        let decode_method = DecodeMethod::for_path(path, &settings.raw_exiftool_preview_ext);
+        if decode_method != DecodeMethod::Rawler {
+            return Self::decode_full_size(path, settings, metadata)
+                .map(|image| Self::resize(image, Some(size)));
+        }
+
+        let image = match extract_preview_from_raw_file(path) {
+            Some(buffer) => Self::decode_generic(&buffer, path)?,
+            None => Self::decode_rawler(path)?,
+        };
+
+        let image = Self::resize(image, Some(size));
+        if should_orient(path) {
+            Some(Self::orient(image, metadata))
+        } else {
+            Some(image)
+        }
+    }
+
+    ///Rotation and mirroring saved for the image, or none if there isn't one
+    pub fn persisted_view_transform(db_repo: &mut DbRepository, path: &Path) -> ViewTransform {
+        match db_repo.get_view_transform(&path.to_string_lossy()) {
+            Ok(Some((quarter_turns, mirrored))) => ViewTransform::new(quarter_turns, mirrored),
+            Ok(None) => ViewTransform::default(),
+            Err(e) => {
+                tracing::error!("{path:?} -> Failure fetching view transform: {e}");
+                ViewTransform::default()
+            }
+        }
+    }
+
     pub fn decode(
         buffer: &mut Vec<u8>,
         path: &Path,
@@ -376,12 +509,121 @@ impl Image {
         }
     }
 
+    ///Mirrors and then rotates the image clockwise, like it is displayed
+    pub fn apply_view_transform(img: DynamicImage, view_transform: &ViewTransform) -> DynamicImage {
+        let img = if view_transform.mirrored {
+            img.fliph()
+        } else {
+            img
+        };
+
+        match view_transform.quarter_turns {
+            1 => img.rotate90(),
+            2 => img.rotate180(),
+            3 => img.rotate270(),
+            _ => img,
+        }
+    }
+
+    ///Draws the frame around the image and writes the caption inside the bottom border
+    pub fn render_frame(image: &RgbImage, style: &FramePreset, caption: &str) -> RgbImage {
+        let size = Vec2::new(image.width() as f32, image.height() as f32);
+        let (_, borders) = style.layout(size, Vec2::INFINITY);
+        let [top, right, bottom, left] = borders.map(|border| border.round() as u32);
+
+        let [r, g, b, _] = style.color.to_srgba_unmultiplied();
+        let mut canvas = RgbImage::from_pixel(
+            image.width() + left + right,
+            image.height() + top + bottom,
+            Rgb([r, g, b]),
+        );
+        image::imageops::replace(&mut canvas, image, left as i64, top as i64);
+
+        if !caption.is_empty() && bottom > 0 {
+            let [r, g, b, _] = style.caption_color.to_srgba_unmultiplied();
+            let center = Pos2::new(
+                left as f32 + size.x / 2.,
+                (top + image.height()) as f32 + bottom as f32 / 2.,
+            );
+            draw_text(
+                &mut canvas,
+                caption,
+                bottom as f32 * CAPTION_SIZE_R,
+                size.x,
+                center,
+                Rgb([r, g, b]),
+            );
+        }
+
+        canvas
+    }
+
+    ///Renders the image with its frame at the original resolution and saves it. Returns the
+    ///path of the written file.
+    pub fn export_framed(
+        path: PathBuf,
+        metadata: HashMap<String, String>,
+        view_transform: ViewTransform,
+        style: FramePreset,
+        settings: &LoadSettings,
+        output: FrameExportOutput,
+    ) -> JoinHandle<Option<PathBuf>> {
+        let settings = settings.clone();
+
+        thread::spawn(move || {
+            let now = Instant::now();
+            let image = Self::decode_full_size(&path, &settings, &metadata)?;
+            let mut image = Self::apply_view_transform(image, &view_transform).into_rgb8();
+
+            if let Some(cpd) = metadata.get(METADATA_PROFILE_DESCRIPTION) {
+                Self::apply_cc(cpd, &mut image, &path, &EXPORT_ICC_PROFILE.to_string());
+            }
+
+            let caption = if style.caption_format.is_empty() {
+                String::new()
+            } else {
+                metadata::Metadata::format_string_with_metadata(&style.caption_format, &metadata)
+            };
+            let framed = Self::render_frame(&image, &style, &caption);
+
+            let output_path = output.path_for(&path)?;
+            if let Some(parent) = output_path.parent()
+                && let Err(e) = fs::create_dir_all(parent)
+            {
+                tracing::error!("Failure creating export directory {parent:?} -> {e}");
+                return None;
+            }
+
+            match export::save_image(
+                &framed,
+                &output_path,
+                output.format,
+                output.quality,
+                SRGB_V4,
+            ) {
+                Ok(()) => {
+                    tracing::info!(
+                        "{output_path:?} -> Spent {}ms exporting framed image",
+                        now.elapsed().as_millis()
+                    );
+                    Some(output_path)
+                }
+                Err(e) => {
+                    tracing::error!("Failure saving framed image to {output_path:?} -> {e}");
+                    None
+                }
+            }
+        })
+    }
+
+    ///Transforms the pixels in place into the output profile, returns true if a transform was
+    ///applied
     pub fn apply_cc(
         color_profile_desc: &str,
         pixels: &mut [u8],
         path: &PathBuf,
         output_profile: &String,
-    ) {
+    ) -> bool {
         if color_profile_desc
             .to_lowercase()
             .contains(&output_profile.to_lowercase())
@@ -389,7 +631,7 @@ impl Image {
             tracing::info!(
                 "Input {color_profile_desc} and output {output_profile} profiles are the same -> skipping"
             );
-            return;
+            return false;
         }
 
         let input_icc_bytes = match profile_desc_to_icc(color_profile_desc) {
@@ -403,7 +645,7 @@ impl Image {
                         tracing::info!("Successfully extracted ICC profile from image");
                         icc_bytes
                     }
-                    None => return,
+                    None => return false,
                 }
             }
         };
@@ -412,7 +654,7 @@ impl Image {
             Some(icc_bytes) => icc_bytes.to_vec(),
             None => {
                 tracing::error!("Badly configured output ICC profile -> {output_profile}");
-                return;
+                return false;
             }
         };
 
@@ -420,7 +662,7 @@ impl Image {
             Ok(profile) => profile,
             Err(_) => {
                 tracing::error!("Failed constructing input lcms2 profile from ICC data");
-                return;
+                return false;
             }
         };
 
@@ -428,7 +670,7 @@ impl Image {
             Ok(profile) => profile,
             Err(_) => {
                 tracing::error!("Failed constructing output lcms2 profile from ICC data");
-                return;
+                return false;
             }
         };
 
@@ -443,11 +685,12 @@ impl Image {
             Ok(transform) => transform,
             Err(_) => {
                 tracing::error!("Failure applying ICC profile to image");
-                return;
+                return false;
             }
         };
 
         transform.transform_in_place(pixels);
+        true
     }
 
     pub fn load_wgpu_linear_texture(
@@ -522,6 +765,8 @@ impl Image {
                 y: size[1] as f32,
             },
             metadata: HashMap::new(),
+            pixels: None,
+            view_transform: ViewTransform::default(),
         };
 
         img.register_texture(render_state);
@@ -549,6 +794,10 @@ impl Image {
         self.texture_id
     }
 
+    pub fn get_texture_view(&self) -> Option<&TextureView> {
+        self.texture_view.as_ref()
+    }
+
     pub fn free_texture(&self, render_state: &RenderState) {
         if let Some(texture_id) = self.texture_id {
             render_state.renderer.write().free_texture(&texture_id);
@@ -556,6 +805,118 @@ impl Image {
     }
 }
 
+///Where and how exported images are written
+#[derive(Clone)]
+pub struct FrameExportOutput {
+    ///Next to the original image when empty
+    pub dir: String,
+    pub format: ExportFormat,
+    ///JPEG quality, from 1 to 100
+    pub quality: u8,
+}
+
+impl FrameExportOutput {
+    ///Numbered when a framed copy was already exported, so earlier ones aren't overwritten
+    fn path_for(&self, path: &Path) -> Option<PathBuf> {
+        let dir = if self.dir.is_empty() {
+            path.parent()?.to_path_buf()
+        } else {
+            PathBuf::from(&self.dir)
+        };
+        let file_name = format!(
+            "{}_framed.{}",
+            path.file_stem()?.to_string_lossy(),
+            self.format.extension()
+        );
+        Some(utils::unique_target(Path::new(&file_name), &dir))
+    }
+}
+
+///Writes a single line of text centered on a point, shrinking it when wider than max_width
+pub fn draw_text(
+    canvas: &mut RgbImage,
+    text: &str,
+    font_size: f32,
+    max_width: f32,
+    center: Pos2,
+    color: Rgb<u8>,
+) {
+    let font = match FontRef::try_from_slice(EXPORT_FONT) {
+        Ok(font) => font,
+        Err(e) => {
+            tracing::error!("Failure loading export font -> {e}");
+            return;
+        }
+    };
+
+    let text_width = |size: f32| {
+        let scaled = font.as_scaled(PxScale::from(size));
+        let mut width = 0.;
+        let mut previous = None;
+        for c in text.chars() {
+            let glyph_id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                width += scaled.kern(previous, glyph_id);
+            }
+            width += scaled.h_advance(glyph_id);
+            previous = Some(glyph_id);
+        }
+        width
+    };
+
+    let mut font_size = font_size;
+    let width = text_width(font_size);
+    if width > max_width {
+        font_size *= max_width / width;
+    }
+
+    let scaled = font.as_scaled(PxScale::from(font_size));
+    let baseline = center.y + (scaled.ascent() + scaled.descent()) / 2.;
+    let mut x = center.x - text_width(font_size) / 2.;
+    let mut previous = None;
+
+    for c in text.chars() {
+        let glyph_id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            x += scaled.kern(previous, glyph_id);
+        }
+        previous = Some(glyph_id);
+
+        let glyph = glyph_id.with_scale_and_position(font_size, point(x, baseline));
+        x += scaled.h_advance(glyph_id);
+
+        let outlined = match font.outline_glyph(glyph) {
+            Some(outlined) => outlined,
+            None => continue,
+        };
+        let bounds = outlined.px_bounds();
+
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i64 + gx as i64;
+            let py = bounds.min.y as i64 + gy as i64;
+            if px < 0 || py < 0 || px >= canvas.width() as i64 || py >= canvas.height() as i64 {
+                return;
+            }
+
+            let pixel = canvas.get_pixel_mut(px as u32, py as u32);
+            for (channel, text_channel) in pixel.0.iter_mut().zip(color.0) {
+                *channel = (*channel as f32 * (1. - coverage) + text_channel as f32 * coverage)
+                    .round() as u8;
+            }
+        });
+    }
+}
+
+fn should_orient(path: &Path) -> bool {
+    !SKIP_ORIENT_EXTENSIONS.contains(
+        &path
+            .extension()
+            .unwrap_or_default()
+            .to_str()
+            .unwrap_or_default(),
+    )
+}
+
 pub fn extract_preview_from_raw_file(path: &Path) -> Option<Vec<u8>> {
     let mut command = Command::new("exiftool");
     command.arg("-b").arg("-PreviewImage").arg(path);
@@ -605,3 +966,57 @@ pub fn set_image_size(
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FrameStyle;
+
+    #[test]
+    fn frame_is_drawn_around_the_image() {
+        let style = FramePreset::new(&FrameStyle {
+            name: "Print".to_string(),
+            color: "#FFFFFF".to_string(),
+            borders: [0.1, 0.1, 0.5, 0.1],
+            shadow: false,
+            caption_format: String::new(),
+            caption_color: "#000000".to_string(),
+        });
+        let image = RgbImage::from_pixel(200, 100, Rgb([255, 0, 0]));
+
+        let framed = Image::render_frame(&image, &style, "Caption");
+
+        assert_eq!(framed.dimensions(), (220, 160));
+        assert_eq!(*framed.get_pixel(0, 0), Rgb([255, 255, 255]));
+        assert_eq!(*framed.get_pixel(10, 10), Rgb([255, 0, 0]));
+        assert_eq!(*framed.get_pixel(209, 109), Rgb([255, 0, 0]));
+
+        //The caption darkens part of the bottom border
+        let caption_pixels = (0..220)
+            .flat_map(|x| (110..160).map(move |y| (x, y)))
+            .filter(|&(x, y)| framed.get_pixel(x, y).0[0] < 128)
+            .count();
+        assert!(caption_pixels > 0);
+    }
+
+    #[test]
+    fn framed_exports_do_not_overwrite_earlier_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = FrameExportOutput {
+            dir: dir.path().to_string_lossy().to_string(),
+            format: ExportFormat::Jpeg,
+            quality: 90,
+        };
+        let path = Path::new("/photos/IMG_0001.raf");
+
+        assert_eq!(
+            output.path_for(path),
+            Some(dir.path().join("IMG_0001_framed.jpg"))
+        );
+        fs::write(dir.path().join("IMG_0001_framed.jpg"), []).unwrap();
+        assert_eq!(
+            output.path_for(path),
+            Some(dir.path().join("IMG_0001_framed_1.jpg"))
+        );
+    }
+}
@@ -1,23 +1,25 @@
 use crate::db::DbRepository;
-use crate::image::Image;
+use crate::image::{Image, ImagePixels, LoadSettings};
+use crate::metadata::{METADATA_ORIENTATION, Orientation};
+use crate::view_transform::ViewTransform;
 use eframe::egui_wgpu::RenderState;
+use eframe::wgpu::TextureView;
 use epaint::{TextureId, Vec2};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::thread::JoinHandle;
 
 pub struct ImageStore {
     imgs: HashMap<PathBuf, StoredImage>,
     loading_imgs: HashMap<PathBuf, LoadingImage>,
     loading_queue: HashMap<PathBuf, QueuedImage>,
-    output_icc_profile_name: String,
-    max_texture_size: u32,
+    load_settings: LoadSettings,
     error_img: Image, //TODO: Make it so error image texture is never freed
     load_budget_per_frame: usize,
     simultaneous_load: usize,
     db_repo: DbRepository,
     render_state: RenderState,
-    raw_exiftool_preview_ext: Vec<String>,
 }
 
 struct StoredImage {
@@ -51,17 +53,34 @@ impl ImageStore {
             imgs: HashMap::new(),
             loading_imgs: HashMap::new(),
             loading_queue: HashMap::new(),
-            output_icc_profile_name,
-            max_texture_size,
+            load_settings: LoadSettings {
+                output_icc_profile: output_icc_profile_name,
+                max_texture_size,
+                raw_exiftool_preview_ext: raw_exiftool_preview_ext.to_vec(),
+                keep_pixels: false,
+                persisted_view_transform: false,
+            },
             load_budget_per_frame: 2, //Higher values can cause bad frametimes when loading a lot
             //of pictures at once
             error_img,
             simultaneous_load,
             db_repo: db_repo.clone(),
             render_state: render_state.clone(),
-            raw_exiftool_preview_ext: raw_exiftool_preview_ext.to_vec(),
         }
     }
+
+    ///Keeps a CPU copy of the pixels of every loaded image, see [`ImagePixels`]
+    pub fn keep_pixels(mut self, keep_pixels: bool) -> ImageStore {
+        self.load_settings.keep_pixels = keep_pixels;
+        self
+    }
+
+    ///Applies the rotation and mirroring saved with [`ImageStore::save_view_transform`]
+    pub fn persisted_view_transform(mut self, persisted_view_transform: bool) -> ImageStore {
+        self.load_settings.persisted_view_transform = persisted_view_transform;
+        self
+    }
+
     pub fn is_image_loaded(&self, pathbuf: &PathBuf) -> bool {
         self.imgs.contains_key(pathbuf)
     }
@@ -80,6 +99,50 @@ impl ImageStore {
         }
     }
 
+    pub fn get_texture_view(&self, pathbuf: &PathBuf) -> Option<TextureView> {
+        self.imgs
+            .get(pathbuf)
+            .and_then(|stored_image| stored_image.image.get_texture_view().cloned())
+    }
+
+    pub fn get_view_transform(&self, pathbuf: &PathBuf) -> Option<ViewTransform> {
+        self.imgs
+            .get(pathbuf)
+            .map(|stored_image| stored_image.image.view_transform)
+    }
+
+    pub fn save_view_transform(&mut self, pathbuf: &PathBuf, view_transform: ViewTransform) {
+        if let Some(stored_image) = self.imgs.get_mut(pathbuf) {
+            stored_image.image.view_transform = view_transform;
+        }
+
+        let path = pathbuf.to_string_lossy();
+        let result = if view_transform.is_identity() {
+            self.db_repo.delete_view_transform(&path)
+        } else {
+            self.db_repo.save_view_transform(
+                &path,
+                view_transform.quarter_turns,
+                view_transform.mirrored,
+            )
+        };
+
+        if let Err(e) = result {
+            tracing::error!("Failure saving view transform for {path} -> {e}");
+        }
+    }
+
+    ///Updates the cached orientation to the one written to the file, so the next load uses it
+    pub fn update_cached_orientation(&mut self, path: &Path, orientation: u8) {
+        if let Err(e) = self.db_repo.update_metadata_tag(
+            path,
+            METADATA_ORIENTATION,
+            Orientation::metadata_for_exif_value(orientation),
+        ) {
+            tracing::error!("Failure updating cached orientation -> {e}");
+        }
+    }
+
     pub fn get_image_metadata(&self, pathbuf: &PathBuf) -> Option<&HashMap<String, String>> {
         if let Some(stored_image) = self.imgs.get_key_value(pathbuf) {
             Some(&stored_image.1.image.metadata)
@@ -88,6 +151,20 @@ impl ImageStore {
         }
     }
 
+    pub fn output_icc_profile(&self) -> &str {
+        &self.load_settings.output_icc_profile
+    }
+
+    pub fn load_settings(&self) -> &LoadSettings {
+        &self.load_settings
+    }
+
+    pub fn get_image_pixels(&self, pathbuf: &PathBuf) -> Option<Arc<ImagePixels>> {
+        self.imgs
+            .get(pathbuf)
+            .and_then(|stored_image| stored_image.image.pixels.clone())
+    }
+
     pub fn register_img(&mut self, pathbuf: &PathBuf, desired_size: Option<u32>) {
         let mut should_reload = false;
         let mut should_return = false;
@@ -166,11 +243,9 @@ impl ImageStore {
             let image_handle = Image::load(
                 key.clone(),
                 img.desired_size,
-                self.output_icc_profile_name.clone(),
+                &self.load_settings,
                 &self.render_state,
-                self.max_texture_size,
                 &self.db_repo,
-                &self.raw_exiftool_preview_ext,
             );
 
             self.loading_imgs.insert(
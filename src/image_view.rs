@@ -1,62 +1,37 @@
-use eframe::egui::{Panel, Response, Sense, Ui};
+use eframe::egui::{Panel, Pos2, Response, Sense, Ui, UiBuilder};
 use eframe::{egui, epaint::Vec2};
 use std::cmp::min;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::time::{Duration, Instant};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
-use crate::config::SlideshowConfig;
+use crate::config::{KenBurnsTarget, SlideshowConfig, SlideshowTransition};
+use crate::culling::{CullFlag, Culling};
+use crate::db::DbRepository;
+use crate::display_adjustments::{Channel, DisplayAdjustments};
 use crate::gallery_image::{GalleryImageFrame, GalleryImageSizing};
+use crate::image::{FrameExportOutput, Image};
 use crate::image_store::ImageStore;
+use crate::ken_burns::{self, KenBurns};
+use crate::metadata::{METADATA_ORIENTATION, Metadata, Orientation};
+use crate::overlay::Overlays;
+use crate::photo_frame::PhotoFrame;
+use crate::pixel_inspector::PixelInspector;
+use crate::rating::Ratings;
+use crate::slideshow::{self, RestoreState, Slideshow, TransitionFrame};
+use crate::view_transform::ViewTransform;
 use crate::{
+    SKIP_ORIENT_EXTENSIONS,
     callback::Callback,
     config::ImageViewConfig,
     gallery_image::GalleryImage,
-    user_action::{self, show_context_menu},
+    user_action::{self, show_context_menu_with},
     utils,
 };
 
 pub const PERCENTAGES: &[f32] = &[200., 100., 75., 50., 25.];
 
-#[derive(Clone)]
-pub struct Slideshow {
-    last_adv_instant: Instant,
-    last_zoom_instant: Instant,
-    zoom_step: Option<f32>,
-    zoom_step_ms: u128,
-    zoom_step_count: f32,
-}
-
-impl Slideshow {
-    pub fn new(cfg: &SlideshowConfig) -> Slideshow {
-        //For now we use a hardcoded step count. Zoom every 50ms to conserve energy
-        let total_zoom_steps = cfg.seconds_per_image as f32 * 20.;
-
-        Slideshow {
-            last_adv_instant: Instant::now(),
-            last_zoom_instant: Instant::now(),
-            zoom_step_ms: 50,
-            zoom_step: None,
-            zoom_step_count: total_zoom_steps,
-        }
-    }
-
-    //when the slideshow advances to the next image it should always display it maximized in  the screen,
-    // aka, no cropping. This can lead to different %zoom baselines for each image
-    pub fn set_zoom_step(&mut self, percent_zoom: f32, active_image: Option<&GalleryImage>) {
-        if percent_zoom != 0.
-            && let Some(active_image) = active_image
-        {
-            //in the very first frame of the app this value is always 0.0
-            if active_image.prev_percentage_zoom != 0.0 {
-                self.zoom_step = Some(
-                    (active_image.prev_percentage_zoom * percent_zoom / self.zoom_step_count)
-                        / 100.,
-                );
-            }
-        }
-    }
-}
-
 pub struct ImageView {
     imgs: Vec<GalleryImage>,
     pub selected_img_index: usize,
@@ -69,6 +44,18 @@ pub struct ImageView {
     nr_images_displayed: usize,
     slideshow_config: SlideshowConfig,
     slideshow: Option<Slideshow>,
+    photo_frame: PhotoFrame,
+    pixel_inspector: PixelInspector,
+    ///Index and normalized position of the image under the pointer
+    hovered_uv: Option<(usize, Pos2)>,
+    ///Dragging pans through the minimap instead of the image
+    minimap_hovered: bool,
+    adjustments: DisplayAdjustments,
+    overlays: Overlays,
+    ///Image the adjustments were made on, they are reset once it changes
+    adjusted_img_path: Option<PathBuf>,
+    frame_exports: Vec<JoinHandle<Option<PathBuf>>>,
+    culling: Culling,
 }
 
 impl ImageView {
@@ -79,31 +66,16 @@ impl ImageView {
         start_slideshow: bool,
         slideshow_config: SlideshowConfig,
         image_store: &mut ImageStore,
+        db_repo: &DbRepository,
     ) -> ImageView {
-        let mut gallery_sizing = GalleryImageSizing {
+        let gallery_sizing = GalleryImageSizing {
             zoom_factor: 1.0,
             scroll_delta: Vec2::new(0., 0.),
             should_maximize: false,
             has_maximized: false,
         };
 
-        let mut frame = GalleryImageFrame {
-            enabled: false,
-            size_r: config.frame_size_relative_to_image,
-        };
-
-        let slideshow: Option<Slideshow>;
-
-        if start_slideshow {
-            slideshow = Some(Slideshow::new(&slideshow_config));
-            gallery_sizing.should_maximize = true;
-
-            if slideshow_config.start_with_frame_enabled {
-                frame.enabled = true;
-            }
-        } else {
-            slideshow = None;
-        };
+        let frame = GalleryImageFrame::new(&config);
 
         let mut sg = ImageView {
             imgs: vec![],
@@ -115,12 +87,25 @@ impl ImageView {
             callback: None,
             nr_images_displayed: config.nr_images_shown,
             config: config.clone(),
+            photo_frame: PhotoFrame::new(&slideshow_config),
             slideshow_config,
-            slideshow,
+            slideshow: None,
+            pixel_inspector: PixelInspector::default(),
+            hovered_uv: None,
+            minimap_hovered: false,
+            adjustments: DisplayAdjustments::default(),
+            overlays: Overlays::new(&config),
+            adjusted_img_path: None,
+            frame_exports: vec![],
+            culling: Culling::new(db_repo, &config.cull_trash_dir),
         };
 
         sg.set_images(image_paths, selected_image_path, image_store);
 
+        if start_slideshow {
+            sg.start_slideshow(image_store);
+        }
+
         sg
     }
 
@@ -144,6 +129,10 @@ impl ImageView {
         self.preload_active =
             Self::is_valid_for_preload(self.config.nr_loaded_images, self.imgs.len());
 
+        if self.culling.enabled {
+            self.culling.load(image_paths);
+        }
+
         tracing::info!(
             "Starting gallery with {} images on image {}",
             self.imgs.len(),
@@ -270,6 +259,11 @@ impl ImageView {
         self.frame.enabled = !self.frame.enabled;
     }
 
+    pub fn cycle_frame_styles(&mut self) {
+        self.frame.cycle_style();
+        self.frame.enabled = true;
+    }
+
     pub fn reset_zoom(&mut self) {
         self.sizing.zoom_factor = 1.0;
     }
@@ -296,7 +290,7 @@ impl ImageView {
             None => return,
         };
 
-        let original_size = match image_store.get_image_size(&img.path) {
+        let original_size = match img.oriented_image_size(image_store) {
             Some(org_size) => org_size,
             None => return,
         };
@@ -375,6 +369,15 @@ impl ImageView {
         self.get_active_img().map(|img| img.path.clone())
     }
 
+    pub fn img_paths(&self) -> Vec<PathBuf> {
+        self.imgs.iter().map(|img| img.path.clone()).collect()
+    }
+
+    ///Borrowed paths, for what runs every frame
+    fn path_refs(imgs: &[GalleryImage]) -> Vec<&Path> {
+        imgs.iter().map(|img| img.path.as_path()).collect()
+    }
+
     pub fn active_img_is_loading(&self, image_store: &ImageStore) -> bool {
         match self.get_active_img() {
             Some(img) => !image_store.is_image_loaded(&img.path),
@@ -398,6 +401,24 @@ impl ImageView {
         self.jump_to.clear();
     }
 
+    ///Puts the images in the given order, staying on the active one
+    pub fn reorder(&mut self, image_paths: &[PathBuf], image_store: &mut ImageStore) {
+        let active_img_path = self.get_active_img_path();
+        let order: HashMap<&Path, usize> = image_paths
+            .iter()
+            .enumerate()
+            .map(|(i, path)| (path.as_path(), i))
+            .collect();
+
+        self.imgs
+            .sort_by_key(|img| order.get(img.path.as_path()).copied().unwrap_or(usize::MAX));
+        self.selected_img_index = active_img_path
+            .and_then(|path| self.imgs.iter().position(|img| img.path == path))
+            .unwrap_or(0);
+
+        self.load(image_store);
+    }
+
     pub fn reload_at(&mut self, path: &Path, image_store: &mut ImageStore) {
         if let Some(index) = self.imgs.iter().position(|x| x.path == path) {
             let img = &mut self.imgs[index];
@@ -432,31 +453,234 @@ impl ImageView {
         flattened: bool,
         watcher_enabled: bool,
         image_store: &mut ImageStore,
+        ratings: &mut Ratings,
     ) {
-        self.handle_input(ui, image_store);
+        self.handle_input(ui, image_store, ratings);
+        self.reset_adjustments_on_image_change();
+        self.poll_frame_exports(ui.ctx());
 
         //In slideshow mode we only want to see the picture
         if self.slideshow.is_none() {
-            self.show_view_bottom_bar(ui, flattened, watcher_enabled, image_store);
+            self.show_view_bottom_bar(ui, flattened, watcher_enabled, image_store, ratings);
+        } else if self.photo_frame.is_quiet() {
+            self.photo_frame.show_quiet(ui);
+            return;
         } else {
             self.handle_slideshow(ui, image_store);
         }
 
         let show_image_response = self.show_image(ui, image_store);
         self.handle_image_scroll(ui, &show_image_response, image_store);
-        self.handle_callbacks(&show_image_response);
+        self.handle_callbacks(&show_image_response, image_store);
+        self.show_pixel_inspector(ui, image_store);
+
+        if self.culling.is_summary_visible() {
+            let paths = Self::path_refs(&self.imgs);
+            if self.culling.summary_ui(ui.ctx(), &paths, ratings) {
+                self.callback = Some(Callback::ReloadAll);
+            }
+        }
+
+        if self.slideshow.is_some() {
+            let path = self.get_active_img_path();
+            self.photo_frame
+                .ui(ui, show_image_response.rect, path.as_deref(), image_store);
+        }
+    }
+
+    pub fn reset_adjustments_on_image_change(&mut self) {
+        let active_img_path = self.get_active_img_path();
+        if self.adjusted_img_path != active_img_path {
+            self.adjustments.reset();
+            self.adjusted_img_path = active_img_path;
+        }
+    }
+
+    pub fn handle_adjustments_input(&mut self, ctx: &egui::Context) {
+        //Checked before the other shortcuts as these share keys with them
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_contrast_up.kbd_shortcut)) {
+            self.adjustments.increase_contrast();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_contrast_down.kbd_shortcut)) {
+            self.adjustments.decrease_contrast();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_exposure_up.kbd_shortcut)) {
+            self.adjustments.increase_exposure();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_exposure_down.kbd_shortcut)) {
+            self.adjustments.decrease_exposure();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_gamma_up.kbd_shortcut)) {
+            self.adjustments.increase_gamma();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_gamma_down.kbd_shortcut)) {
+            self.adjustments.decrease_gamma();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_invert.kbd_shortcut)) {
+            self.adjustments.toggle_invert();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_grayscale.kbd_shortcut)) {
+            self.adjustments.toggle_grayscale();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_red_channel.kbd_shortcut)) {
+            self.adjustments.toggle_channel(Channel::Red);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_green_channel.kbd_shortcut)) {
+            self.adjustments.toggle_channel(Channel::Green);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_blue_channel.kbd_shortcut)) {
+            self.adjustments.toggle_channel(Channel::Blue);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_reset_adjustments.kbd_shortcut)) {
+            self.adjustments.reset();
+        }
+    }
+
+    pub fn transform_active_img(
+        &mut self,
+        image_store: &mut ImageStore,
+        transform: impl FnOnce(&mut ViewTransform),
+    ) {
+        let persist = self.config.persist_view_transform;
+        if let Some(img) = self.get_active_img_mut() {
+            let mut view_transform = img.view_transform();
+            transform(&mut view_transform);
+            img.set_view_transform(view_transform);
+
+            if persist {
+                image_store.save_view_transform(&img.path, view_transform);
+            }
+        }
     }
 
-    pub fn handle_input(&mut self, ui: &mut Ui, image_store: &mut ImageStore) {
+    ///Writes the displayed orientation to the file's EXIF orientation tag
+    pub fn write_orientation(&mut self, image_store: &mut ImageStore) {
+        let img = match self.get_active_img_mut() {
+            Some(img) => img,
+            None => return,
+        };
+
+        let view_transform = img.view_transform();
+        if view_transform.is_identity() {
+            return;
+        }
+
+        let ext = img
+            .path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        if SKIP_ORIENT_EXTENSIONS.contains(&ext.as_str()) {
+            tracing::warn!("EXIF orientation is not applied to {ext} files, not writing it");
+            return;
+        }
+
+        let exif_transform = Self::exif_view_transform(image_store.get_image_metadata(&img.path));
+        let orientation = exif_transform
+            .followed_by(&view_transform)
+            .exif_orientation();
+
+        if Metadata::write_orientation(&img.path, orientation) {
+            let path = img.path.clone();
+            img.set_view_transform(ViewTransform::default());
+            image_store.save_view_transform(&path, ViewTransform::default());
+            image_store.update_cached_orientation(&path, orientation);
+            self.callback = Some(Callback::Reload(Some(path)));
+        }
+    }
+
+    pub fn show_pixel_inspector(&mut self, ui: &mut Ui, image_store: &ImageStore) {
+        if !self.pixel_inspector.enabled {
+            return;
+        }
+
+        let (index, uv) = match self.hovered_uv {
+            Some(hovered) => hovered,
+            None => {
+                self.pixel_inspector.clear();
+                return;
+            }
+        };
+
+        let path = &self.imgs[index].path;
+        match image_store.get_image_pixels(path) {
+            Some(pixels) => {
+                self.pixel_inspector
+                    .update(path, &pixels, uv, image_store.output_icc_profile());
+                self.pixel_inspector.ui(ui);
+            }
+            None => self.pixel_inspector.clear(),
+        }
+    }
+
+    pub fn copy_pixel_value(&self, ctx: &egui::Context) {
+        if let Some(sample) = self.pixel_inspector.sample() {
+            ctx.copy_text(sample.to_clipboard_string());
+        }
+    }
+
+    pub fn handle_input(
+        &mut self,
+        ui: &mut Ui,
+        image_store: &mut ImageStore,
+        ratings: &mut Ratings,
+    ) {
         let ctx = ui.ctx();
 
         if utils::are_inputs_muted(ctx) {
             return;
         }
 
+        self.handle_adjustments_input(ctx);
+        self.handle_slideshow_input(ctx, image_store);
+        self.handle_culling_input(ctx, image_store, ratings);
+
+        //Rotating counterclockwise shares its key with rotating clockwise
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_rotate_ccw.kbd_shortcut)) {
+            self.transform_active_img(image_store, ViewTransform::rotate_ccw);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_rotate_cw.kbd_shortcut)) {
+            self.transform_active_img(image_store, ViewTransform::rotate_cw);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_rotate_180.kbd_shortcut)) {
+            self.transform_active_img(image_store, ViewTransform::rotate_180);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_flip_horizontal.kbd_shortcut)) {
+            self.transform_active_img(image_store, ViewTransform::flip_horizontal);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_flip_vertical.kbd_shortcut)) {
+            self.transform_active_img(image_store, ViewTransform::flip_vertical);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_write_orientation.kbd_shortcut)) {
+            self.write_orientation(image_store);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_cycle_guides.kbd_shortcut)) {
+            self.overlays.cycle_guide();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_cycle_crop_masks.kbd_shortcut)) {
+            self.overlays.cycle_crop_mask();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_focus_peaking.kbd_shortcut)) {
+            self.overlays.focus_peaking.toggle();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_peaking_threshold_up.kbd_shortcut))
+        {
+            self.overlays.focus_peaking.increase_threshold();
+        }
+        if ctx
+            .input_mut(|i| i.consume_shortcut(&self.config.sc_peaking_threshold_down.kbd_shortcut))
+        {
+            self.overlays.focus_peaking.decrease_threshold();
+        }
+
         if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_fit.kbd_shortcut)) {
             self.reset_zoom();
         }
+        //Consumed first, the plain frame shortcut would match it too
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_cycle_frame_styles.kbd_shortcut)) {
+            self.cycle_frame_styles();
+        }
         if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_frame.kbd_shortcut)) {
             self.toggle_frame();
         }
@@ -484,6 +708,17 @@ impl ImageView {
         if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_latch_fit_maximize.kbd_shortcut)) {
             self.latch_fit_maximize();
         }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_pixel_inspector.kbd_shortcut)) {
+            if !self.config.keep_pixels {
+                tracing::warn!("Pixel inspector requires image_view.keep_pixels to be enabled");
+            }
+            self.pixel_inspector.toggle();
+        }
+        if self.pixel_inspector.enabled
+            && ctx.input_mut(|i| self.config.sc_copy_pixel.consume_or_copy_event(i))
+        {
+            self.copy_pixel_value(ctx);
+        }
         if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_more_images_shown.kbd_shortcut))
             && self.nr_images_displayed < self.config.nr_loaded_images
         {
@@ -514,19 +749,96 @@ impl ImageView {
         }
     }
 
+    pub fn toggle_culling(&mut self) {
+        let paths = self.img_paths();
+        self.culling.toggle(&paths);
+    }
+
+    ///Flags the active image and moves on to the next one, the summary is shown after the last
+    pub fn cull_active_img(
+        &mut self,
+        flag: Option<CullFlag>,
+        image_store: &mut ImageStore,
+        ratings: &mut Ratings,
+    ) {
+        let path = match self.get_active_img_path() {
+            Some(path) => path,
+            None => return,
+        };
+
+        self.culling.set(&path, flag, ratings);
+
+        if self.selected_img_index + 1 >= self.imgs.len() {
+            self.culling.show_summary();
+        } else {
+            self.next_image(image_store);
+        }
+    }
+
+    //Checked first so culling keys set to match other shortcuts take over while it's enabled
+    pub fn handle_culling_input(
+        &mut self,
+        ctx: &egui::Context,
+        image_store: &mut ImageStore,
+        ratings: &mut Ratings,
+    ) {
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_culling.kbd_shortcut)) {
+            self.toggle_culling();
+        }
+
+        if !self.culling.enabled || self.culling.is_summary_visible() {
+            return;
+        }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_cull_pick.kbd_shortcut)) {
+            self.cull_active_img(Some(CullFlag::Pick), image_store, ratings);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_cull_reject.kbd_shortcut)) {
+            self.cull_active_img(Some(CullFlag::Reject), image_store, ratings);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_cull_unflag.kbd_shortcut)) {
+            self.cull_active_img(None, image_store, ratings);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.config.sc_cull_summary.kbd_shortcut)) {
+            self.culling.show_summary();
+        }
+    }
+
     pub fn take_callback(&mut self) -> Option<Callback> {
         self.callback.take()
     }
 
     pub fn show_image(&mut self, ui: &mut Ui, image_store: &ImageStore) -> Response {
+        self.hovered_uv = None;
+        self.minimap_hovered = false;
+
+        let transition = self
+            .slideshow
+            .as_mut()
+            .and_then(|s| s.transition_frame(self.slideshow_config.transition_ms))
+            .filter(|t| self.nr_images_displayed == 1 && t.from_index < self.imgs.len());
+
         egui::CentralPanel::default()
             .frame(self.get_image_frame())
             .show_inside(ui, |ui| {
-                if !self.imgs.is_empty() {
+                if let Some(transition) = transition {
+                    self.show_transition(ui, transition, image_store);
+                } else if !self.imgs.is_empty() {
                     if self.imgs.len() == 1 {
                         ui.centered_and_justified(|ui| {
                             let img: &mut GalleryImage = &mut self.imgs[self.selected_img_index];
-                            img.ui(ui, &self.frame, &mut self.sizing, image_store);
+                            img.ui(
+                                ui,
+                                &self.frame,
+                                &mut self.sizing,
+                                &self.adjustments,
+                                &self.overlays,
+                                image_store,
+                            );
+                            if let Some(uv) = img.hovered_uv() {
+                                self.hovered_uv = Some((self.selected_img_index, uv));
+                            }
+                            self.minimap_hovered = img.is_minimap_hovered();
                         });
                     } else {
                         let w = (ui.available_width() / self.nr_images_displayed as f32) - 1.;
@@ -543,7 +855,18 @@ impl ImageView {
                                             i,
                                         );
                                         let img: &mut GalleryImage = &mut self.imgs[index];
-                                        img.ui(ui, &self.frame, &mut self.sizing, image_store);
+                                        img.ui(
+                                            ui,
+                                            &self.frame,
+                                            &mut self.sizing,
+                                            &self.adjustments,
+                                            &self.overlays,
+                                            image_store,
+                                        );
+                                        if let Some(uv) = img.hovered_uv() {
+                                            self.hovered_uv = Some((index, uv));
+                                        }
+                                        self.minimap_hovered |= img.is_minimap_hovered();
                                     });
                                 });
                             }
@@ -592,7 +915,7 @@ impl ImageView {
             }
 
             self.sizing.scroll_delta = ctx.input(|i| i.smooth_scroll_delta);
-            if ctx.input(|i| i.pointer.is_decidedly_dragging()) {
+            if ctx.input(|i| i.pointer.is_decidedly_dragging()) && !self.minimap_hovered {
                 //drag
                 self.sizing.scroll_delta +=
                     ctx.input(|i| i.pointer.delta()) * ctx.pixels_per_point();
@@ -605,9 +928,45 @@ impl ImageView {
         }
     }
 
-    pub fn handle_callbacks(&mut self, response: &Response) {
+    pub fn handle_callbacks(&mut self, response: &Response, image_store: &mut ImageStore) {
         if let Some(path) = self.get_active_img_path() {
-            let callback = show_context_menu(&self.config.context_menu, response, &path);
+            let entries = &self.config.context_menu;
+            let overlays = &mut self.overlays;
+            let slideshow_running = self.slideshow.is_some();
+            let frame_enabled = self.frame.enabled;
+            let mut toggle_slideshow = false;
+            let mut export_framed = false;
+            let paths = [path.clone()];
+            let callback = show_context_menu_with(entries, response, &paths, |ui| {
+                overlays.menu_ui(ui);
+
+                let slideshow_text = if slideshow_running {
+                    "Stop slideshow"
+                } else {
+                    "Start slideshow"
+                };
+                if ui.button(slideshow_text).clicked() {
+                    toggle_slideshow = true;
+                    ui.close();
+                }
+
+                if frame_enabled && ui.button("Export framed image").clicked() {
+                    export_framed = true;
+                    ui.close();
+                }
+
+                if !entries.is_empty() {
+                    ui.separator();
+                }
+            });
+
+            if toggle_slideshow {
+                self.toggle_slideshow(image_store);
+            }
+
+            if export_framed {
+                self.export_framed_image(image_store);
+            }
 
             if let Some(callback) = callback {
                 self.callback = Some(Callback::from_callback(callback, Some(path)));
@@ -615,52 +974,355 @@ impl ImageView {
         }
     }
 
-    pub fn handle_slideshow(&mut self, ctx: &egui::Context, image_store: &mut ImageStore) {
-        if self.slideshow.is_none() {
+    ///Saves the active image with its frame and caption at the original resolution
+    pub fn export_framed_image(&mut self, image_store: &ImageStore) {
+        let (img, style) = match (self.imgs.get(self.selected_img_index), self.frame.style()) {
+            (Some(img), Some(style)) => (img, style),
+            _ => return,
+        };
+
+        let output = FrameExportOutput {
+            dir: self.config.frame_export_dir.clone(),
+            format: self.config.frame_export_format,
+            quality: self.config.frame_export_quality,
+        };
+
+        self.frame_exports.push(Image::export_framed(
+            img.path.clone(),
+            image_store
+                .get_image_metadata(&img.path)
+                .cloned()
+                .unwrap_or_default(),
+            img.view_transform(),
+            style.clone(),
+            image_store.load_settings(),
+            output,
+        ));
+    }
+
+    fn poll_frame_exports(&mut self, ctx: &egui::Context) {
+        if self.frame_exports.is_empty() {
             return;
         }
 
-        let mut slideshow = self.slideshow.clone().unwrap();
+        let (finished, running): (Vec<_>, Vec<_>) = self
+            .frame_exports
+            .drain(..)
+            .partition(|job| job.is_finished());
+        self.frame_exports = running;
+
+        for job in finished {
+            match job.join() {
+                Ok(Some(path)) => tracing::info!("Exported framed image to {path:?}"),
+                Ok(None) => {}
+                Err(_) => tracing::error!("Failure joining framed image export"),
+            }
+        }
 
-        if slideshow.zoom_step.is_none() {
-            slideshow.set_zoom_step(self.slideshow_config.percent_zoom, self.get_active_img());
+        if !self.frame_exports.is_empty() {
+            ctx.request_repaint_after(Duration::from_millis(200));
         }
+    }
 
-        let mut new_zoom_percentage = if let Some(active_image) = self.get_active_img() {
-            active_image.prev_percentage_zoom
+    pub fn is_slideshow_running(&self) -> bool {
+        self.slideshow.is_some()
+    }
+
+    ///Keeps the slideshow shuffled after its images were replaced
+    pub fn slideshow_images_replaced(&mut self, image_store: &mut ImageStore) {
+        let slideshow = match self.slideshow.as_mut() {
+            Some(slideshow) => slideshow,
+            None => return,
+        };
+
+        //The outgoing image of a running transition may no longer exist
+        slideshow.cancel_transition();
+
+        if self.slideshow_config.shuffle && self.imgs.len() > 1 {
+            if let Some(restore) = slideshow.restore.as_mut() {
+                restore.image_order = Some(self.imgs.iter().map(|img| img.path.clone()).collect());
+            }
+            slideshow::shuffle_with_first(&mut self.imgs, self.selected_img_index);
+            self.selected_img_index = 0;
+            self.load(image_store);
+        }
+    }
+
+    pub fn toggle_slideshow(&mut self, image_store: &mut ImageStore) {
+        if self.slideshow.is_some() {
+            self.stop_slideshow(image_store);
         } else {
-            100.
+            self.start_slideshow(image_store);
+        }
+    }
+
+    pub fn start_slideshow(&mut self, image_store: &mut ImageStore) {
+        let mut slideshow = Slideshow::new(&self.slideshow_config);
+        let mut restore = RestoreState {
+            should_maximize: self.sizing.should_maximize,
+            frame_enabled: self.frame.enabled,
+            minimap_enabled: self.overlays.minimap.enabled,
+            image_order: None,
         };
 
-        if slideshow.last_zoom_instant.elapsed().as_millis() > slideshow.zoom_step_ms {
-            slideshow.last_zoom_instant = Instant::now();
-            new_zoom_percentage += slideshow.zoom_step.unwrap_or(0.);
+        if self.slideshow_config.shuffle && self.imgs.len() > 1 {
+            restore.image_order = Some(self.imgs.iter().map(|img| img.path.clone()).collect());
+            slideshow::shuffle_with_first(&mut self.imgs, self.selected_img_index);
+            self.selected_img_index = 0;
+            self.load(image_store);
         }
 
-        if slideshow.last_adv_instant.elapsed().as_secs() > self.slideshow_config.seconds_per_image
-        {
-            slideshow.last_zoom_instant = Instant::now();
-            slideshow.last_adv_instant = Instant::now();
-            slideshow.zoom_step = None;
+        slideshow.restore = Some(restore);
+        self.slideshow = Some(slideshow);
+
+        self.sizing.should_maximize = true;
+        self.sizing.has_maximized = false;
+        self.overlays.minimap.enabled = false;
+        if self.slideshow_config.start_with_frame_enabled {
+            self.frame.enabled = true;
+        }
+    }
+
+    pub fn stop_slideshow(&mut self, image_store: &mut ImageStore) {
+        let restore = match self.slideshow.take().and_then(|s| s.restore) {
+            Some(restore) => restore,
+            None => return,
+        };
+
+        self.sizing.should_maximize = restore.should_maximize;
+        self.sizing.has_maximized = false;
+        self.frame.enabled = restore.frame_enabled;
+        self.overlays.minimap.enabled = restore.minimap_enabled;
+        self.reset_zoom();
+
+        if let Some(order) = restore.image_order {
+            let active_img_path = self.get_active_img_path();
+            //Images added while the slideshow ran go to the end
+            self.imgs.sort_by_key(|img| {
+                order
+                    .iter()
+                    .position(|path| *path == img.path)
+                    .unwrap_or(usize::MAX)
+            });
+            self.selected_img_index = self
+                .imgs
+                .iter()
+                .position(|img| Some(&img.path) == active_img_path.as_ref())
+                .unwrap_or(0);
+            self.load(image_store);
+        }
+    }
+
+    pub fn handle_slideshow_input(&mut self, ctx: &egui::Context, image_store: &mut ImageStore) {
+        if ctx.input_mut(|i| i.consume_shortcut(&self.slideshow_config.sc_toggle.kbd_shortcut)) {
+            self.toggle_slideshow(image_store);
+        }
+
+        let slideshow = match self.slideshow.as_mut() {
+            Some(slideshow) => slideshow,
+            None => return,
+        };
+
+        if ctx.input_mut(|i| i.consume_shortcut(&self.slideshow_config.sc_pause.kbd_shortcut)) {
+            slideshow.toggle_pause();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.slideshow_config.sc_faster.kbd_shortcut)) {
+            slideshow.faster();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&self.slideshow_config.sc_slower.kbd_shortcut)) {
+            slideshow.slower();
+        }
+    }
+
+    pub fn handle_slideshow(&mut self, ui: &Ui, image_store: &mut ImageStore) {
+        if self.slideshow.is_none() {
+            return;
+        }
+
+        let mut slideshow = self.slideshow.clone().unwrap();
+
+        if slideshow.is_paused() {
+            self.slideshow = Some(slideshow);
+            return;
+        }
+
+        if slideshow.should_advance() {
+            if !self.slideshow_config.repeat && self.selected_img_index + 1 >= self.imgs.len() {
+                self.slideshow = Some(slideshow);
+                self.stop_slideshow(image_store);
+                return;
+            }
+
+            let from_index = self.selected_img_index;
+            let from_zoom_factor = self.sizing.zoom_factor;
+
+            slideshow.advanced();
             self.next_image(image_store);
+
+            if self.selected_img_index != from_index {
+                slideshow.start_transition(
+                    from_index,
+                    from_zoom_factor,
+                    self.slideshow_config.transition,
+                );
+            }
+        }
+
+        let ken_burns_enabled = self.slideshow_config.percent_zoom > 0.;
+        if ken_burns_enabled {
+            self.apply_ken_burns(&mut slideshow, ui.available_size(), image_store);
         }
 
-        if self.slideshow_config.percent_zoom != 0. {
-            self.set_zoom_factor_from_percentage(&new_zoom_percentage, image_store);
-            ctx.request_repaint_after(Duration::from_millis(slideshow.zoom_step_ms as u64));
+        if slideshow.is_transitioning() {
+            ui.ctx().request_repaint();
+        } else if ken_burns_enabled {
+            ui.ctx()
+                .request_repaint_after(slideshow.ken_burns_interval());
         } else {
-            ctx.request_repaint_after(Duration::from_secs(self.slideshow_config.seconds_per_image));
+            ui.ctx()
+                .request_repaint_after(slideshow.time_until_advance());
         }
 
         self.slideshow = Some(slideshow);
     }
 
+    ///Pans and zooms the active image along the Ken Burns motion of the slideshow
+    fn apply_ken_burns(
+        &mut self,
+        slideshow: &mut Slideshow,
+        available: Vec2,
+        image_store: &ImageStore,
+    ) {
+        let img = match self.imgs.get_mut(self.selected_img_index) {
+            Some(img) => img,
+            None => return,
+        };
+
+        //The size of the image is needed to keep the motion inside of it
+        let maximized_zoom = match img.maximized_zoom_factor(available, image_store) {
+            Some(zoom) => zoom,
+            None => return,
+        };
+
+        if slideshow.ken_burns().is_none() {
+            let target = match self.slideshow_config.ken_burns_target {
+                KenBurnsTarget::Random => None,
+                KenBurnsTarget::Center => Some(Pos2::new(0.5, 0.5)),
+                KenBurnsTarget::FocusPoint => Some(
+                    image_store
+                        .get_image_metadata(&img.path)
+                        .and_then(ken_burns::focus_point)
+                        .map(|uv| {
+                            Self::exif_view_transform(image_store.get_image_metadata(&img.path))
+                                .followed_by(&img.view_transform())
+                                .from_texture_uv(uv)
+                        })
+                        .unwrap_or(Pos2::new(0.5, 0.5)),
+                ),
+            };
+
+            slideshow.set_ken_burns(KenBurns::random(
+                1. + self.slideshow_config.percent_zoom / 100.,
+                target,
+            ));
+        }
+
+        let framing = match slideshow.ken_burns() {
+            Some(ken_burns) => ken_burns.framing_at(slideshow.progress()),
+            None => return,
+        };
+
+        let zoom_factor = maximized_zoom * framing.zoom;
+        if let Some(visible) = img.visible_fraction(available, zoom_factor, image_store) {
+            img.set_scroll_center(framing.clamped(visible).center);
+            self.sizing.zoom_factor = zoom_factor;
+            //Already sized, maximizing would undo the zoom for a frame
+            self.sizing.has_maximized = true;
+        }
+    }
+
+    ///Orientation the texture was decoded with
+    fn exif_view_transform(metadata: Option<&HashMap<String, String>>) -> ViewTransform {
+        metadata
+            .and_then(|metadata| metadata.get(METADATA_ORIENTATION))
+            .map(|o| ViewTransform::from_orientation(&Orientation::from_orientation_metadata(o)))
+            .unwrap_or_default()
+    }
+
+    ///Draws the outgoing and the incoming image of a slideshow transition
+    fn show_transition(
+        &mut self,
+        ui: &mut Ui,
+        transition: TransitionFrame,
+        image_store: &ImageStore,
+    ) {
+        let rect = ui.max_rect();
+        let progress = transition.progress;
+
+        let (from_offset, to_offset, from_opacity, to_opacity) =
+            match self.slideshow_config.transition {
+                SlideshowTransition::Slide => (
+                    -progress * rect.width(),
+                    (1. - progress) * rect.width(),
+                    1.,
+                    1.,
+                ),
+                SlideshowTransition::Crossfade => (0., 0., 1. - progress, progress),
+                SlideshowTransition::Cut => (0., 0., 0., 1.),
+            };
+
+        //The outgoing image keeps the zoom it was displayed with
+        let mut from_sizing = GalleryImageSizing {
+            zoom_factor: transition.from_zoom_factor,
+            scroll_delta: Vec2::ZERO,
+            should_maximize: false,
+            has_maximized: true,
+        };
+
+        let layers = [
+            (
+                transition.from_index,
+                from_offset,
+                from_opacity,
+                &mut from_sizing,
+            ),
+            (
+                self.selected_img_index,
+                to_offset,
+                to_opacity,
+                &mut self.sizing,
+            ),
+        ];
+
+        for (index, offset, opacity, sizing) in layers {
+            let mut ui = ui.new_child(
+                UiBuilder::new()
+                    .max_rect(rect.translate(Vec2::new(offset, 0.)))
+                    .layout(egui::Layout::centered_and_justified(
+                        egui::Direction::TopDown,
+                    )),
+            );
+            ui.set_clip_rect(rect);
+            ui.multiply_opacity(opacity);
+
+            self.imgs[index].ui(
+                &mut ui,
+                &self.frame,
+                sizing,
+                &self.adjustments,
+                &self.overlays,
+                image_store,
+            );
+        }
+    }
+
     pub fn show_view_bottom_bar(
         &mut self,
         ui: &mut Ui,
         flattened: bool,
         watcher_enabled: bool,
         image_store: &mut ImageStore,
+        ratings: &Ratings,
     ) {
         Panel::bottom("image_view_bottom_bar")
             .show_separator_line(false)
@@ -698,6 +1360,51 @@ impl ImageView {
                         ui.label("Maximizing");
                     }
 
+                    if self.pixel_inspector.enabled {
+                        ui.label("Inspecting");
+                    }
+
+                    if !self.adjustments.is_default() {
+                        ui.label(self.adjustments.label());
+                    }
+
+                    if self.overlays.focus_peaking.enabled {
+                        ui.label(self.overlays.focus_peaking.label());
+                    }
+
+                    if self.frame.enabled
+                        && let Some(style) = self.frame.style()
+                    {
+                        ui.label(format!("Frame: {}", style.name));
+                    }
+
+                    if !self.frame_exports.is_empty() {
+                        ui.label("Exporting");
+                    }
+
+                    if let Some(slideshow) = &self.slideshow {
+                        ui.label(slideshow.label());
+                    }
+
+                    if self.culling.enabled {
+                        let paths = Self::path_refs(&self.imgs);
+                        ui.label(self.culling.label(&paths, ratings));
+
+                        if let Some(flag) = self
+                            .get_active_img_path()
+                            .and_then(|path| self.culling.get(&path, ratings))
+                        {
+                            ui.label(flag.name());
+                        }
+                    }
+
+                    if let Some(path) = self.get_active_img_path() {
+                        let rating = ratings.get(&path);
+                        if !rating.is_unrated() {
+                            ui.label(rating.label());
+                        }
+                    }
+
                     let mut label = egui::Label::new(self.get_active_img_name(image_store));
                     label = label.truncate();
                     ui.add_sized(
@@ -0,0 +1,156 @@
+use crate::slideshow::ease_in_out;
+use eframe::egui::{Pos2, Vec2};
+use std::collections::HashMap;
+
+//How close the end of the motion gets to the focus point or the center, 1 ends right on it
+const TARGET_BIAS: f32 = 0.75;
+
+///Tags holding the focus point, the first ones found are used
+const SUBJECT_TAGS: &[&str] = &["Subject Area", "Subject Location"];
+///Sony writes the image size followed by the focus point
+const FOCUS_LOCATION_TAG: &str = "Focus Location";
+const WIDTH_TAGS: &[&str] = &["Exif Image Width", "Image Width"];
+const HEIGHT_TAGS: &[&str] = &["Exif Image Height", "Image Height"];
+
+///Part of the image shown by the slideshow
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Framing {
+    ///Relative to the image maximized on screen, 1 shows the whole image
+    pub zoom: f32,
+    ///Normalized coordinates of the displayed image at the center of the screen
+    pub center: Pos2,
+}
+
+impl Framing {
+    ///Moves the center so that the visible part, the given fraction of the image on each axis,
+    ///stays inside the image
+    pub fn clamped(self, visible: Vec2) -> Framing {
+        let half = (visible / 2.).min(Vec2::splat(0.5));
+
+        Framing {
+            zoom: self.zoom,
+            center: Pos2::new(
+                self.center.x.clamp(half.x, 1. - half.x),
+                self.center.y.clamp(half.y, 1. - half.y),
+            ),
+        }
+    }
+}
+
+///Slow pan and zoom between two framings while an image is displayed
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct KenBurns {
+    start: Framing,
+    end: Framing,
+}
+
+impl KenBurns {
+    ///Zooms in or out up to max_zoom in a random direction, the end of the motion is pulled
+    ///towards the target when there is one
+    pub fn random(max_zoom: f32, target: Option<Pos2>) -> KenBurns {
+        let random_point = || Pos2::new(fastrand::f32(), fastrand::f32());
+        let end_center = match target {
+            Some(target) => random_point().lerp(target, TARGET_BIAS),
+            None => random_point(),
+        };
+
+        let (start_zoom, end_zoom) = if fastrand::bool() {
+            (1., max_zoom.max(1.))
+        } else {
+            (max_zoom.max(1.), 1.)
+        };
+
+        KenBurns {
+            start: Framing {
+                zoom: start_zoom,
+                center: random_point(),
+            },
+            end: Framing {
+                zoom: end_zoom,
+                center: end_center,
+            },
+        }
+    }
+
+    ///Framing at a point of the motion, from 0 to 1
+    pub fn framing_at(&self, progress: f32) -> Framing {
+        let t = ease_in_out(progress);
+
+        Framing {
+            zoom: self.start.zoom + (self.end.zoom - self.start.zoom) * t,
+            center: self.start.center.lerp(self.end.center, t),
+        }
+    }
+}
+
+///Focus point written by the camera, in normalized coordinates of the unrotated image
+pub fn focus_point(metadata: &HashMap<String, String>) -> Option<Pos2> {
+    let numbers = |tag: &str| -> Option<Vec<f32>> {
+        let values: Vec<f32> = metadata
+            .get(tag)?
+            .split_whitespace()
+            .filter_map(|v| v.parse::<f32>().ok())
+            .collect();
+        Some(values)
+    };
+    let first_number = |tags: &[&str]| -> Option<f32> {
+        tags.iter()
+            .find_map(|tag| numbers(tag).and_then(|n| n.first().copied()))
+    };
+
+    let point = if let Some(values) = numbers(FOCUS_LOCATION_TAG).filter(|v| v.len() == 4) {
+        Vec2::new(values[2] / values[0], values[3] / values[1])
+    } else {
+        let values = SUBJECT_TAGS
+            .iter()
+            .find_map(|tag| numbers(tag).filter(|v| v.len() >= 2))?;
+        let size = Vec2::new(first_number(WIDTH_TAGS)?, first_number(HEIGHT_TAGS)?);
+        Vec2::new(values[0], values[1]) / size
+    };
+
+    let in_bounds = (0.0..=1.0).contains(&point.x) && (0.0..=1.0).contains(&point.y);
+    in_bounds.then(|| point.to_pos2())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamping_keeps_the_view_inside_the_image() {
+        let framing = Framing {
+            zoom: 2.,
+            center: Pos2::new(0.9, 0.1),
+        };
+
+        let clamped = framing.clamped(Vec2::new(0.5, 1.));
+        assert_eq!(clamped.center, Pos2::new(0.75, 0.5));
+    }
+
+    #[test]
+    fn motion_goes_from_start_to_end() {
+        let ken_burns = KenBurns::random(1.5, Some(Pos2::new(0.5, 0.5)));
+
+        assert_eq!(ken_burns.framing_at(0.), ken_burns.start);
+        assert_eq!(ken_burns.framing_at(1.), ken_burns.end);
+        assert!([1., 1.5].contains(&ken_burns.end.zoom));
+        assert!((ken_burns.end.center.x - 0.5).abs() <= 0.5 * (1. - TARGET_BIAS));
+    }
+
+    #[test]
+    fn reads_focus_point_from_metadata() {
+        let mut metadata = HashMap::new();
+        metadata.insert("Subject Area".to_string(), "1500 500 200 200".to_string());
+        metadata.insert("Exif Image Width".to_string(), "6000".to_string());
+        metadata.insert("Exif Image Height".to_string(), "4000".to_string());
+        assert_eq!(focus_point(&metadata), Some(Pos2::new(0.25, 0.125)));
+
+        metadata.insert(
+            "Focus Location".to_string(),
+            "6000 4000 3000 3000".to_string(),
+        );
+        assert_eq!(focus_point(&metadata), Some(Pos2::new(0.5, 0.75)));
+
+        assert_eq!(focus_point(&HashMap::new()), None);
+    }
+}
@@ -2,12 +2,17 @@ extern crate core;
 
 use eframe::egui;
 
+pub mod albums;
 pub mod app;
 pub mod callback;
 pub mod config;
+pub mod contact_sheet;
 pub mod crawler;
+pub mod culling;
 pub mod db;
+pub mod display_adjustments;
 pub mod dropdown;
+pub mod export;
 pub mod filters;
 pub mod gallery_image;
 pub mod grid_view;
@@ -15,15 +20,26 @@ pub mod icc;
 pub mod image;
 pub mod image_store;
 pub mod image_view;
+pub mod ken_burns;
 pub mod metadata;
 pub mod navigator;
+pub mod overlay;
 pub mod perf_metrics;
+pub mod photo_frame;
+pub mod pixel_inspector;
+pub mod quality;
+pub mod rating;
+pub mod slideshow;
+pub mod tags;
 pub mod theme;
 pub mod thumbnail_image;
 pub mod tree;
 pub mod user_action;
 pub mod utils;
+pub mod view_transform;
+pub mod web_gallery;
 pub mod worker;
+pub mod xmp;
 
 pub const QUALIFIER: &str = "com";
 pub const ORGANIZATION: &str = "avis-imgv";
@@ -150,6 +166,7 @@ pub const VALID_EXTENSIONS: &[&str] = &[
 ];
 pub const SKIP_ORIENT_EXTENSIONS: &[&str] = &[JXL_EXTENSION];
 pub const STARTER_STATE_ARGS: &[&str] = &["--slideshow", "--fullscreen"]; //arguments which change the initial state of the app
+pub const STARTER_VALUE_ARGS: &[&str] = &["--search"]; //same, but followed by a value
 
 pub fn no_icon(
     _ui: &egui::Ui,
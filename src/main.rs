@@ -1,5 +1,8 @@
 use avis_imgv::app::App;
+use avis_imgv::config::{Config, ContactSheetFormat};
+use avis_imgv::contact_sheet::ContactSheetJob;
 use avis_imgv::db::DbRepository;
+use avis_imgv::image::LoadSettings;
 use eframe::egui_wgpu::{WgpuConfiguration, WgpuSetup, WgpuSetupCreateNew};
 use eframe::wgpu::{BackendOptions, Backends, InstanceDescriptor, InstanceFlags, MemoryBudgetThresholds};
 use eframe::{
@@ -29,32 +32,70 @@ fn main() {
     tracing::info!("Starting avis-imgv with args: {}", args.join(","));
 
     if args.len() > 1 && args[1] == "--import" {
-        if args.len() < 3 {
-            tracing::error!("Usage: avis-imgv --import <path>");
+        let Some(path) = path_arg(&args, "Usage: avis-imgv --import <path>") else {
             return;
-        }
-        let path_str = &args[2];
-        let path = PathBuf::from(path_str);
+        };
+
+        tracing::info!("Starting recursive crawl from: {path:?}");
+        let image_paths = avis_imgv::crawler::crawl(&path, true);
+        let mut repo = import_images(&image_paths);
+        avis_imgv::quality::cache_missing_metrics(&mut repo, &image_paths);
+        avis_imgv::metadata::Metadata::clean_moved_files(&mut repo);
+        tracing::info!("Metadata caching finished. Exiting.");
+        return;
+    }
+    if args.len() > 1 && args[1] == "--contact-sheet" {
+        let Some(path) = path_arg(
+            &args,
+            "Usage: avis-imgv --contact-sheet <path> [output.pdf|output.png]",
+        ) else {
+            return;
+        };
 
-        if !path.exists() {
-            tracing::error!("Error: Path does not exist: {path_str}");
+        if !path.is_dir() {
+            tracing::error!("Error: Path is not a directory: {path:?}");
             return;
         }
 
-        tracing::info!("Starting recursive crawl from: {path:?}");
-        let image_paths = avis_imgv::crawler::crawl(&path, true);
-        tracing::info!("Found {} images. Caching metadata...", image_paths.len());
-        let mut repo = DbRepository::new();
-        match repo.init_db() {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("Failure initializing database {e}");
-            }
+        let cfg = Config::new();
+        let mut config = cfg.contact_sheet;
+        let output = match args.get(3) {
+            Some(output) => PathBuf::from(output),
+            None => ContactSheetJob::default_output(&config, &path),
+        };
+        if let Some(extension) = output.extension() {
+            config.format = match extension.to_ascii_lowercase().to_str() {
+                Some("png") => ContactSheetFormat::Png,
+                _ => ContactSheetFormat::Pdf,
+            };
         }
 
-        avis_imgv::metadata::Metadata::cache_metadata_for_images(&mut repo, &image_paths);
-        avis_imgv::metadata::Metadata::clean_moved_files(&mut repo);
-        tracing::info!("Metadata caching finished. Exiting.");
+        let mut image_paths = avis_imgv::crawler::crawl(&path, false);
+        image_paths.sort();
+        let mut repo = import_images(&image_paths);
+
+        let total = image_paths.len();
+        let job = ContactSheetJob {
+            paths: image_paths,
+            config,
+            settings: LoadSettings {
+                output_icc_profile: cfg.general.output_icc_profile,
+                max_texture_size: u32::MAX,
+                raw_exiftool_preview_ext: cfg.general.raw_exiftool_preview_ext,
+                keep_pixels: false,
+                persisted_view_transform: cfg.image_view.persist_view_transform,
+            },
+            title: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            output,
+        };
+        let written = job.run(&mut repo, |index| {
+            tracing::info!("Adding image {} of {total} to the contact sheet", index + 1);
+        });
+        tracing::info!("Contact sheet written to {written:?}. Exiting.");
         return;
     }
     if args.len() > 1 && args[1] == "--help" {
@@ -67,6 +108,12 @@ fn main() {
             "\t --import <path> \n \t\t Imports all images in the directory and sub directories into the database"
         );
         tracing::info!("\t --clean <path> \n \t\t Removes moved/deleted files from the database");
+        tracing::info!(
+            "\t --search <name> \n \t\t Opens the images of a search saved in the Filter & Order panel"
+        );
+        tracing::info!(
+            "\t --contact-sheet <path> [output] \n \t\t Creates a contact sheet of the images in the directory, PNG or PDF depending on the output extension"
+        );
         return;
     }
     if args.len() > 1 && args[1] == "--clean" {
@@ -82,17 +129,53 @@ fn main() {
         fullscreen = true;
         tracing::info!("Starting with fullscreen enabled");
     }
+    let search = args
+        .iter()
+        .position(|arg| arg == "--search")
+        .and_then(|i| args.get(i + 1))
+        .cloned();
+    if let Some(search) = &search {
+        tracing::info!("Starting with the saved search {search}");
+    }
 
     match eframe::run_native(
         "Avis Image Viewer",
         get_native_options(),
-        Box::new(|cc| Ok(Box::new(App::new(cc, slideshow, fullscreen)))),
+        Box::new(|cc| Ok(Box::new(App::new(cc, slideshow, fullscreen, search)))),
     ) {
         Ok(_) => {}
         Err(e) => tracing::error!("{e}"),
     }
 }
 
+///Path given to a subcommand, None when it's missing or doesn't exist
+fn path_arg(args: &[String], usage: &str) -> Option<PathBuf> {
+    let Some(path_str) = args.get(2) else {
+        tracing::error!("{usage}");
+        return None;
+    };
+
+    let path = PathBuf::from(path_str);
+    if !path.exists() {
+        tracing::error!("Error: Path does not exist: {path_str}");
+        return None;
+    }
+
+    Some(path)
+}
+
+///Opens the database and imports the metadata of the images, before a subcommand works on them
+fn import_images(image_paths: &[PathBuf]) -> DbRepository {
+    tracing::info!("Found {} images. Caching metadata...", image_paths.len());
+    let mut repo = DbRepository::new();
+    if let Err(e) = repo.init_db() {
+        panic!("Failure initializing database {e}");
+    }
+
+    avis_imgv::metadata::Metadata::cache_metadata_for_images(&mut repo, image_paths);
+    repo
+}
+
 //Some low powered pcs like raspberry pis can only handle small texture sizes
 //The default for egui w/ wgpu seems to be 8192, which is too high for the
 //RPi5 which can only handle 4096,
@@ -1,14 +1,18 @@
 use crate::RAW_EXTENSIONS;
 use crate::db::DbRepository;
+use crate::rating::{self, MAX_RATING, REJECTED};
+use crate::tags;
 use itertools::Itertools;
 use regex::{self, Regex};
 use std::sync::mpsc;
 use std::{
     collections::HashMap,
-    path::PathBuf,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
     process::{Command, Output, Stdio},
     thread,
-    time::Instant,
+    time::{Instant, UNIX_EPOCH},
 };
 
 //for exiftool, the bigger the chunk the better as the startup time is slow
@@ -17,6 +21,12 @@ pub const METADATA_PROFILE_DESCRIPTION: &str = "Profile Description";
 pub const METADATA_ORIENTATION: &str = "Orientation";
 pub const METADATA_DIRECTORY: &str = "Directory";
 pub const METADATA_DATE: &str = "Date/Time Original";
+pub const METADATA_IMAGE_SIZE: &str = "Image Size";
+pub const METADATA_CAMERA_MODEL: &str = "Camera Model Name";
+pub const METADATA_RATING: &str = "Rating";
+pub const METADATA_LABEL: &str = "Label";
+pub const METADATA_SUBJECT: &str = "Subject";
+pub const METADATA_HIERARCHICAL_SUBJECT: &str = "Hierarchical Subject";
 
 pub enum Orientation {
     Normal,
@@ -43,14 +53,106 @@ impl Orientation {
             _ => Orientation::Normal,
         }
     }
+
+    ///Metadata value exiftool reads for a numeric EXIF orientation
+    pub fn metadata_for_exif_value(value: u8) -> &'static str {
+        match value {
+            2 => "Mirror horizontal",
+            3 => "Rotate 180",
+            4 => "Mirror vertical",
+            5 => "Mirror horizontal and rotate 270 CW",
+            6 => "Rotate 90 CW",
+            7 => "Mirror horizontal and rotate 90 CW",
+            8 => "Rotate 270 CW",
+            _ => "Horizontal (normal)",
+        }
+    }
+
+    ///Whether the image is shown with its width and height swapped
+    pub fn swaps_sides(&self) -> bool {
+        matches!(
+            self,
+            Orientation::MirrorHorizontalRotate270
+                | Orientation::Rotate90CW
+                | Orientation::MirrorHorizontalRotate90CW
+                | Orientation::Rotate270CW
+        )
+    }
+}
+
+///Rating, label and keywords read from XMP, in a sidecar or embedded in the image
+#[derive(Debug, PartialEq)]
+pub struct XmpImport {
+    pub image_path: String,
+    ///Milliseconds since the epoch of the sidecar the values come from, a different value means
+    ///the sidecar changed. None for embedded XMP, which is already in the image metadata.
+    pub sidecar_modified: Option<i64>,
+    pub rating: i8,
+    pub label: Option<String>,
+    ///Same names as the tags exiftool reads from embedded XMP
+    pub tags: HashMap<String, String>,
 }
 
 pub struct Metadata {}
 
+impl XmpImport {
+    pub fn new(
+        image_path: String,
+        sidecar_modified: Option<i64>,
+        tags: HashMap<String, String>,
+    ) -> XmpImport {
+        let rating = tags
+            .get(METADATA_RATING)
+            .and_then(|r| r.parse::<i8>().ok())
+            .unwrap_or_default()
+            .clamp(REJECTED, MAX_RATING);
+        let label = tags
+            .get(METADATA_LABEL)
+            .filter(|l| !l.trim().is_empty())
+            .cloned();
+
+        XmpImport {
+            image_path,
+            sidecar_modified,
+            rating,
+            label,
+            tags,
+        }
+    }
+
+    ///Nothing to import, no rating, label or keywords
+    pub fn is_empty(&self) -> bool {
+        self.rating == 0 && self.label.is_none() && self.keywords().is_none()
+    }
+
+    ///Hierarchical keywords when the sidecar has any, otherwise its flat ones
+    pub fn keywords(&self) -> Option<Vec<String>> {
+        let (value, hierarchical) = match self.tags.get(METADATA_HIERARCHICAL_SUBJECT) {
+            Some(value) => (value, true),
+            None => (self.tags.get(METADATA_SUBJECT)?, false),
+        };
+
+        Some(
+            value
+                .split(", ")
+                .filter_map(|keyword| {
+                    if hierarchical {
+                        tags::from_xmp_hierarchy(keyword)
+                    } else {
+                        tags::normalize(keyword)
+                    }
+                })
+                .unique()
+                .collect(),
+        )
+    }
+}
+
 impl Metadata {
     pub fn cache_metadata_for_images(db_repo: &mut DbRepository, image_paths: &[PathBuf]) {
         let timer = Instant::now();
 
+        let all_image_paths = image_paths;
         let mut image_paths = image_paths
             .iter()
             .map(|p| p.to_string_lossy().to_string())
@@ -71,7 +173,33 @@ impl Metadata {
             cached_paths.len()
         );
 
-        image_paths.retain(|x| !cached_paths.contains(x));
+        let cached_paths = cached_paths
+            .into_iter()
+            .collect::<HashMap<String, Option<i64>>>();
+        let modified = all_image_paths
+            .iter()
+            .filter_map(|p| Some((p.to_string_lossy().to_string(), modified_millis(p)?)))
+            .collect::<HashMap<String, i64>>();
+
+        //Rows cached before modification times were stored are taken as up to date
+        let untracked = cached_paths
+            .iter()
+            .filter(|(_, cached)| cached.is_none())
+            .filter_map(|(path, _)| Some((path.clone(), *modified.get(path)?)))
+            .collect::<Vec<(String, i64)>>();
+        if let Err(e) = db_repo.set_files_modified(&untracked) {
+            tracing::error!("Failure saving file modification times -> {e}");
+        }
+
+        //New images and the ones changed since they were cached, by another application for example
+        image_paths.retain(|path| match cached_paths.get(path) {
+            None => true,
+            Some(cached) => cached.is_some_and(|cached| {
+                modified
+                    .get(path)
+                    .is_some_and(|modified| *modified != cached)
+            }),
+        });
 
         tracing::info!("Retained a total of {} images to cache", image_paths.len());
 
@@ -107,15 +235,13 @@ impl Metadata {
                 let chunk = chunk.to_vec();
                 let handle = thread::spawn(move || {
                     let cmd = Command::new("exiftool")
-                        .args(chunk)
+                        .args(&chunk)
                         .stdout(Stdio::piped())
                         .spawn();
 
                     match cmd {
                         Ok(cmd) => match cmd.wait_with_output() {
-                            Ok(output) => {
-                                tx.send(output).unwrap();
-                            }
+                            Ok(output) => tx.send(output).unwrap(),
                             Err(e) => tracing::error!("Error fetching metadata -> {e}"),
                         },
                         Err(e) => tracing::error!("Error fetching metadata -> {e}"),
@@ -160,12 +286,174 @@ impl Metadata {
             }
         }
 
+        let read_paths = image_paths
+            .iter()
+            .filter_map(|path| Some((path.clone(), *modified.get(path)?)))
+            .collect::<Vec<(String, i64)>>();
+        if let Err(e) = db_repo.set_files_modified(&read_paths) {
+            tracing::error!("Failure saving file modification times -> {e}");
+        }
+
+        Self::import_embedded_xmp(db_repo, &image_paths, &cached_paths);
+
+        Self::import_xmp_sidecars(db_repo, all_image_paths);
+
         tracing::info!(
             "Finished caching metadata for all images in {}ms",
             timer.elapsed().as_millis()
         );
     }
 
+    ///Imports the rating, label and keywords embedded in the images which were just read. Images
+    ///with a sidecar take them from it instead. New images only import what they have, so values
+    ///set in the app before the image got cached aren't cleared.
+    fn import_embedded_xmp(
+        db_repo: &mut DbRepository,
+        read_paths: &[String],
+        cached_paths: &HashMap<String, Option<i64>>,
+    ) {
+        let paths = read_paths
+            .iter()
+            .filter(|path| rating::existing_sidecar(Path::new(path)).is_none())
+            .cloned()
+            .collect::<Vec<String>>();
+
+        if paths.is_empty() {
+            return;
+        }
+
+        let imports = match db_repo.get_embedded_xmp_tags(&paths) {
+            Ok(tags) => tags
+                .into_iter()
+                .map(|(path, tags)| XmpImport::new(path, None, tags))
+                .filter(|import| {
+                    cached_paths.contains_key(&import.image_path) || !import.is_empty()
+                })
+                .collect::<Vec<XmpImport>>(),
+            Err(e) => {
+                tracing::error!("Failure fetching embedded XMP -> {e}");
+                return;
+            }
+        };
+
+        if let Err(e) = db_repo.import_xmp(&imports) {
+            tracing::error!("Failure importing embedded XMP -> {e}");
+        }
+    }
+
+    ///Merges the XMP sidecars which are new or changed since they were last imported
+    pub fn import_xmp_sidecars(db_repo: &mut DbRepository, image_paths: &[PathBuf]) {
+        let paths = image_paths
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+
+        let imported = match db_repo.get_imported_sidecars(&paths) {
+            Ok(imported) => imported.into_iter().collect::<HashMap<String, i64>>(),
+            Err(e) => {
+                tracing::error!("Failure fetching imported sidecars -> {e}");
+                return;
+            }
+        };
+
+        let changed = image_paths
+            .iter()
+            .filter_map(|path| {
+                let sidecar = rating::existing_sidecar(path)?;
+                let modified = modified_millis(&sidecar)?;
+                let path = path.to_string_lossy().to_string();
+
+                (imported.get(&path) != Some(&modified)).then_some((path, sidecar, modified))
+            })
+            .collect::<Vec<(String, PathBuf, i64)>>();
+
+        if changed.is_empty() {
+            return;
+        }
+
+        tracing::info!("Importing {} new or changed XMP sidecars", changed.len());
+
+        for chunk in changed.chunks(*CHUNK_SIZE) {
+            let output = Command::new("exiftool")
+                .arg("-json")
+                .arg("-XMP-xmp:Rating")
+                .arg("-XMP-xmp:Label")
+                .arg("-XMP-dc:Subject")
+                .arg("-XMP-lr:HierarchicalSubject")
+                .args(chunk.iter().map(|(_, sidecar, _)| sidecar))
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .output();
+
+            let output = match output {
+                Ok(output) => output,
+                Err(e) => {
+                    tracing::error!("Failure spawning exiftool process -> {e}");
+                    return;
+                }
+            };
+
+            let values = Self::parse_xmp_json(&String::from_utf8_lossy(&output.stdout));
+            let sidecars = chunk
+                .iter()
+                .map(|(path, sidecar, modified)| {
+                    let tags = values
+                        .get(sidecar.to_string_lossy().as_ref())
+                        .cloned()
+                        .unwrap_or_default();
+                    XmpImport::new(path.clone(), Some(*modified), tags)
+                })
+                .collect::<Vec<XmpImport>>();
+
+            if let Err(e) = db_repo.import_xmp(&sidecars) {
+                tracing::error!("Failure importing XMP sidecars -> {e}");
+            }
+        }
+    }
+
+    ///Tags of each file in exiftool's json output, by source file. Lists are joined like exiftool
+    ///does in its default output.
+    pub fn parse_xmp_json(output: &str) -> HashMap<String, HashMap<String, String>> {
+        let files: Vec<serde_json::Map<String, serde_json::Value>> =
+            match serde_json::from_str(output) {
+                Ok(files) => files,
+                Err(e) => {
+                    tracing::error!("Failure parsing exiftool json output -> {e}");
+                    return HashMap::new();
+                }
+            };
+
+        let value_to_string = |value: &serde_json::Value| match value {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            serde_json::Value::Array(values) => Some(
+                values
+                    .iter()
+                    .map(|v| v.as_str().map(String::from).unwrap_or(v.to_string()))
+                    .join(", "),
+            ),
+            _ => None,
+        };
+
+        files
+            .into_iter()
+            .filter_map(|file| {
+                let source = file.get("SourceFile")?.as_str()?.to_string();
+                let tags = [
+                    ("Rating", METADATA_RATING),
+                    ("Label", METADATA_LABEL),
+                    ("Subject", METADATA_SUBJECT),
+                    ("HierarchicalSubject", METADATA_HIERARCHICAL_SUBJECT),
+                ]
+                .into_iter()
+                .filter_map(|(key, tag)| Some((tag.to_string(), value_to_string(file.get(key)?)?)))
+                .collect();
+
+                Some((source, tags))
+            })
+            .collect()
+    }
+
     pub fn parse_exiftool_output(
         db_repo: &mut DbRepository,
         output: &Output,
@@ -273,6 +561,126 @@ impl Metadata {
             .map(|(_, metadata)| metadata)
     }
 
+    ///Writes the numeric EXIF orientation tag in place
+    pub fn write_orientation(path: &Path, orientation: u8) -> bool {
+        let cmd = Command::new("exiftool")
+            .arg("-overwrite_original")
+            .arg("-n")
+            .arg(format!("-Orientation={orientation}"))
+            .arg(path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+
+        match cmd {
+            Ok(cmd) => match cmd.wait_with_output() {
+                Ok(output) if output.status.success() => true,
+                Ok(output) => {
+                    tracing::error!(
+                        "Failure writing orientation -> {}",
+                        String::from_utf8_lossy(&output.stderr)
+                    );
+                    false
+                }
+                Err(e) => {
+                    tracing::error!("Failure waiting for exiftool process -> {e}");
+                    false
+                }
+            },
+            Err(e) => {
+                tracing::error!("Failure spawning exiftool process -> {e}");
+                false
+            }
+        }
+    }
+
+    ///Runs exiftool tag assignments on the file, creating it when it's a missing sidecar
+    pub fn write_xmp(path: &Path, assignments: &[String]) -> bool {
+        let cmd = Command::new("exiftool")
+            .args(Self::write_xmp_args(path, assignments, path.exists()))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+
+        match cmd {
+            Ok(cmd) => match cmd.wait_with_output() {
+                Ok(output) if output.status.success() => true,
+                Ok(output) => {
+                    tracing::error!(
+                        "Failure writing XMP to {path:?} -> {}",
+                        String::from_utf8_lossy(&output.stderr)
+                    );
+                    false
+                }
+                Err(e) => {
+                    tracing::error!("Failure waiting for exiftool process -> {e}");
+                    false
+                }
+            },
+            Err(e) => {
+                tracing::error!("Failure spawning exiftool process -> {e}");
+                false
+            }
+        }
+    }
+
+    ///exiftool only edits existing files in place, a new XMP file is written from scratch with -o
+    fn write_xmp_args(path: &Path, assignments: &[String], exists: bool) -> Vec<OsString> {
+        let mut args: Vec<OsString> = assignments.iter().map(OsString::from).collect();
+
+        if exists {
+            args.insert(0, OsString::from("-overwrite_original"));
+        } else {
+            args.push(OsString::from("-o"));
+        }
+        args.push(path.as_os_str().to_owned());
+
+        args
+    }
+
+    ///Copies the metadata of an image to an exported copy of it. The orientation and ICC profile
+    ///are left out since the exported pixels are already oriented and converted.
+    pub fn copy_metadata(source: &Path, target: &Path, strip_gps: bool) -> bool {
+        let mut cmd = Command::new("exiftool");
+        cmd.arg("-overwrite_original")
+            .arg("-TagsFromFile")
+            .arg(source)
+            .arg("-all:all")
+            .arg("--Orientation")
+            .arg("--ICC_Profile:all");
+
+        if strip_gps {
+            cmd.arg("--GPS:all").arg("--XMP-exif:GPS*");
+        }
+
+        let cmd = cmd
+            .arg(target)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+
+        match cmd {
+            Ok(cmd) => match cmd.wait_with_output() {
+                Ok(output) if output.status.success() => true,
+                Ok(output) => {
+                    tracing::error!(
+                        "Failure copying metadata to {target:?} -> {}",
+                        String::from_utf8_lossy(&output.stderr)
+                    );
+                    false
+                }
+                Err(e) => {
+                    tracing::error!("Failure waiting for exiftool process -> {e}");
+                    false
+                }
+            },
+            Err(e) => {
+                tracing::error!("Failure spawning exiftool process -> {e}");
+                false
+            }
+        }
+    }
+
     pub fn extract_icc_from_image(path: &PathBuf) -> Option<Vec<u8>> {
         let cmd = Command::new("exiftool")
             .arg("-icc_profile")
@@ -305,7 +713,7 @@ impl Metadata {
     pub fn format_string_with_metadata(input: &str, metadata: &HashMap<String, String>) -> String {
         let mut output = String::from(input);
 
-        let tag_regex = Regex::new("(\\$\\(([^\\(\\)]*#([\\w \\s]*)#[^\\(\\)]*)\\))").unwrap();
+        let tag_regex = Regex::new("(\\$\\(([^\\(\\)]*#([\\w \\s/]*)#[^\\(\\)]*)\\))").unwrap();
 
         for cap_group in tag_regex.captures_iter(input) {
             //Whole string including  $()
@@ -338,6 +746,21 @@ impl Metadata {
         output
     }
 
+    ///Width over height as displayed, from exiftool's `6000x4000` image size and the orientation
+    pub fn aspect_ratio(image_size: &str, orientation: Option<&str>) -> Option<f32> {
+        let (width, height) = image_size.split_once(['x', ' '])?;
+        let width: f32 = width.trim().parse().ok()?;
+        let height: f32 = height.trim().parse().ok()?;
+        if width <= 0. || height <= 0. {
+            return None;
+        }
+
+        match orientation.map(Orientation::from_orientation_metadata) {
+            Some(orientation) if orientation.swaps_sides() => Some(height / width),
+            _ => Some(width / height),
+        }
+    }
+
     pub fn clean_moved_files(db_repo: &mut DbRepository) {
         //This can be a bit heavy if the user has lots of files. We are talking in the millions
         //though... Highly unlikely. Even with 250k images it will use less than 100MB of ram
@@ -431,9 +854,35 @@ impl Metadata {
     }
 }
 
+///Modification time of the file in milliseconds since the epoch
+fn modified_millis(path: &Path) -> Option<i64> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_millis() as i64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::view_transform::ViewTransform;
+
+    #[test]
+    fn missing_sidecars_are_created_with_exiftool_output() {
+        let sidecar = Path::new("/photos/img.jpg.xmp");
+        let assignments = ["-XMP-xmp:Rating=3".to_string()];
+
+        assert_eq!(
+            Metadata::write_xmp_args(sidecar, &assignments, true),
+            [
+                "-overwrite_original",
+                "-XMP-xmp:Rating=3",
+                "/photos/img.jpg.xmp"
+            ]
+        );
+        assert_eq!(
+            Metadata::write_xmp_args(sidecar, &assignments, false),
+            ["-XMP-xmp:Rating=3", "-o", "/photos/img.jpg.xmp"]
+        );
+    }
 
     #[test]
     fn test_format_string_with_metadata() {
@@ -447,6 +896,91 @@ mod tests {
             Metadata::format_string_with_metadata(input, &metadata),
             "test.jpg • ƒ5.0 • 500 ISO".to_string()
         );
+
+        metadata.insert(
+            "Date/Time Original".to_string(),
+            "2024:05:01 10:00:00".to_string(),
+        );
+        assert_eq!(
+            Metadata::format_string_with_metadata("$(#Date/Time Original#)", &metadata),
+            "2024:05:01 10:00:00".to_string()
+        );
+    }
+
+    #[test]
+    fn test_aspect_ratio() {
+        assert_eq!(Metadata::aspect_ratio("6000x4000", None), Some(1.5));
+        assert_eq!(Metadata::aspect_ratio("6000 4000", None), Some(1.5));
+        assert_eq!(
+            Metadata::aspect_ratio("6000x4000", Some("Rotate 90 CW")),
+            Some(4000. / 6000.)
+        );
+        assert_eq!(
+            Metadata::aspect_ratio("6000x4000", Some("Rotate 180")),
+            Some(1.5)
+        );
+        assert_eq!(Metadata::aspect_ratio("6000x0", None), None);
+        assert_eq!(Metadata::aspect_ratio("unknown", None), None);
+    }
+
+    #[test]
+    fn test_parse_xmp_json() {
+        let output = r#"[{
+            "SourceFile": "/photos/DSCF0001.RAF.xmp",
+            "Rating": 4,
+            "Label": "Red",
+            "Subject": ["Alice", "Lisbon"],
+            "HierarchicalSubject": ["People|Alice", "Places|Lisbon"]
+        }, {
+            "SourceFile": "/photos/DSCF0002.xmp",
+            "Rating": -1
+        }]"#;
+
+        let values = Metadata::parse_xmp_json(output);
+        let first = XmpImport::new(
+            "/photos/DSCF0001.RAF".to_string(),
+            Some(0),
+            values["/photos/DSCF0001.RAF.xmp"].clone(),
+        );
+        assert_eq!(first.rating, 4);
+        assert_eq!(first.label, Some("Red".to_string()));
+        assert_eq!(first.tags[METADATA_SUBJECT], "Alice, Lisbon");
+        assert_eq!(
+            first.tags[METADATA_HIERARCHICAL_SUBJECT],
+            "People|Alice, Places|Lisbon"
+        );
+
+        let second = XmpImport::new(
+            "/photos/DSCF0002.RAF".to_string(),
+            Some(0),
+            values["/photos/DSCF0002.xmp"].clone(),
+        );
+        assert_eq!(second.rating, REJECTED);
+        assert_eq!(second.label, None);
+
+        assert_eq!(
+            first.keywords(),
+            Some(vec![
+                "People/Alice".to_string(),
+                "Places/Lisbon".to_string()
+            ])
+        );
+        assert_eq!(second.keywords(), None);
+    }
+
+    #[test]
+    fn embedded_ratings_are_clamped() {
+        let tags =
+            |rating: &str| HashMap::from([(METADATA_RATING.to_string(), rating.to_string())]);
+
+        let high = XmpImport::new("/photos/a.jpg".to_string(), None, tags("7"));
+        let low = XmpImport::new("/photos/b.jpg".to_string(), None, tags("-3"));
+        let unrated = XmpImport::new("/photos/c.jpg".to_string(), None, tags("0"));
+
+        assert_eq!(high.rating, MAX_RATING);
+        assert_eq!(low.rating, REJECTED);
+        assert!(unrated.is_empty());
+        assert!(!high.is_empty());
     }
 
     #[test]
@@ -531,4 +1065,15 @@ mod tests {
         let result = Metadata::group_raw_jpg_paths(&paths);
         assert_eq!(result, Vec::<PathBuf>::new());
     }
+    #[test]
+    fn written_orientations_are_cached_as_exiftool_reads_them() {
+        for value in 1..=8 {
+            let orientation =
+                Orientation::from_orientation_metadata(Orientation::metadata_for_exif_value(value));
+            assert_eq!(
+                ViewTransform::from_orientation(&orientation).exif_orientation(),
+                value
+            );
+        }
+    }
 }
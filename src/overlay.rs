@@ -0,0 +1,341 @@
+use crate::config::{Corner, ImageViewConfig};
+use crate::display_adjustments::FocusPeaking;
+use eframe::egui::{self, Painter, Ui};
+use epaint::{Color32, Pos2, Rect, Stroke, Vec2};
+
+const GOLDEN_RATIO_SECTION: f32 = 0.381_966; // 1 - 1/φ
+const GUIDE_COLOR: Color32 = Color32::from_rgba_premultiplied(200, 200, 200, 200);
+const MASK_COLOR: Color32 = Color32::from_rgba_premultiplied(0, 0, 0, 170);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Guide {
+    RuleOfThirds,
+    GoldenRatio,
+    Diagonals,
+    Center,
+}
+
+impl Guide {
+    pub const ALL: [Guide; 4] = [
+        Guide::RuleOfThirds,
+        Guide::GoldenRatio,
+        Guide::Diagonals,
+        Guide::Center,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Guide::RuleOfThirds => "Rule of thirds",
+            Guide::GoldenRatio => "Golden ratio",
+            Guide::Diagonals => "Diagonals",
+            Guide::Center => "Center",
+        }
+    }
+
+    ///Lines in normalized image coordinates
+    pub fn lines(&self) -> Vec<[Pos2; 2]> {
+        match self {
+            Guide::RuleOfThirds => grid_lines(&[1. / 3., 2. / 3.]),
+            Guide::GoldenRatio => grid_lines(&[GOLDEN_RATIO_SECTION, 1. - GOLDEN_RATIO_SECTION]),
+            Guide::Diagonals => vec![
+                [Pos2::new(0., 0.), Pos2::new(1., 1.)],
+                [Pos2::new(1., 0.), Pos2::new(0., 1.)],
+            ],
+            Guide::Center => grid_lines(&[0.5]),
+        }
+    }
+}
+
+fn grid_lines(positions: &[f32]) -> Vec<[Pos2; 2]> {
+    positions
+        .iter()
+        .flat_map(|p| {
+            [
+                [Pos2::new(*p, 0.), Pos2::new(*p, 1.)],
+                [Pos2::new(0., *p), Pos2::new(1., *p)],
+            ]
+        })
+        .collect()
+}
+
+///Aspect ratio in the "width:height" format, ex: 4:5
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CropRatio {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl CropRatio {
+    pub fn parse(ratio: &str) -> Option<CropRatio> {
+        let (width, height) = ratio.split_once(':')?;
+        let width = width.trim().parse::<f32>().ok()?;
+        let height = height.trim().parse::<f32>().ok()?;
+
+        if width > 0. && height > 0. {
+            Some(CropRatio { width, height })
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!("{}:{}", self.width, self.height)
+    }
+
+    ///Largest centered rect with this ratio in normalized coordinates of an image of the given size
+    pub fn crop_rect(&self, image_size: Vec2) -> Rect {
+        let image_ratio = image_size.x / image_size.y;
+        let ratio = self.width / self.height;
+
+        let size = if ratio > image_ratio {
+            Vec2::new(1., image_ratio / ratio)
+        } else {
+            Vec2::new(ratio / image_ratio, 1.)
+        };
+
+        Rect::from_center_size(Pos2::new(0.5, 0.5), size)
+    }
+}
+
+pub struct MinimapSettings {
+    pub enabled: bool,
+    pub corner: Corner,
+    ///Length of the longest side
+    pub size: f32,
+    ///0 keeps it always visible
+    pub hide_after_secs: f32,
+}
+
+impl MinimapSettings {
+    const MARGIN: f32 = 10.;
+
+    ///Where the minimap is placed inside the displayed image
+    pub fn rect(&self, image_rect: Rect, image_size: Vec2) -> Rect {
+        let size = image_size * (self.size / image_size.max_elem());
+        let min = match self.corner {
+            Corner::TopLeft => image_rect.min + Vec2::splat(Self::MARGIN),
+            Corner::TopRight => Pos2::new(
+                image_rect.max.x - Self::MARGIN - size.x,
+                image_rect.min.y + Self::MARGIN,
+            ),
+            Corner::BottomLeft => Pos2::new(
+                image_rect.min.x + Self::MARGIN,
+                image_rect.max.y - Self::MARGIN - size.y,
+            ),
+            Corner::BottomRight => image_rect.max - Vec2::splat(Self::MARGIN) - size,
+        };
+
+        Rect::from_min_size(min, size)
+    }
+}
+
+///Composition guides, crop masks, focus peaking and the minimap drawn on top of the displayed
+///image
+pub struct Overlays {
+    pub guide: Option<Guide>,
+    pub crop_ratios: Vec<CropRatio>,
+    ///Index into crop_ratios
+    pub crop_mask: Option<usize>,
+    pub minimap: MinimapSettings,
+    pub focus_peaking: FocusPeaking,
+}
+
+impl Overlays {
+    pub fn new(config: &ImageViewConfig) -> Overlays {
+        Overlays {
+            guide: None,
+            minimap: MinimapSettings {
+                enabled: config.minimap_enabled,
+                corner: config.minimap_corner,
+                size: config.minimap_size,
+                hide_after_secs: config.minimap_hide_after_secs,
+            },
+            crop_ratios: config
+                .crop_ratios
+                .iter()
+                .filter_map(|ratio| {
+                    let parsed = CropRatio::parse(ratio);
+                    if parsed.is_none() {
+                        tracing::error!("Invalid crop ratio({ratio}) in configuration");
+                    }
+                    parsed
+                })
+                .collect(),
+            crop_mask: None,
+            focus_peaking: FocusPeaking::new(config),
+        }
+    }
+
+    ///No guide or crop mask is shown
+    pub fn is_empty(&self) -> bool {
+        self.guide.is_none() && self.crop_mask.is_none()
+    }
+
+    ///Goes through every guide and then back to none
+    pub fn cycle_guide(&mut self) {
+        self.guide = match self.guide {
+            None => Some(Guide::ALL[0]),
+            Some(guide) => Guide::ALL
+                .iter()
+                .position(|g| *g == guide)
+                .and_then(|i| Guide::ALL.get(i + 1))
+                .copied(),
+        };
+    }
+
+    ///Goes through every crop ratio and then back to none
+    pub fn cycle_crop_mask(&mut self) {
+        self.crop_mask = match self.crop_mask {
+            None if !self.crop_ratios.is_empty() => Some(0),
+            Some(i) if i + 1 < self.crop_ratios.len() => Some(i + 1),
+            _ => None,
+        };
+    }
+
+    ///Paints the guides and crop mask over the image displayed in rect, visible_rect_normalized
+    ///being the part of the image that is visible, image_size its displayed size
+    pub fn paint(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        visible_rect_normalized: &Rect,
+        image_size: Vec2,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+
+        let painter = painter.with_clip_rect(rect);
+        let to_screen = |pos: Pos2| {
+            rect.min
+                + ((pos - visible_rect_normalized.min) / visible_rect_normalized.size())
+                    * rect.size()
+        };
+
+        if let Some(ratio) = self.crop_mask.and_then(|i| self.crop_ratios.get(i)) {
+            let crop_rect = ratio.crop_rect(image_size);
+            let crop = Rect::from_min_max(to_screen(crop_rect.min), to_screen(crop_rect.max));
+            let image = Rect::from_min_max(to_screen(Pos2::ZERO), to_screen(Pos2::new(1., 1.)));
+
+            for mask in [
+                Rect::from_x_y_ranges(image.x_range(), image.min.y..=crop.min.y),
+                Rect::from_x_y_ranges(image.x_range(), crop.max.y..=image.max.y),
+                Rect::from_x_y_ranges(image.min.x..=crop.min.x, crop.y_range()),
+                Rect::from_x_y_ranges(crop.max.x..=image.max.x, crop.y_range()),
+            ] {
+                if mask.is_positive() {
+                    painter.rect_filled(mask, 0., MASK_COLOR);
+                }
+            }
+
+            painter.rect_stroke(
+                crop,
+                0.,
+                Stroke::new(1., GUIDE_COLOR),
+                egui::StrokeKind::Inside,
+            );
+        }
+
+        if let Some(guide) = self.guide {
+            for [from, to] in guide.lines() {
+                painter.line_segment(
+                    [to_screen(from), to_screen(to)],
+                    Stroke::new(1., GUIDE_COLOR),
+                );
+            }
+        }
+    }
+
+    ///Entries for the Image View context menu
+    pub fn menu_ui(&mut self, ui: &mut Ui) {
+        ui.menu_button("Overlays", |ui| {
+            ui.radio_value(&mut self.guide, None, "No guide");
+            for guide in Guide::ALL {
+                ui.radio_value(&mut self.guide, Some(guide), guide.name());
+            }
+
+            ui.separator();
+
+            ui.radio_value(&mut self.crop_mask, None, "No crop mask");
+            for (i, ratio) in self.crop_ratios.iter().enumerate() {
+                ui.radio_value(&mut self.crop_mask, Some(i), ratio.name());
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_crop_ratios() {
+        assert_eq!(
+            CropRatio::parse("4:5"),
+            Some(CropRatio {
+                width: 4.,
+                height: 5.
+            })
+        );
+        assert_eq!(CropRatio::parse("2.39 : 1").map(|r| r.width), Some(2.39));
+        assert_eq!(CropRatio::parse("16x9"), None);
+        assert_eq!(CropRatio::parse("0:1"), None);
+    }
+
+    #[test]
+    fn crop_rect_fits_inside_the_image() {
+        let square = CropRatio::parse("1:1").unwrap();
+        let crop = square.crop_rect(Vec2::new(300., 200.));
+        assert!((crop.width() - 2. / 3.).abs() < 1e-6);
+        assert_eq!(crop.height(), 1.);
+        assert_eq!(crop.center(), Pos2::new(0.5, 0.5));
+
+        let wide = CropRatio::parse("16:9").unwrap();
+        let crop = wide.crop_rect(Vec2::new(300., 200.));
+        assert_eq!(crop.width(), 1.);
+        assert!(crop.height() < 1.);
+    }
+
+    #[test]
+    fn cycling_ends_with_no_overlay() {
+        let mut overlays = Overlays::new(&ImageViewConfig {
+            crop_ratios: vec!["1:1".to_string(), "invalid".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(overlays.crop_ratios.len(), 1);
+
+        overlays.cycle_crop_mask();
+        assert_eq!(overlays.crop_mask, Some(0));
+        overlays.cycle_crop_mask();
+        assert_eq!(overlays.crop_mask, None);
+
+        for _ in Guide::ALL {
+            overlays.cycle_guide();
+        }
+        assert_eq!(overlays.guide, Some(Guide::Center));
+        overlays.cycle_guide();
+        assert!(overlays.is_empty());
+    }
+
+    #[test]
+    fn minimap_is_placed_in_the_configured_corner() {
+        let mut minimap = MinimapSettings {
+            enabled: true,
+            corner: Corner::BottomRight,
+            size: 100.,
+            hide_after_secs: 0.,
+        };
+        let image_rect = Rect::from_min_size(Pos2::ZERO, Vec2::new(1000., 500.));
+        let image_size = Vec2::new(400., 200.);
+
+        let rect = minimap.rect(image_rect, image_size);
+        assert_eq!(rect.size(), Vec2::new(100., 50.));
+        assert_eq!(rect.max, Pos2::new(990., 490.));
+
+        minimap.corner = Corner::TopLeft;
+        assert_eq!(
+            minimap.rect(image_rect, image_size).min,
+            Pos2::new(10., 10.)
+        );
+    }
+}
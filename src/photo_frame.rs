@@ -0,0 +1,256 @@
+use crate::config::{SlideshowConfig, default_clock_format};
+use crate::db::DbRepository;
+use crate::filters::FilterQuery;
+use crate::image_store::ImageStore;
+use crate::metadata::Metadata;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Local, NaiveTime, Timelike};
+use eframe::egui::{self, Align2, Color32, FontId, Rect, Ui, Vec2};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const TEXT_MARGIN: f32 = 20.;
+const TEXT_PADDING: f32 = 8.;
+const QUIET_HOURS_CHECK: Duration = Duration::from_secs(30);
+
+///Time of the day during which the slideshow blanks the screen, can span midnight
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn new(start: &Option<String>, end: &Option<String>) -> Option<QuietHours> {
+        let parse = |time: &str| match NaiveTime::parse_from_str(time, "%H:%M") {
+            Ok(time) => Some(time),
+            Err(e) => {
+                tracing::error!(
+                    "Invalid quiet hours time({time}) in configuration, expected HH:MM: {e}"
+                );
+                None
+            }
+        };
+
+        Some(QuietHours {
+            start: parse(start.as_ref()?)?,
+            end: parse(end.as_ref()?)?,
+        })
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            time >= self.start && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+///Caption, clock and quiet hours shown while the slideshow runs
+pub struct PhotoFrame {
+    quiet_hours: Option<QuietHours>,
+    caption_format: String,
+    clock_format: Option<String>,
+    text_size: f32,
+    ///Caption of the last displayed image
+    caption: Option<(PathBuf, String)>,
+}
+
+impl PhotoFrame {
+    pub fn new(cfg: &SlideshowConfig) -> PhotoFrame {
+        let clock_format = if !cfg.show_clock {
+            None
+        } else if StrftimeItems::new(&cfg.clock_format).any(|item| item == Item::Error) {
+            tracing::error!(
+                "Invalid clock format({}) in configuration",
+                cfg.clock_format
+            );
+            Some(default_clock_format())
+        } else {
+            Some(cfg.clock_format.clone())
+        };
+
+        PhotoFrame {
+            quiet_hours: QuietHours::new(&cfg.quiet_hours_start, &cfg.quiet_hours_end),
+            caption_format: cfg.caption_format.clone(),
+            clock_format,
+            text_size: cfg.overlay_text_size,
+            caption: None,
+        }
+    }
+
+    pub fn is_quiet(&self) -> bool {
+        self.quiet_hours
+            .is_some_and(|quiet_hours| quiet_hours.contains(Local::now().time()))
+    }
+
+    ///Blanks the screen, checking every now and then if the quiet hours are over
+    pub fn show_quiet(&self, ui: &mut Ui) {
+        egui::CentralPanel::default()
+            .frame(egui::Frame::NONE.fill(Color32::BLACK))
+            .show_inside(ui, |_| {});
+
+        ui.ctx().request_repaint_after(QUIET_HOURS_CHECK);
+    }
+
+    pub fn ui(&mut self, ui: &Ui, rect: Rect, path: Option<&Path>, image_store: &ImageStore) {
+        if let Some(path) = path
+            && let Some(caption) = self.caption(path, image_store)
+            && !caption.is_empty()
+        {
+            self.paint_text(ui, rect.left_bottom(), Align2::LEFT_BOTTOM, &caption);
+        }
+
+        if let Some(clock_format) = &self.clock_format {
+            let now = Local::now();
+            self.paint_text(
+                ui,
+                rect.right_bottom(),
+                Align2::RIGHT_BOTTOM,
+                &now.format(clock_format).to_string(),
+            );
+
+            ui.ctx()
+                .request_repaint_after(Duration::from_secs(60 - now.second() as u64));
+        }
+    }
+
+    fn caption(&mut self, path: &Path, image_store: &ImageStore) -> Option<String> {
+        if self.caption_format.is_empty() {
+            return None;
+        }
+
+        if let Some((caption_path, caption)) = &self.caption
+            && caption_path == path
+        {
+            return Some(caption.clone());
+        }
+
+        //Metadata is only available once the image is in the store
+        let metadata = image_store.get_image_metadata(&path.to_path_buf())?;
+        let caption = Metadata::format_string_with_metadata(&self.caption_format, metadata);
+        self.caption = Some((path.to_path_buf(), caption.clone()));
+
+        Some(caption)
+    }
+
+    fn paint_text(&self, ui: &Ui, corner: egui::Pos2, align: Align2, text: &str) {
+        let painter = ui.painter();
+        let offset = Vec2::new(
+            if align == Align2::LEFT_BOTTOM {
+                TEXT_MARGIN
+            } else {
+                -TEXT_MARGIN
+            },
+            -TEXT_MARGIN,
+        );
+
+        let galley = painter.layout_no_wrap(
+            text.to_string(),
+            FontId::proportional(self.text_size),
+            Color32::WHITE,
+        );
+        let text_rect = align.anchor_size(corner + offset, galley.size());
+
+        painter.rect_filled(
+            text_rect.expand(TEXT_PADDING),
+            4.,
+            Color32::from_black_alpha(140),
+        );
+        painter.galley(text_rect.min, galley, Color32::WHITE);
+    }
+}
+
+///Slideshow images taken from a saved filter query, refreshed periodically
+pub struct Playlist {
+    query: FilterQuery,
+    refresh_every: Duration,
+    last_refresh: Option<Instant>,
+    job: Option<JoinHandle<Option<Vec<PathBuf>>>>,
+    db_repo: DbRepository,
+}
+
+impl Playlist {
+    pub fn new(cfg: &SlideshowConfig, db_repo: &DbRepository) -> Option<Playlist> {
+        let query = cfg.playlist.clone()?;
+
+        Some(Playlist {
+            query,
+            refresh_every: Duration::from_secs(cfg.playlist_refresh_minutes.max(1) * 60),
+            last_refresh: None,
+            job: None,
+            db_repo: db_repo.clone(),
+        })
+    }
+
+    ///Runs the query when a refresh is due, returning the paths once it finishes
+    pub fn poll(&mut self) -> Option<Vec<PathBuf>> {
+        if let Some(job) = self.job.take() {
+            if !job.is_finished() {
+                self.job = Some(job);
+                return None;
+            }
+
+            return match job.join() {
+                Ok(Some(paths)) if !paths.is_empty() => Some(paths),
+                Ok(_) => {
+                    tracing::info!("Slideshow playlist query returned no images");
+                    None
+                }
+                Err(_) => {
+                    tracing::error!("Failure joining slideshow playlist query");
+                    None
+                }
+            };
+        }
+
+        if self
+            .last_refresh
+            .is_none_or(|last_refresh| last_refresh.elapsed() >= self.refresh_every)
+        {
+            let query = self.query.clone();
+            let mut repo = self.db_repo.clone();
+            self.job = Some(thread::spawn(move || query.run(&mut repo)));
+            self.last_refresh = Some(Instant::now());
+        }
+
+        None
+    }
+
+    ///Forgets the last refresh and drops a running query, so the next poll starts a new one
+    pub fn reset(&mut self) {
+        self.last_refresh = None;
+        self.job = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn quiet_hours_can_span_midnight() {
+        let quiet_hours =
+            QuietHours::new(&Some("22:30".to_string()), &Some("07:00".to_string())).unwrap();
+
+        assert!(quiet_hours.contains(time(23, 0)));
+        assert!(quiet_hours.contains(time(3, 0)));
+        assert!(!quiet_hours.contains(time(7, 0)));
+        assert!(!quiet_hours.contains(time(12, 0)));
+    }
+
+    #[test]
+    fn quiet_hours_need_both_ends() {
+        assert_eq!(QuietHours::new(&Some("22:00".to_string()), &None), None);
+        assert_eq!(
+            QuietHours::new(&Some("22h".to_string()), &Some("07:00".to_string())),
+            None
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use crate::icc::profile_desc_to_icc;
+use crate::image::ImagePixels;
+use eframe::egui::{self, Area, Id, RichText, Ui};
+use epaint::{Color32, Pos2, Vec2};
+use lcms2::{CIExyY, GlobalContext, Intent, PixelFormat, Profile, Transform};
+use std::path::{Path, PathBuf};
+
+//D50 is the white point of the ICC profile connection space
+const D50_WHITE_POINT: CIExyY = CIExyY {
+    x: 0.3457,
+    y: 0.3585,
+    Y: 1.0,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PixelSample {
+    ///Coordinates in the decoded image, before any resizing
+    pub x: u32,
+    pub y: u32,
+    ///Value before color management. Read from the loaded preview, which is downscaled when the
+    ///image is larger than the texture, so it may average several decoded pixels.
+    pub source: [u8; 3],
+    ///Value after color management, what is displayed on screen
+    pub output: [u8; 3],
+    pub lab: Option<[f64; 3]>,
+}
+
+impl PixelSample {
+    ///Samples the pixel at the normalized image coordinates
+    pub fn from_pixels(pixels: &ImagePixels, uv: Pos2) -> Option<PixelSample> {
+        if !(0.0..1.0).contains(&uv.x) || !(0.0..1.0).contains(&uv.y) {
+            return None;
+        }
+
+        let (width, height) = pixels.source.dimensions();
+        let tx = ((uv.x * width as f32) as u32).min(width.saturating_sub(1));
+        let ty = ((uv.y * height as f32) as u32).min(height.saturating_sub(1));
+
+        Some(PixelSample {
+            x: ((uv.x * pixels.decoded_size[0] as f32) as u32)
+                .min(pixels.decoded_size[0].saturating_sub(1)),
+            y: ((uv.y * pixels.decoded_size[1] as f32) as u32)
+                .min(pixels.decoded_size[1].saturating_sub(1)),
+            source: pixels.source.get_pixel_checked(tx, ty)?.0,
+            output: pixels.output().get_pixel_checked(tx, ty)?.0,
+            lab: None,
+        })
+    }
+
+    pub fn hex(&self) -> String {
+        rgb_to_hex(&self.output)
+    }
+
+    pub fn hsv(&self) -> [f32; 3] {
+        rgb_to_hsv(&self.output)
+    }
+
+    pub fn lines(&self) -> Vec<(&'static str, String)> {
+        let [h, s, v] = self.hsv();
+        let mut lines = vec![
+            ("XY", format!("{}, {}", self.x, self.y)),
+            (
+                "Preview RGB",
+                format!("{} {} {}", self.source[0], self.source[1], self.source[2]),
+            ),
+            (
+                "Shown RGB",
+                format!("{} {} {}", self.output[0], self.output[1], self.output[2]),
+            ),
+            ("Hex", self.hex()),
+            ("HSV", format!("{h:.0}° {:.0}% {:.0}%", s * 100., v * 100.)),
+        ];
+
+        if let Some([l, a, b]) = self.lab {
+            lines.push(("Lab", format!("{l:.1} {a:.1} {b:.1}")));
+        }
+
+        lines
+    }
+
+    ///Single line representation used when copying the value
+    pub fn to_clipboard_string(&self) -> String {
+        self.lines()
+            .iter()
+            .map(|(name, value)| format!("{name}: {value}"))
+            .collect::<Vec<String>>()
+            .join(" | ")
+    }
+}
+
+#[derive(Default)]
+pub struct PixelInspector {
+    pub enabled: bool,
+    sample: Option<PixelSample>,
+    sampled_at: Option<(PathBuf, u32, u32)>,
+    lab_transform: Option<LabTransform>,
+}
+
+///Transform from the display profile to Lab, built once per profile instead of per sample
+struct LabTransform {
+    output_icc_profile: String,
+    ///None when the profile couldn't be loaded, which is not retried
+    transform: Option<Transform<[u8; 3], [f64; 3]>>,
+}
+
+impl PixelInspector {
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.sample = None;
+        self.sampled_at = None;
+    }
+
+    pub fn sample(&self) -> Option<&PixelSample> {
+        self.sample.as_ref()
+    }
+
+    ///Updates the sample, Lab values are only recomputed when the sampled pixel changes
+    pub fn update(
+        &mut self,
+        path: &Path,
+        pixels: &ImagePixels,
+        uv: Pos2,
+        output_icc_profile: &str,
+    ) {
+        let mut sample = match PixelSample::from_pixels(pixels, uv) {
+            Some(sample) => sample,
+            None => {
+                self.clear();
+                return;
+            }
+        };
+
+        let sampled_at = Some((path.to_path_buf(), sample.x, sample.y));
+        if self.sampled_at == sampled_at {
+            return;
+        }
+
+        if self
+            .lab_transform
+            .as_ref()
+            .is_none_or(|lab| lab.output_icc_profile != output_icc_profile)
+        {
+            self.lab_transform = Some(LabTransform {
+                output_icc_profile: output_icc_profile.to_string(),
+                transform: lab_transform(output_icc_profile),
+            });
+        }
+
+        sample.lab = self
+            .lab_transform
+            .as_ref()
+            .and_then(|lab| lab.transform.as_ref())
+            .map(|transform| rgb_to_lab(&sample.output, transform));
+        self.sample = Some(sample);
+        self.sampled_at = sampled_at;
+    }
+
+    pub fn ui(&self, ui: &mut Ui) {
+        let (sample, pointer) = match (&self.sample, ui.ctx().pointer_hover_pos()) {
+            (Some(sample), Some(pointer)) => (sample, pointer),
+            _ => return,
+        };
+
+        Area::new(Id::new("pixel_inspector"))
+            .fixed_pos(pointer + Vec2::new(16., 16.))
+            .order(egui::Order::Tooltip)
+            .interactable(false)
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let (rect, _) =
+                            ui.allocate_exact_size(Vec2::splat(14.), egui::Sense::hover());
+                        ui.painter().rect_filled(
+                            rect,
+                            2.,
+                            Color32::from_rgb(sample.output[0], sample.output[1], sample.output[2]),
+                        );
+                        ui.label(RichText::new(sample.hex()).monospace().strong());
+                    });
+
+                    egui::Grid::new("pixel_inspector_grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            for (name, value) in sample.lines() {
+                                ui.label(RichText::new(name).strong());
+                                ui.label(RichText::new(value).monospace());
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+    }
+}
+
+pub fn rgb_to_hex(rgb: &[u8; 3]) -> String {
+    format!("#{:02X}{:02X}{:02X}", rgb[0], rgb[1], rgb[2])
+}
+
+///Returns hue in degrees, saturation and value between 0 and 1
+pub fn rgb_to_hsv(rgb: &[u8; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(|c| c as f32 / 255.);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0. {
+        0.
+    } else if max == r {
+        60. * ((g - b) / delta).rem_euclid(6.)
+    } else if max == g {
+        60. * ((b - r) / delta + 2.)
+    } else {
+        60. * ((r - g) / delta + 4.)
+    };
+
+    let saturation = if max == 0. { 0. } else { delta / max };
+
+    [hue, saturation, max]
+}
+
+///Transform from the output profile to CIE Lab (D50)
+pub fn lab_transform(output_profile: &str) -> Option<Transform<[u8; 3], [f64; 3]>> {
+    let icc = profile_desc_to_icc(output_profile)?;
+    let input_profile = Profile::new_icc(icc).ok()?;
+    let lab_profile = Profile::new_lab4_context(GlobalContext::new(), &D50_WHITE_POINT).ok()?;
+
+    match Transform::new(
+        &input_profile,
+        PixelFormat::RGB_8,
+        &lab_profile,
+        PixelFormat::Lab_DBL,
+        Intent::Perceptual,
+    ) {
+        Ok(transform) => Some(transform),
+        Err(e) => {
+            tracing::error!("Failure creating Lab transform for the pixel inspector -> {e}");
+            None
+        }
+    }
+}
+
+pub fn rgb_to_lab(rgb: &[u8; 3], transform: &Transform<[u8; 3], [f64; 3]>) -> [f64; 3] {
+    let mut lab = [[0f64; 3]];
+    transform.transform_pixels(&[*rgb], &mut lab);
+    lab[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    #[test]
+    fn rgb_to_hex_is_uppercase_and_padded() {
+        assert_eq!(rgb_to_hex(&[255, 8, 0]), "#FF0800");
+    }
+
+    #[test]
+    fn rgb_to_hsv_primaries() {
+        assert_eq!(rgb_to_hsv(&[255, 0, 0]), [0., 1., 1.]);
+        assert_eq!(rgb_to_hsv(&[0, 255, 0]), [120., 1., 1.]);
+        assert_eq!(rgb_to_hsv(&[0, 0, 255]), [240., 1., 1.]);
+        assert_eq!(rgb_to_hsv(&[0, 0, 0]), [0., 0., 0.]);
+    }
+
+    #[test]
+    fn sample_maps_uv_to_texture_and_decoded_coordinates() {
+        let mut source = RgbImage::new(4, 2);
+        source.put_pixel(3, 1, image::Rgb([10, 20, 30]));
+        let pixels = ImagePixels {
+            source,
+            output: None,
+            decoded_size: [8, 4],
+        };
+
+        let sample = PixelSample::from_pixels(&pixels, Pos2::new(0.9, 0.9)).unwrap();
+        assert_eq!((sample.x, sample.y), (7, 3));
+        assert_eq!(sample.source, [10, 20, 30]);
+        assert_eq!(sample.output, [10, 20, 30]);
+
+        assert!(PixelSample::from_pixels(&pixels, Pos2::new(1.2, 0.5)).is_none());
+    }
+    #[test]
+    fn lab_transform_maps_white_to_full_lightness() {
+        let transform = lab_transform("sRGB").unwrap();
+        let [l, a, b] = rgb_to_lab(&[255, 255, 255], &transform);
+        assert!((l - 100.).abs() < 0.5);
+        assert!(a.abs() < 0.5 && b.abs() < 0.5);
+    }
+}
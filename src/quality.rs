@@ -0,0 +1,314 @@
+use crate::db::DbRepository;
+use crate::image::{Image, extract_preview_from_raw_file};
+use crate::{JXL_EXTENSION, RAW_EXTENSIONS};
+use image::{DynamicImage, GrayImage};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Sender, channel};
+use std::thread;
+use std::time::Instant;
+
+pub const QUALITY_SHARPNESS: &str = "Quality Sharpness";
+pub const QUALITY_LUMINANCE: &str = "Quality Luminance";
+pub const QUALITY_CLIPPED_HIGHLIGHTS: &str = "Quality Clipped Highlights";
+pub const QUALITY_CLIPPED_SHADOWS: &str = "Quality Clipped Shadows";
+pub const QUALITY_NOISE: &str = "Quality Noise";
+
+///Filter tag and column of the file table holding each metric
+pub const QUALITY_COLUMNS: &[(&str, &str)] = &[
+    (QUALITY_SHARPNESS, "sharpness"),
+    (QUALITY_LUMINANCE, "luminance"),
+    (QUALITY_CLIPPED_HIGHLIGHTS, "clipped_highlights"),
+    (QUALITY_CLIPPED_SHADOWS, "clipped_shadows"),
+    (QUALITY_NOISE, "noise"),
+];
+
+//Metrics are computed on a downscaled copy
+const ANALYSIS_SIZE: u32 = 1024;
+///Images analysed in parallel, decoding is memory hungry
+const ANALYSIS_THREADS: usize = 4;
+///Images analysed between saves to the library
+const BATCH_SIZE: usize = 64;
+
+///Image quality metrics computed at import, used to filter and sort through culling candidates
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct QualityMetrics {
+    ///Variance of the Laplacian of the luminance, higher is sharper
+    pub sharpness: f64,
+    ///Mean luminance, 0 to 255
+    pub luminance: f64,
+    ///Percentage of pixels with a channel at 255
+    pub clipped_highlights: f64,
+    ///Percentage of pixels with every channel at 0
+    pub clipped_shadows: f64,
+    ///Estimated standard deviation of the noise
+    pub noise: f64,
+}
+
+impl QualityMetrics {
+    pub fn from_path(path: &Path) -> Option<QualityMetrics> {
+        let ext = path
+            .extension()
+            .unwrap_or_default()
+            .to_str()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        //The embedded preview is good enough and much faster than developing the raw
+        let preview = if RAW_EXTENSIONS.contains(&ext.as_str()) {
+            extract_preview_from_raw_file(path)
+        } else {
+            None
+        };
+
+        let buffer = match preview {
+            Some(buffer) => buffer,
+            None => match std::fs::read(path) {
+                Ok(buffer) => buffer,
+                Err(e) => {
+                    tracing::error!("{path:?} -> Failure reading image for quality metrics: {e}");
+                    return None;
+                }
+            },
+        };
+
+        let img = if ext == JXL_EXTENSION {
+            Image::decode_jxl(&buffer, path)?
+        } else {
+            Image::decode_generic(&buffer, path)?
+        };
+
+        Some(Self::compute(img))
+    }
+
+    pub fn compute(img: DynamicImage) -> QualityMetrics {
+        let rgb = if img.width().max(img.height()) > ANALYSIS_SIZE {
+            Image::resize(img, Some(ANALYSIS_SIZE)).to_rgb8()
+        } else {
+            img.to_rgb8()
+        };
+        let pixel_count = (rgb.width() * rgb.height()).max(1) as f64;
+
+        let mut highlights = 0u64;
+        let mut shadows = 0u64;
+        for pixel in rgb.pixels() {
+            if pixel.0.contains(&255) {
+                highlights += 1;
+            }
+            if pixel.0 == [0, 0, 0] {
+                shadows += 1;
+            }
+        }
+
+        let luma = DynamicImage::ImageRgb8(rgb).to_luma8();
+        let luminance = luma.pixels().map(|p| p.0[0] as f64).sum::<f64>() / pixel_count;
+
+        QualityMetrics {
+            sharpness: laplacian_variance(&luma),
+            luminance,
+            clipped_highlights: highlights as f64 / pixel_count * 100.,
+            clipped_shadows: shadows as f64 / pixel_count * 100.,
+            noise: noise_sigma(&luma),
+        }
+    }
+
+    ///Values in the same order as QUALITY_COLUMNS
+    pub fn values(&self) -> [f64; 5] {
+        [
+            self.sharpness,
+            self.luminance,
+            self.clipped_highlights,
+            self.clipped_shadows,
+            self.noise,
+        ]
+        .map(|v| (v * 100.).round() / 100.)
+    }
+}
+
+///Computes the metrics missing from the library in the background. Decoding every image is
+///much slower than reading its exif tags, so it's kept out of the metadata import.
+#[derive(Clone)]
+pub struct QualityWorker {
+    tx: Sender<Vec<PathBuf>>,
+}
+
+impl QualityWorker {
+    pub fn new(db_repo: &DbRepository) -> QualityWorker {
+        let (tx, rx) = channel::<Vec<PathBuf>>();
+        let mut db_repo = db_repo.clone();
+        thread::spawn(move || {
+            while let Ok(paths) = rx.recv() {
+                cache_missing_metrics(&mut db_repo, &paths);
+            }
+        });
+
+        QualityWorker { tx }
+    }
+
+    ///Queues the images, those already analysed or not imported yet are skipped
+    pub fn queue(&self, paths: Vec<PathBuf>) {
+        if self.tx.send(paths).is_err() {
+            tracing::error!("Failure sending images to the quality metrics worker");
+        }
+    }
+}
+
+///Computes and saves the metrics of the imported images which don't have them yet
+pub fn cache_missing_metrics(db_repo: &mut DbRepository, paths: &[PathBuf]) {
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    let missing = match db_repo.get_paths_without_quality_metrics(&paths) {
+        Ok(missing) => missing,
+        Err(e) => {
+            tracing::error!("Failure fetching images without quality metrics -> {e}");
+            return;
+        }
+    };
+
+    if missing.is_empty() {
+        return;
+    }
+
+    let timer = Instant::now();
+    tracing::info!("Computing quality metrics for {} images", missing.len());
+
+    for batch in missing.chunks(BATCH_SIZE) {
+        let metrics = thread::scope(|s| {
+            let handles: Vec<_> = batch
+                .chunks(batch.len().div_ceil(ANALYSIS_THREADS))
+                .map(|chunk| {
+                    s.spawn(move || {
+                        chunk
+                            .iter()
+                            .filter_map(|path| {
+                                QualityMetrics::from_path(Path::new(path))
+                                    .map(|metrics| (path.clone(), metrics))
+                            })
+                            .collect::<Vec<(String, QualityMetrics)>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap_or_default())
+                .collect::<Vec<(String, QualityMetrics)>>()
+        });
+
+        if let Err(e) = db_repo.update_quality_metrics(&metrics) {
+            tracing::error!("Failure saving quality metrics into db -> {e}");
+        }
+
+        //Otherwise they'd be decoded again every time they're opened
+        let failed = batch
+            .iter()
+            .filter(|path| !metrics.iter().any(|(analysed, _)| analysed == *path))
+            .cloned()
+            .collect::<Vec<String>>();
+        if !failed.is_empty()
+            && let Err(e) = db_repo.set_quality_failed(&failed)
+        {
+            tracing::error!("Failure marking failed quality metrics into db -> {e}");
+        }
+    }
+
+    tracing::info!(
+        "Computed quality metrics for {} images in {}ms",
+        missing.len(),
+        timer.elapsed().as_millis()
+    );
+}
+
+///Filter tag for a column of the file table, None if the tag is read from the exif metadata
+pub fn quality_column(tag: &str) -> Option<&'static str> {
+    QUALITY_COLUMNS
+        .iter()
+        .find(|(name, _)| *name == tag)
+        .map(|(_, column)| *column)
+}
+
+fn inner_pixels(luma: &GrayImage) -> impl Iterator<Item = (u32, u32)> + '_ {
+    (1..luma.height().saturating_sub(1))
+        .flat_map(move |y| (1..luma.width().saturating_sub(1)).map(move |x| (x, y)))
+}
+
+fn laplacian_variance(luma: &GrayImage) -> f64 {
+    let at = |x: u32, y: u32| luma.get_pixel(x, y).0[0] as f64;
+
+    let (mut count, mut sum, mut sum_sq) = (0f64, 0f64, 0f64);
+    for (x, y) in inner_pixels(luma) {
+        let laplacian = at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4. * at(x, y);
+        count += 1.;
+        sum += laplacian;
+        sum_sq += laplacian * laplacian;
+    }
+
+    if count == 0. {
+        return 0.;
+    }
+
+    let mean = sum / count;
+    sum_sq / count - mean * mean
+}
+
+///Immerkær's fast noise variance estimation, see "Fast Noise Variance Estimation", 1996
+fn noise_sigma(luma: &GrayImage) -> f64 {
+    let at = |x: u32, y: u32| luma.get_pixel(x, y).0[0] as f64;
+
+    let (mut count, mut sum) = (0f64, 0f64);
+    for (x, y) in inner_pixels(luma) {
+        let corners = at(x - 1, y - 1) + at(x + 1, y - 1) + at(x - 1, y + 1) + at(x + 1, y + 1);
+        let edges = at(x, y - 1) + at(x - 1, y) + at(x + 1, y) + at(x, y + 1);
+        sum += (corners - 2. * edges + 4. * at(x, y)).abs();
+        count += 1.;
+    }
+
+    if count == 0. {
+        return 0.;
+    }
+
+    (std::f64::consts::PI / 2.).sqrt() * sum / (6. * count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn flat_image_is_neither_sharp_nor_noisy() {
+        let img = RgbImage::from_pixel(64, 64, Rgb([128, 128, 128]));
+        let metrics = QualityMetrics::compute(DynamicImage::ImageRgb8(img));
+
+        assert_eq!(metrics.sharpness, 0.);
+        assert_eq!(metrics.noise, 0.);
+        assert_eq!(metrics.luminance, 128.);
+        assert_eq!(metrics.clipped_highlights, 0.);
+    }
+
+    #[test]
+    fn edges_are_sharper_than_gradients() {
+        let checker = RgbImage::from_fn(64, 64, |x, y| {
+            if (x / 4 + y / 4) % 2 == 0 {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([0, 0, 0])
+            }
+        });
+        let gradient = RgbImage::from_fn(64, 64, |x, _| Rgb([(x * 4) as u8; 3]));
+
+        let checker = QualityMetrics::compute(DynamicImage::ImageRgb8(checker));
+        let gradient = QualityMetrics::compute(DynamicImage::ImageRgb8(gradient));
+
+        assert!(checker.sharpness > gradient.sharpness);
+        assert_eq!(checker.clipped_highlights, 50.);
+        assert_eq!(checker.clipped_shadows, 50.);
+    }
+
+    #[test]
+    fn maps_filter_tags_to_columns() {
+        assert_eq!(quality_column(QUALITY_NOISE), Some("noise"));
+        assert_eq!(quality_column("ISO"), None);
+    }
+}
@@ -0,0 +1,315 @@
+use crate::config::{GeneralConfig, SidecarNaming, XmpWrite};
+use crate::db::DbRepository;
+use crate::xmp::XmpWriter;
+use eframe::egui::{Align2, FontId, Painter, Rect, Stroke, vec2};
+use epaint::Color32;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+///Filter tags backed by the rating table
+pub const RATING_TAG: &str = "Star Rating";
+pub const LABEL_TAG: &str = "Color Label";
+pub const MAX_RATING: i8 = 5;
+///Same value darktable and Lightroom write to `xmp:Rating`
+pub const REJECTED: i8 = -1;
+const XMP_EXTENSION: &str = "xmp";
+const STAR_COLOR: Color32 = Color32::from_rgb(240, 200, 60);
+const REJECTED_COLOR: Color32 = Color32::from_rgb(220, 60, 50);
+///Badge size relative to the thumbnail height
+const BADGE_SIZE_R: f32 = 0.08;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorLabel {
+    Red,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl ColorLabel {
+    pub const ALL: [ColorLabel; 5] = [
+        ColorLabel::Red,
+        ColorLabel::Yellow,
+        ColorLabel::Green,
+        ColorLabel::Blue,
+        ColorLabel::Purple,
+    ];
+
+    ///Value of `xmp:Label`, shared by darktable and Lightroom
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorLabel::Red => "Red",
+            ColorLabel::Yellow => "Yellow",
+            ColorLabel::Green => "Green",
+            ColorLabel::Blue => "Blue",
+            ColorLabel::Purple => "Purple",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ColorLabel> {
+        Self::ALL
+            .into_iter()
+            .find(|label| label.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    pub fn color(&self) -> Color32 {
+        match self {
+            ColorLabel::Red => Color32::from_rgb(220, 60, 50),
+            ColorLabel::Yellow => Color32::from_rgb(230, 190, 40),
+            ColorLabel::Green => Color32::from_rgb(70, 170, 70),
+            ColorLabel::Blue => Color32::from_rgb(50, 120, 220),
+            ColorLabel::Purple => Color32::from_rgb(150, 80, 200),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct ImageRating {
+    ///Stars from 0 to [`MAX_RATING`] or [`REJECTED`]
+    pub rating: i8,
+    pub label: Option<ColorLabel>,
+}
+
+impl ImageRating {
+    pub fn is_rejected(&self) -> bool {
+        self.rating == REJECTED
+    }
+
+    pub fn is_unrated(&self) -> bool {
+        *self == ImageRating::default()
+    }
+
+    ///Shown in Image View's bottom bar
+    pub fn label(&self) -> String {
+        let rating = if self.is_rejected() {
+            "✖ Rejected".to_string()
+        } else {
+            let stars = self.rating.clamp(0, MAX_RATING) as usize;
+            format!(
+                "{}{}",
+                "★".repeat(stars),
+                "☆".repeat(MAX_RATING as usize - stars)
+            )
+        };
+
+        match self.label {
+            Some(label) => format!("{rating} {}", label.name()),
+            None => rating,
+        }
+    }
+
+    ///Stars in the bottom left corner, the label in the top right and rejected images dimmed
+    pub fn paint_badge(&self, painter: &Painter, rect: Rect) {
+        let size = (rect.height() * BADGE_SIZE_R).clamp(8., 16.);
+
+        if self.is_rejected() {
+            painter.rect_filled(rect, 0, Color32::from_black_alpha(150));
+            painter.text(
+                rect.center(),
+                Align2::CENTER_CENTER,
+                "✖",
+                FontId::proportional(size * 3.),
+                REJECTED_COLOR,
+            );
+        } else if self.rating > 0 {
+            let galley = painter.layout_no_wrap(
+                "★".repeat(self.rating as usize),
+                FontId::proportional(size),
+                STAR_COLOR,
+            );
+            let pos = rect.left_bottom() + vec2(size * 0.5, -size * 0.5 - galley.size().y);
+            painter.rect_filled(
+                Rect::from_min_size(pos, galley.size()).expand(size * 0.2),
+                size * 0.2,
+                Color32::from_black_alpha(160),
+            );
+            painter.galley(pos, galley, STAR_COLOR);
+        }
+
+        if let Some(label) = self.label {
+            painter.circle(
+                rect.right_top() + vec2(-size, size),
+                size * 0.5,
+                label.color(),
+                Stroke::new(1., Color32::from_black_alpha(160)),
+            );
+        }
+    }
+}
+
+///Ratings and color labels of the opened collection. Changes are saved to the database and,
+///depending on `rating_xmp`, written to XMP in the background.
+pub struct Ratings {
+    ratings: HashMap<PathBuf, ImageRating>,
+    db_repo: DbRepository,
+    xmp_write: XmpWrite,
+    xmp_writer: XmpWriter,
+}
+
+impl Ratings {
+    pub fn new(db_repo: &DbRepository, config: &GeneralConfig, xmp_writer: &XmpWriter) -> Ratings {
+        Ratings {
+            ratings: HashMap::new(),
+            db_repo: db_repo.clone(),
+            xmp_write: config.rating_xmp,
+            xmp_writer: xmp_writer.clone(),
+        }
+    }
+
+    ///Replaces the cached ratings with the ones of a new collection
+    pub fn load(&mut self, paths: &[PathBuf]) {
+        let paths: Vec<String> = paths
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+
+        self.ratings = match self.db_repo.get_ratings(&paths) {
+            Ok(ratings) => ratings
+                .into_iter()
+                .map(|(path, rating, label)| {
+                    let label = label.as_deref().and_then(ColorLabel::from_name);
+                    (PathBuf::from(path), ImageRating { rating, label })
+                })
+                .collect(),
+            Err(e) => {
+                tracing::error!("Failure fetching ratings -> {e}");
+                HashMap::new()
+            }
+        };
+    }
+
+    pub fn get(&self, path: &Path) -> ImageRating {
+        self.ratings.get(path).copied().unwrap_or_default()
+    }
+
+    pub fn set_rating(&mut self, path: &Path, rating: i8) {
+        let mut image_rating = self.get(path);
+        image_rating.rating = rating.clamp(REJECTED, MAX_RATING);
+        self.save(path, image_rating);
+    }
+
+    ///Setting the label all the images already have removes it, otherwise they all get it
+    pub fn toggle_label(&mut self, paths: &[PathBuf], label: ColorLabel) {
+        let label =
+            (!paths.iter().all(|path| self.get(path).label == Some(label))).then_some(label);
+        for path in paths {
+            let mut image_rating = self.get(path);
+            image_rating.label = label;
+            self.save(path, image_rating);
+        }
+    }
+
+    pub fn clear_label(&mut self, path: &Path) {
+        let mut image_rating = self.get(path);
+        image_rating.label = None;
+        self.save(path, image_rating);
+    }
+
+    ///Forgets an image which was moved away, its XMP is left as it is
+    pub fn remove(&mut self, path: &Path) {
+        let path_str = path.to_string_lossy();
+        if let Err(e) = self.db_repo.delete_rating(&path_str) {
+            tracing::error!("Failure deleting rating for {path_str} -> {e}");
+        }
+
+        self.ratings.remove(path);
+    }
+
+    fn save(&mut self, path: &Path, image_rating: ImageRating) {
+        let path_str = path.to_string_lossy();
+        let result = if image_rating.is_unrated() {
+            self.db_repo.delete_rating(&path_str)
+        } else {
+            self.db_repo.save_rating(
+                &path_str,
+                image_rating.rating,
+                image_rating.label.map(|l| l.name()),
+            )
+        };
+
+        if let Err(e) = result {
+            tracing::error!("Failure saving rating for {path_str} -> {e}");
+        }
+
+        self.ratings.insert(path.to_path_buf(), image_rating);
+
+        //No label removes the tag
+        let label = image_rating.label.map(|l| l.name()).unwrap_or_default();
+        self.xmp_writer.write(
+            path,
+            self.xmp_write,
+            vec![
+                format!("-XMP-xmp:Rating={}", image_rating.rating),
+                format!("-XMP-xmp:Label={label}"),
+            ],
+        );
+    }
+}
+
+///The sidecar next to the image, whichever naming it uses, or the configured naming when there's
+///none yet
+pub fn sidecar_path(path: &Path, naming: SidecarNaming) -> PathBuf {
+    let [preferred, other] = sidecar_candidates(path, naming);
+
+    if !preferred.exists() && other.exists() {
+        return other;
+    }
+
+    preferred
+}
+
+///The sidecar next to the image, if any. Darktable's naming is preferred as it can't be shared
+///by a raw and a jpeg with the same name.
+pub fn existing_sidecar(path: &Path) -> Option<PathBuf> {
+    sidecar_candidates(path, SidecarNaming::Darktable)
+        .into_iter()
+        .find(|candidate| candidate.exists())
+}
+
+fn sidecar_candidates(path: &Path, naming: SidecarNaming) -> [PathBuf; 2] {
+    let mut darktable = OsString::from(path.as_os_str());
+    darktable.push(format!(".{XMP_EXTENSION}"));
+    let darktable = PathBuf::from(darktable);
+    let lightroom = path.with_extension(XMP_EXTENSION);
+
+    match naming {
+        SidecarNaming::Darktable => [darktable, lightroom],
+        SidecarNaming::Lightroom => [lightroom, darktable],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecars_follow_the_configured_naming() {
+        let path = Path::new("/nonexistent/DSCF0001.RAF");
+
+        assert_eq!(
+            sidecar_path(path, SidecarNaming::Darktable),
+            PathBuf::from("/nonexistent/DSCF0001.RAF.xmp")
+        );
+        assert_eq!(
+            sidecar_path(path, SidecarNaming::Lightroom),
+            PathBuf::from("/nonexistent/DSCF0001.xmp")
+        );
+    }
+
+    #[test]
+    fn ratings_are_labeled_for_the_bottom_bar() {
+        let rating = ImageRating {
+            rating: 3,
+            label: ColorLabel::from_name("green"),
+        };
+        assert_eq!(rating.label(), "★★★☆☆ Green");
+
+        let rejected = ImageRating {
+            rating: REJECTED,
+            label: None,
+        };
+        assert_eq!(rejected.label(), "✖ Rejected");
+    }
+}
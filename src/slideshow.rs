@@ -0,0 +1,261 @@
+use crate::config::{SlideshowConfig, SlideshowTransition};
+use crate::ken_burns::KenBurns;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+const SPEED_STEP: f32 = 1.25;
+const SECONDS_PER_IMAGE_RANGE: std::ops::RangeInclusive<f32> = 1.0..=3600.0;
+//Ken Burns frames are only drawn every 50ms to conserve energy
+const KEN_BURNS_FRAME_INTERVAL: Duration = Duration::from_millis(50);
+
+///View settings replaced while the slideshow runs, restored once it stops
+#[derive(Clone)]
+pub struct RestoreState {
+    pub should_maximize: bool,
+    pub frame_enabled: bool,
+    pub minimap_enabled: bool,
+    ///Order of the images before shuffling
+    pub image_order: Option<Vec<PathBuf>>,
+}
+
+///Outgoing image of a transition and how far along the transition is, from 0 to 1
+pub struct TransitionFrame {
+    pub from_index: usize,
+    ///Zoom the outgoing image was displayed with
+    pub from_zoom_factor: f32,
+    pub progress: f32,
+}
+
+#[derive(Clone)]
+struct Transition {
+    from_index: usize,
+    from_zoom_factor: f32,
+    started: Instant,
+}
+
+#[derive(Clone)]
+pub struct Slideshow {
+    last_adv_instant: Instant,
+    ///Motion of the displayed image, None until it's loaded
+    ken_burns: Option<KenBurns>,
+    paused_at: Option<Instant>,
+    ///Can be changed while the slideshow runs
+    seconds_per_image: f32,
+    transition: Option<Transition>,
+    pub restore: Option<RestoreState>,
+}
+
+impl Slideshow {
+    pub fn new(cfg: &SlideshowConfig) -> Slideshow {
+        let seconds_per_image = (cfg.seconds_per_image as f32).clamp(
+            *SECONDS_PER_IMAGE_RANGE.start(),
+            *SECONDS_PER_IMAGE_RANGE.end(),
+        );
+
+        Slideshow {
+            last_adv_instant: Instant::now(),
+            ken_burns: None,
+            paused_at: None,
+            seconds_per_image,
+            transition: None,
+            restore: None,
+        }
+    }
+
+    pub fn ken_burns(&self) -> Option<&KenBurns> {
+        self.ken_burns.as_ref()
+    }
+
+    pub fn set_ken_burns(&mut self, ken_burns: KenBurns) {
+        self.ken_burns = Some(ken_burns);
+    }
+
+    pub fn ken_burns_interval(&self) -> Duration {
+        KEN_BURNS_FRAME_INTERVAL
+    }
+
+    ///How far along the display of the current image the slideshow is, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        let elapsed = match self.paused_at {
+            Some(paused_at) => paused_at - self.last_adv_instant,
+            None => self.last_adv_instant.elapsed(),
+        };
+
+        (elapsed.as_secs_f32() / self.seconds_per_image).clamp(0., 1.)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    ///Pausing keeps the time already spent on the current image
+    pub fn toggle_pause(&mut self) {
+        match self.paused_at.take() {
+            Some(paused_at) => self.last_adv_instant += paused_at.elapsed(),
+            None => self.paused_at = Some(Instant::now()),
+        }
+    }
+
+    pub fn seconds_per_image(&self) -> f32 {
+        self.seconds_per_image
+    }
+
+    pub fn faster(&mut self) {
+        self.set_seconds_per_image(self.seconds_per_image / SPEED_STEP);
+    }
+
+    pub fn slower(&mut self) {
+        self.set_seconds_per_image(self.seconds_per_image * SPEED_STEP);
+    }
+
+    fn set_seconds_per_image(&mut self, seconds: f32) {
+        self.seconds_per_image = seconds.clamp(
+            *SECONDS_PER_IMAGE_RANGE.start(),
+            *SECONDS_PER_IMAGE_RANGE.end(),
+        );
+    }
+
+    pub fn should_advance(&self) -> bool {
+        !self.is_paused() && self.last_adv_instant.elapsed().as_secs_f32() > self.seconds_per_image
+    }
+
+    pub fn time_until_advance(&self) -> Duration {
+        Duration::from_secs_f32(
+            (self.seconds_per_image - self.last_adv_instant.elapsed().as_secs_f32()).max(0.),
+        )
+    }
+
+    ///Restarts the timers for a newly displayed image
+    pub fn advanced(&mut self) {
+        self.last_adv_instant = Instant::now();
+        self.ken_burns = None;
+    }
+
+    pub fn start_transition(
+        &mut self,
+        from_index: usize,
+        from_zoom_factor: f32,
+        transition: SlideshowTransition,
+    ) {
+        self.transition = match transition {
+            SlideshowTransition::Cut => None,
+            SlideshowTransition::Crossfade | SlideshowTransition::Slide => Some(Transition {
+                from_index,
+                from_zoom_factor,
+                started: Instant::now(),
+            }),
+        };
+    }
+
+    ///Current frame of the running transition, the transition ends once it's complete
+    pub fn transition_frame(&mut self, duration_ms: u64) -> Option<TransitionFrame> {
+        let transition = self.transition.as_ref()?;
+        let progress = transition.started.elapsed().as_millis() as f32 / duration_ms.max(1) as f32;
+
+        if progress >= 1. {
+            self.transition = None;
+            return None;
+        }
+
+        Some(TransitionFrame {
+            from_index: transition.from_index,
+            from_zoom_factor: transition.from_zoom_factor,
+            progress: ease_in_out(progress),
+        })
+    }
+
+    pub fn cancel_transition(&mut self) {
+        self.transition = None;
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    pub fn label(&self) -> String {
+        if self.is_paused() {
+            "Slideshow paused".to_string()
+        } else {
+            format!("Slideshow {:.1}s", self.seconds_per_image)
+        }
+    }
+}
+
+pub fn ease_in_out(t: f32) -> f32 {
+    let t = t.clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+///Moves the item at index first to the front and shuffles the others
+pub fn shuffle_with_first<T>(items: &mut Vec<T>, first: usize) {
+    if first >= items.len() {
+        return;
+    }
+
+    let first = items.remove(first);
+    fastrand::shuffle(items);
+    items.insert(0, first);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shuffling_keeps_the_first_item_and_every_element() {
+        let mut items: Vec<usize> = (0..50).collect();
+        shuffle_with_first(&mut items, 7);
+
+        assert_eq!(items[0], 7);
+        let mut sorted = items.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..50).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn speed_is_clamped() {
+        let mut slideshow = Slideshow::new(&SlideshowConfig::default());
+        for _ in 0..100 {
+            slideshow.faster();
+        }
+        assert_eq!(
+            slideshow.seconds_per_image(),
+            *SECONDS_PER_IMAGE_RANGE.start()
+        );
+    }
+
+    #[test]
+    fn paused_slideshow_does_not_advance() {
+        let mut slideshow = Slideshow::new(&SlideshowConfig {
+            seconds_per_image: 1,
+            ..Default::default()
+        });
+        slideshow.last_adv_instant -= Duration::from_secs(2);
+        assert!(slideshow.should_advance());
+
+        slideshow.toggle_pause();
+        assert!(!slideshow.should_advance());
+    }
+
+    #[test]
+    fn progress_stops_while_paused() {
+        let mut slideshow = Slideshow::new(&SlideshowConfig {
+            seconds_per_image: 10,
+            ..Default::default()
+        });
+        slideshow.last_adv_instant -= Duration::from_secs(5);
+        slideshow.toggle_pause();
+        let progress = slideshow.progress();
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(slideshow.progress(), progress);
+        assert!((progress - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn easing_starts_and_ends_at_rest() {
+        assert_eq!(ease_in_out(0.), 0.);
+        assert_eq!(ease_in_out(1.), 1.);
+        assert_eq!(ease_in_out(0.5), 0.5);
+    }
+}
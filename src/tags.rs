@@ -0,0 +1,399 @@
+use crate::config::{GeneralConfig, XmpWrite};
+use crate::db::DbRepository;
+use crate::dropdown::DropDownBox;
+use crate::xmp::XmpWriter;
+use eframe::egui::{self, Align2, Context, Id, Popup, Ui, collapsing_header::CollapsingState};
+use itertools::Itertools;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+///Filter tag listing the tags of each image
+pub const KEYWORDS_TAG: &str = "Keywords";
+pub const TAG_SEPARATOR: char = '/';
+///Separator used by `lr:hierarchicalSubject`
+const XMP_SEPARATOR: char = '|';
+const EDITOR_INPUT_ID: &str = "tag_editor_input";
+
+///Trims every level of a tag like `People/Alice`, None when nothing is left
+pub fn normalize(tag: &str) -> Option<String> {
+    let tag = tag
+        .split(TAG_SEPARATOR)
+        .map(str::trim)
+        .filter(|level| !level.is_empty())
+        .join(&TAG_SEPARATOR.to_string());
+
+    (!tag.is_empty()).then_some(tag)
+}
+
+///The tag and every tag above it, `People/Alice` has `People` and `People/Alice`
+pub fn ancestors(tag: &str) -> Vec<String> {
+    tag.match_indices(TAG_SEPARATOR)
+        .map(|(i, _)| tag[..i].to_string())
+        .chain([tag.to_string()])
+        .collect()
+}
+
+pub fn from_xmp_hierarchy(value: &str) -> Option<String> {
+    normalize(&value.replace(XMP_SEPARATOR, &TAG_SEPARATOR.to_string()))
+}
+
+///Every level goes to `dc:subject`, as Lightroom does, and the full tags to
+///`lr:hierarchicalSubject`. Assigning a list tag replaces all its values.
+fn xmp_assignments(tags: &[String]) -> Vec<String> {
+    if tags.is_empty() {
+        return vec![
+            "-XMP-dc:Subject=".to_string(),
+            "-XMP-lr:HierarchicalSubject=".to_string(),
+        ];
+    }
+
+    let subjects = tags
+        .iter()
+        .flat_map(|tag| tag.split(TAG_SEPARATOR))
+        .unique()
+        .map(|keyword| format!("-XMP-dc:Subject={keyword}"));
+    let hierarchical = tags.iter().map(|tag| {
+        format!(
+            "-XMP-lr:HierarchicalSubject={}",
+            tag.replace(TAG_SEPARATOR, &XMP_SEPARATOR.to_string())
+        )
+    });
+
+    subjects.chain(hierarchical).collect()
+}
+
+#[derive(Default)]
+struct TagNode {
+    children: BTreeMap<String, TagNode>,
+}
+
+impl TagNode {
+    fn from_tags(tags: &[String]) -> TagNode {
+        let mut root = TagNode::default();
+        for tag in tags {
+            let mut node = &mut root;
+            for level in tag.split(TAG_SEPARATOR) {
+                node = node.children.entry(level.to_string()).or_default();
+            }
+        }
+
+        root
+    }
+}
+
+struct TagEditor {
+    paths: Vec<PathBuf>,
+    input: String,
+    focus_requested: bool,
+}
+
+///Hierarchical tags of the opened collection, saved to the database and, depending on
+///`tag_xmp`, written to XMP in the background
+pub struct Tags {
+    tags: HashMap<PathBuf, Vec<String>>,
+    all_tags: Vec<String>,
+    tree: TagNode,
+    db_repo: DbRepository,
+    xmp_write: XmpWrite,
+    xmp_writer: XmpWriter,
+    editor: Option<TagEditor>,
+}
+
+impl Tags {
+    pub fn new(db_repo: &DbRepository, config: &GeneralConfig, xmp_writer: &XmpWriter) -> Tags {
+        let mut tags = Tags {
+            tags: HashMap::new(),
+            all_tags: vec![],
+            tree: TagNode::default(),
+            db_repo: db_repo.clone(),
+            xmp_write: config.tag_xmp,
+            xmp_writer: xmp_writer.clone(),
+            editor: None,
+        };
+
+        tags.load_all_tags();
+        tags
+    }
+
+    ///Replaces the cached tags with the ones of a new collection
+    pub fn load(&mut self, paths: &[PathBuf]) {
+        let paths: Vec<String> = paths
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+
+        self.tags = match self.db_repo.get_image_tags(&paths) {
+            Ok(tags) => tags
+                .into_iter()
+                .map(|(path, tag)| (PathBuf::from(path), tag))
+                .into_group_map(),
+            Err(e) => {
+                tracing::error!("Failure fetching image tags -> {e}");
+                HashMap::new()
+            }
+        };
+
+        //Tags may have been imported from XMP since
+        self.load_all_tags();
+    }
+
+    fn load_all_tags(&mut self) {
+        match self.db_repo.get_all_tags() {
+            Ok(all_tags) => {
+                self.tree = TagNode::from_tags(&all_tags);
+                self.all_tags = all_tags;
+            }
+            Err(e) => tracing::error!("Failure fetching tags -> {e}"),
+        }
+    }
+
+    pub fn get(&self, path: &Path) -> &[String] {
+        self.tags.get(path).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn add(&mut self, paths: &[PathBuf], tag: &str) {
+        let Some(tag) = normalize(tag) else {
+            return;
+        };
+
+        let path_strs: Vec<String> = paths
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        if let Err(e) = self.db_repo.add_tag(&path_strs, &tag) {
+            tracing::error!("Failure saving tag {tag} -> {e}");
+            return;
+        }
+
+        for path in paths {
+            let tags = self.tags.entry(path.clone()).or_default();
+            if !tags.contains(&tag) {
+                tags.push(tag.clone());
+                tags.sort();
+                self.write_xmp(path);
+            }
+        }
+
+        if !self.all_tags.contains(&tag) {
+            self.load_all_tags();
+        }
+    }
+
+    pub fn remove(&mut self, paths: &[PathBuf], tag: &str) {
+        let path_strs: Vec<String> = paths
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        if let Err(e) = self.db_repo.remove_tag(&path_strs, tag) {
+            tracing::error!("Failure removing tag {tag} -> {e}");
+            return;
+        }
+
+        for path in paths {
+            if let Some(tags) = self.tags.get_mut(path)
+                && let Some(pos) = tags.iter().position(|t| t == tag)
+            {
+                tags.remove(pos);
+                self.write_xmp(path);
+            }
+        }
+    }
+
+    fn write_xmp(&self, path: &Path) {
+        self.xmp_writer
+            .write(path, self.xmp_write, xmp_assignments(self.get(path)));
+    }
+
+    ///Opens the tag popup for the images, several of them are tagged at once
+    pub fn open_editor(&mut self, paths: Vec<PathBuf>) {
+        if paths.is_empty() {
+            return;
+        }
+
+        self.editor = Some(TagEditor {
+            paths,
+            input: String::new(),
+            focus_requested: false,
+        });
+    }
+
+    pub fn editor_ui(&mut self, ctx: &Context) {
+        let Some(mut editor) = self.editor.take() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut to_add: Option<String> = None;
+        let mut to_remove: Option<String> = None;
+        let tag_counts = editor
+            .paths
+            .iter()
+            .flat_map(|path| self.get(path))
+            .cloned()
+            .counts();
+
+        egui::Window::new("Tags")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_TOP, [0., 40.])
+            .show(ctx, |ui| {
+                if editor.paths.len() > 1 {
+                    ui.label(format!("{} images", editor.paths.len()));
+                }
+
+                for (tag, count) in tag_counts.iter().sorted() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("✖").clicked() {
+                            to_remove = Some(tag.clone());
+                        }
+
+                        if *count < editor.paths.len() {
+                            ui.label(format!("{tag} ({count}/{})", editor.paths.len()));
+                        } else {
+                            ui.label(tag);
+                        }
+                    });
+                }
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    let response = ui.add(
+                        DropDownBox::from_iter(
+                            &self.all_tags,
+                            EDITOR_INPUT_ID,
+                            &mut editor.input,
+                            |ui, text| ui.selectable_label(false, text),
+                        )
+                        .hint_text("People/Alice")
+                        .max_height(300.)
+                        .desired_width(250.)
+                        .filter_by_input(true),
+                    );
+
+                    if !editor.focus_requested {
+                        response.request_focus();
+                        editor.focus_requested = true;
+                    }
+
+                    //Suggestions are shown while typing, not only when clicking the field
+                    if response.changed() && !editor.input.is_empty() {
+                        Popup::open_id(ui.ctx(), Id::new(EDITOR_INPUT_ID));
+                    }
+
+                    let submitted =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if (ui.button("Add").clicked() || submitted) && !editor.input.is_empty() {
+                        to_add = Some(std::mem::take(&mut editor.input));
+                        Popup::close_id(ui.ctx(), Id::new(EDITOR_INPUT_ID));
+                        response.request_focus();
+                    }
+                });
+            });
+
+        if let Some(tag) = to_add {
+            self.add(&editor.paths, &tag);
+        }
+
+        if let Some(tag) = to_remove {
+            self.remove(&editor.paths, &tag);
+        }
+
+        if open && !ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.editor = Some(editor);
+        }
+    }
+
+    ///Tag tree for the side panel, returns the images of the clicked tag and its descendants
+    pub fn browser_ui(&mut self, ui: &mut Ui) -> Option<Vec<PathBuf>> {
+        ui.label(egui::RichText::new("Tags").heading());
+        ui.add_space(10.);
+
+        if self.all_tags.is_empty() {
+            ui.label("No tags yet");
+            return None;
+        }
+
+        let mut clicked: Option<String> = None;
+        for (name, node) in &self.tree.children {
+            tag_node_ui(ui, name, name, node, &mut clicked);
+        }
+
+        let tag = clicked?;
+        match self.db_repo.get_paths_with_tag(&tag) {
+            Ok(paths) if !paths.is_empty() => Some(paths),
+            Ok(_) => {
+                tracing::info!("No images tagged with {tag}");
+                None
+            }
+            Err(e) => {
+                tracing::error!("Failure fetching images tagged with {tag} -> {e}");
+                None
+            }
+        }
+    }
+}
+
+fn tag_node_ui(ui: &mut Ui, name: &str, tag: &str, node: &TagNode, clicked: &mut Option<String>) {
+    if node.children.is_empty() {
+        if ui.selectable_label(false, name).clicked() {
+            *clicked = Some(tag.to_string());
+        }
+        return;
+    }
+
+    CollapsingState::load_with_default_open(ui.ctx(), Id::new(("tag_browser", tag)), false)
+        .show_header(ui, |ui| {
+            if ui.selectable_label(false, name).clicked() {
+                *clicked = Some(tag.to_string());
+            }
+        })
+        .body(|ui| {
+            for (child_name, child) in &node.children {
+                let child_tag = format!("{tag}{TAG_SEPARATOR}{child_name}");
+                tag_node_ui(ui, child_name, &child_tag, child, clicked);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_normalized_with_their_ancestors() {
+        assert_eq!(
+            normalize(" People / Alice/ "),
+            Some("People/Alice".to_string())
+        );
+        assert_eq!(normalize(" / "), None);
+        assert_eq!(
+            from_xmp_hierarchy("Places|Portugal|Lisbon"),
+            Some("Places/Portugal/Lisbon".to_string())
+        );
+        assert_eq!(
+            ancestors("Places/Portugal/Lisbon"),
+            vec!["Places", "Places/Portugal", "Places/Portugal/Lisbon"]
+        );
+    }
+
+    #[test]
+    fn xmp_lists_are_replaced_with_every_level() {
+        let tags = vec!["People/Alice".to_string(), "People/Bob".to_string()];
+        assert_eq!(
+            xmp_assignments(&tags),
+            vec![
+                "-XMP-dc:Subject=People",
+                "-XMP-dc:Subject=Alice",
+                "-XMP-dc:Subject=Bob",
+                "-XMP-lr:HierarchicalSubject=People|Alice",
+                "-XMP-lr:HierarchicalSubject=People|Bob",
+            ]
+        );
+        assert_eq!(
+            xmp_assignments(&[]),
+            vec!["-XMP-dc:Subject=", "-XMP-lr:HierarchicalSubject="]
+        );
+    }
+}
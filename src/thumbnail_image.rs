@@ -1,27 +1,36 @@
 use crate::image_store::ImageStore;
 use eframe::egui::load::SizedTexture;
-use eframe::egui::{self, Color32, Response, UiBuilder, Vec2};
+use eframe::egui::{self, Color32, Rect, Response, UiBuilder, Vec2};
 use eframe::epaint::vec2;
 use std::path::PathBuf;
 
 pub struct ThumbnailImage {
     pub path: PathBuf,
     pub name: String,
+    ///Index in the opened images, Grid View sections may show it elsewhere
+    pub position: usize,
     pub registered: bool,
+    pub selected: bool,
+    ///Width over height from the library, known before the image is decoded
+    pub aspect_ratio: Option<f32>,
 }
 
 impl ThumbnailImage {
     pub fn from_paths(paths: &[PathBuf]) -> Vec<Self> {
         paths
             .iter()
-            .map(|p| Self {
+            .enumerate()
+            .map(|(position, p)| Self {
                 path: p.clone(),
                 name: p
                     .file_name()
                     .unwrap_or_default()
                     .to_string_lossy()
                     .to_string(),
+                position,
                 registered: false,
+                selected: false,
+                aspect_ratio: None,
             })
             .collect()
     }
@@ -33,14 +42,14 @@ impl ThumbnailImage {
         image_store: &mut ImageStore,
     ) -> Option<Response> {
         if !image_store.is_image_loaded(&self.path) {
-            Self::display_empty_image_frame(ui, size[1]);
+            Self::display_empty_image_frame(ui, Vec2::splat(size[1]));
             return None;
         }
 
         let image_size = match image_store.get_image_size(&self.path) {
             Some(size) => size,
             None => {
-                Self::display_empty_image_frame(ui, size[1]);
+                Self::display_empty_image_frame(ui, Vec2::splat(size[1]));
                 return None;
             }
         };
@@ -48,7 +57,7 @@ impl ThumbnailImage {
         let texture_id = match image_store.get_texture_id(&self.path) {
             Some(texture_id) => texture_id,
             None => {
-                Self::display_empty_image_frame(ui, size[1]);
+                Self::display_empty_image_frame(ui, Vec2::splat(size[1]));
                 return None;
             }
         };
@@ -93,16 +102,62 @@ impl ThumbnailImage {
         response
     }
 
-    pub fn display_empty_image_frame(ui: &mut egui::Ui, size: f32) {
-        let rect_size = Vec2::splat(size);
-        let rect = ui.allocate_space(rect_size);
+    ///Fills the whole cell, cropping what doesn't fit its aspect ratio
+    pub fn cover_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        size: Vec2,
+        image_store: &mut ImageStore,
+    ) -> Option<Response> {
+        let (image_size, texture_id) = match (
+            image_store.get_image_size(&self.path),
+            image_store.get_texture_id(&self.path),
+        ) {
+            (Some(image_size), Some(texture_id)) => (image_size, texture_id),
+            _ => {
+                Self::display_empty_image_frame(ui, size);
+                return None;
+            }
+        };
+
+        let image_aspect_ratio = image_size.x / image_size.y;
+        let cell_aspect_ratio = size.x / size.y;
+        let uv = if image_aspect_ratio > cell_aspect_ratio {
+            let visible = cell_aspect_ratio / image_aspect_ratio;
+            Rect::from_x_y_ranges((1. - visible) / 2.0..=(1. + visible) / 2.0, 0.0..=1.0)
+        } else {
+            let visible = image_aspect_ratio / cell_aspect_ratio;
+            Rect::from_x_y_ranges(0.0..=1.0, (1. - visible) / 2.0..=(1. + visible) / 2.0)
+        };
+
+        let response = ui
+            .add(
+                egui::Image::new(SizedTexture::new(texture_id, image_size))
+                    .uv(uv)
+                    .fit_to_exact_size(size)
+                    .sense(egui::Sense::CLICK),
+            )
+            .on_hover_text_at_pointer(&self.name);
+
+        ui.painter().rect_stroke(
+            response.rect,
+            0.,
+            egui::Stroke::new(1.0, Color32::from_rgb(48, 48, 48)),
+            egui::StrokeKind::Inside,
+        );
+
+        Some(response)
+    }
+
+    pub fn display_empty_image_frame(ui: &mut egui::Ui, size: Vec2) {
+        let rect = ui.allocate_space(size);
 
         ui.painter()
             .rect_filled(rect.1, 0, egui::Color32::from_rgb(119, 119, 119));
 
         ui.scope_builder(UiBuilder::new().max_rect(rect.1), |ui| {
             ui.centered_and_justified(|ui| {
-                let spinner_size = size / 3.;
+                let spinner_size = size.min_elem() / 3.;
                 ui.add(egui::Spinner::new().size(spinner_size));
             });
         });
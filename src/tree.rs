@@ -185,7 +185,7 @@ impl Tree {
             })
             .collect::<Vec<TreeEntry>>();
 
-        entries.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        entries.sort_by_key(|a| a.name.to_lowercase());
 
         entries
     }
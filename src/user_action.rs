@@ -1,9 +1,15 @@
-use std::{path::Path, process::Command};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
 
-use eframe::egui::Response;
+use eframe::egui::{Response, Ui};
 
 use crate::{callback::Callback, config::ContextMenuEntry};
 
+///Replaced with every path, running the command once for all of them
+pub const ALL_PATHS_PLACEHOLDER: &str = "{@}";
+
 fn format_exec_string(exec: &str, path: &Path) -> Option<String> {
     let mut exec = exec.to_string();
 
@@ -34,6 +40,43 @@ fn format_exec_string(exec: &str, path: &Path) -> Option<String> {
     Some(exec)
 }
 
+///`{@}` becomes the paths, the other placeholders refer to the first path. On its own `{@}` is
+///one argument per path, inside a longer argument like a `bash -c` script the paths are quoted
+///for the shell reading it
+fn format_batch_exec_args(exec: &str, paths: &[PathBuf]) -> Option<Vec<String>> {
+    let first = paths.first()?;
+    let mut args = vec![];
+
+    for arg in get_command_args(exec) {
+        if arg == ALL_PATHS_PLACEHOLDER {
+            for path in paths {
+                args.push(path.to_str()?.to_string());
+            }
+            continue;
+        }
+
+        //Formatted before adding the paths so placeholders in their names are left alone
+        let arg = format_exec_string(&arg, first)?;
+        if arg.contains(ALL_PATHS_PLACEHOLDER) {
+            let quoted = paths
+                .iter()
+                .map(|path| Some(shell_quote(path.to_str()?)))
+                .collect::<Option<Vec<String>>>()?
+                .join(" ");
+            args.push(arg.replace(ALL_PATHS_PLACEHOLDER, &quoted));
+        } else {
+            args.push(arg);
+        }
+    }
+
+    Some(args)
+}
+
+///Single quoted for a shell, quotes inside it are closed, escaped and reopened
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
 /// Executes command, returns false if command wasn't executed
 /// or errored out
 pub fn execute(exec: &str, path: &Path) -> bool {
@@ -41,14 +84,34 @@ pub fn execute(exec: &str, path: &Path) -> bool {
         return true;
     }
 
-    let exec = match format_exec_string(exec, path) {
-        Some(exec) => exec,
-        None => return false,
-    };
+    match format_exec_string(exec, path) {
+        Some(exec) => spawn(&exec),
+        None => false,
+    }
+}
 
+/// Executes command once with all paths when it has the `{@}` placeholder, otherwise once per
+/// path. Returns false if any of them wasn't executed or errored out
+pub fn execute_for_paths(exec: &str, paths: &[PathBuf]) -> bool {
+    if !exec.contains(ALL_PATHS_PLACEHOLDER) {
+        //Every path gets its run even when one of them fails
+        let failed = paths.iter().filter(|path| !execute(exec, path)).count();
+        return failed == 0;
+    }
+
+    match format_batch_exec_args(exec, paths) {
+        Some(args) => spawn_args(&args),
+        None => false,
+    }
+}
+
+fn spawn(exec: &str) -> bool {
     tracing::info!("exec -> {exec}");
-    let exec_split = get_command_args(&exec);
-    let mut exec_split = exec_split.iter();
+    spawn_args(&get_command_args(exec))
+}
+
+fn spawn_args(args: &[String]) -> bool {
+    let mut exec_split = args.iter();
 
     let cmd = match exec_split.next() {
         Some(cmd) => cmd,
@@ -113,20 +176,33 @@ pub fn get_command_args(cmd: &str) -> Vec<String> {
 pub fn show_context_menu(
     entries: &Vec<ContextMenuEntry>,
     response: &Response,
-    path: &Path,
+    paths: &[PathBuf],
 ) -> Option<Callback> {
     if entries.is_empty() {
         return None;
     }
 
+    show_context_menu_with(entries, response, paths, |_| {})
+}
+
+///Context menu with the configured entries preceded by the built in ones added by add_builtin.
+///Entries run against all the paths, see `execute_for_paths`
+pub fn show_context_menu_with(
+    entries: &Vec<ContextMenuEntry>,
+    response: &Response,
+    paths: &[PathBuf],
+    add_builtin: impl FnOnce(&mut Ui),
+) -> Option<Callback> {
     let mut result: Option<Callback> = None;
     response.context_menu(|ui| {
         ui.set_max_width(300.);
+        add_builtin(ui);
+
         for entry in entries {
             let button_resp = ui.button(&entry.description);
 
             if button_resp.clicked() {
-                if execute(&entry.exec, path) {
+                if execute_for_paths(&entry.exec, paths) {
                     result = entry.callback.clone();
                 }
                 ui.close();
@@ -209,4 +285,31 @@ mod tests {
             "Should replace {{.//}} with grandparent directory"
         );
     }
+
+    #[test]
+    fn format_batch_exec_args_placeholders() {
+        let paths = vec![
+            PathBuf::from("/tmp/foo/bar.jpg"),
+            PathBuf::from("/tmp/foo/my baz's.jpg"),
+        ];
+
+        assert_eq!(
+            format_batch_exec_args("zip {//}/photos.zip {@}", &paths).unwrap(),
+            vec![
+                "zip",
+                "/tmp/foo/photos.zip",
+                "/tmp/foo/bar.jpg",
+                "/tmp/foo/my baz's.jpg"
+            ]
+        );
+        assert_eq!(
+            format_batch_exec_args("bash -c 'cp {@} {//}/selected'", &paths).unwrap(),
+            vec![
+                "bash",
+                "-c",
+                "cp '/tmp/foo/bar.jpg' '/tmp/foo/my baz'\\''s.jpg' /tmp/foo/selected"
+            ]
+        );
+        assert_eq!(format_batch_exec_args("cmd {@}", &[]), None);
+    }
 }
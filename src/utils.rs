@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use eframe::egui::{self, Id, Response};
 
@@ -82,3 +82,45 @@ pub fn capitalize_first_char(str: &str) -> String {
         Some(f) => f.to_uppercase().collect::<String>() + chars.as_str(),
     }
 }
+
+///Path in dir with the file name of path, numbered when it's already taken
+pub fn unique_target(path: &Path, dir: &Path) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default();
+    let target = dir.join(file_name);
+    if !target.exists() {
+        return target;
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|n| dir.join(format!("{stem}_{n}{extension}")))
+        .find(|target| !target.exists())
+        .unwrap_or(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn taken_paths_are_numbered_instead_of_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("IMG_0001.jpg"), []).unwrap();
+        fs::write(dir.path().join("IMG_0001_1.jpg"), []).unwrap();
+
+        assert_eq!(
+            unique_target(Path::new("/photos/IMG_0001.jpg"), dir.path()),
+            dir.path().join("IMG_0001_2.jpg")
+        );
+        assert_eq!(
+            unique_target(Path::new("/photos/IMG_0002.jpg"), dir.path()),
+            dir.path().join("IMG_0002.jpg")
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use crate::metadata::Orientation;
+use epaint::{Pos2, Rect};
+
+const ROTATE_CW: ViewTransform = ViewTransform {
+    quarter_turns: 1,
+    mirrored: false,
+};
+const MIRROR: ViewTransform = ViewTransform {
+    quarter_turns: 0,
+    mirrored: true,
+};
+
+///Rotation and mirroring applied when displaying an image, on top of its EXIF orientation.
+///The image is first mirrored horizontally and then rotated clockwise.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ViewTransform {
+    ///Clockwise quarter turns, between 0 and 3
+    pub quarter_turns: u8,
+    pub mirrored: bool,
+}
+
+impl ViewTransform {
+    pub fn new(quarter_turns: u8, mirrored: bool) -> ViewTransform {
+        ViewTransform {
+            quarter_turns: quarter_turns % 4,
+            mirrored,
+        }
+    }
+
+    pub fn from_orientation(orientation: &Orientation) -> ViewTransform {
+        //Same operations as Image::orient
+        match orientation {
+            Orientation::Normal => ViewTransform::new(0, false),
+            Orientation::MirrorHorizontal => ViewTransform::new(0, true),
+            Orientation::Rotate180 => ViewTransform::new(2, false),
+            Orientation::MirrorVertical => ViewTransform::new(2, true),
+            Orientation::MirrorHorizontalRotate270 => ViewTransform::new(3, true),
+            Orientation::Rotate90CW => ViewTransform::new(1, false),
+            Orientation::MirrorHorizontalRotate90CW => ViewTransform::new(1, true),
+            Orientation::Rotate270CW => ViewTransform::new(3, false),
+        }
+    }
+
+    ///Numeric value of the EXIF orientation tag
+    pub fn exif_orientation(&self) -> u8 {
+        match (self.quarter_turns, self.mirrored) {
+            (0, false) => 1,
+            (0, true) => 2,
+            (2, false) => 3,
+            (2, true) => 4,
+            (3, true) => 5,
+            (1, false) => 6,
+            (1, true) => 7,
+            _ => 8,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == ViewTransform::default()
+    }
+
+    pub fn swaps_dimensions(&self) -> bool {
+        self.quarter_turns % 2 == 1
+    }
+
+    ///Transform resulting from applying self and then next
+    pub fn followed_by(&self, next: &ViewTransform) -> ViewTransform {
+        //Mirroring after a rotation is the same as mirroring before the opposite rotation
+        let quarter_turns = if next.mirrored {
+            next.quarter_turns + 4 - self.quarter_turns
+        } else {
+            next.quarter_turns + self.quarter_turns
+        };
+
+        ViewTransform::new(quarter_turns, self.mirrored != next.mirrored)
+    }
+
+    pub fn rotate_cw(&mut self) {
+        *self = self.followed_by(&ROTATE_CW);
+    }
+
+    pub fn rotate_ccw(&mut self) {
+        *self = self
+            .followed_by(&ROTATE_CW)
+            .followed_by(&ROTATE_CW)
+            .followed_by(&ROTATE_CW);
+    }
+
+    pub fn rotate_180(&mut self) {
+        *self = self.followed_by(&ROTATE_CW).followed_by(&ROTATE_CW);
+    }
+
+    pub fn flip_horizontal(&mut self) {
+        *self = self.followed_by(&MIRROR);
+    }
+
+    pub fn flip_vertical(&mut self) {
+        *self = self
+            .followed_by(&MIRROR)
+            .followed_by(&ROTATE_CW)
+            .followed_by(&ROTATE_CW);
+    }
+
+    pub fn apply_to_size(&self, size: epaint::Vec2) -> epaint::Vec2 {
+        if self.swaps_dimensions() {
+            epaint::Vec2::new(size.y, size.x)
+        } else {
+            size
+        }
+    }
+
+    ///Maps normalized coordinates of the displayed image to the untransformed texture
+    pub fn to_texture_uv(&self, pos: Pos2) -> Pos2 {
+        let mut pos = pos;
+        for _ in 0..self.quarter_turns {
+            pos = Pos2::new(pos.y, 1. - pos.x);
+        }
+
+        if self.mirrored {
+            pos.x = 1. - pos.x;
+        }
+
+        pos
+    }
+
+    ///Maps normalized coordinates of the untransformed texture to the displayed image
+    pub fn from_texture_uv(&self, uv: Pos2) -> Pos2 {
+        let mut pos = uv;
+        if self.mirrored {
+            pos.x = 1. - pos.x;
+        }
+
+        for _ in 0..self.quarter_turns {
+            pos = Pos2::new(1. - pos.y, pos.x);
+        }
+
+        pos
+    }
+
+    ///Texture coordinates for the top left, top right, bottom left and bottom right corners of
+    ///the visible part of the displayed image
+    pub fn uv_corners(&self, visible_rect_normalized: &Rect) -> [Pos2; 4] {
+        [
+            visible_rect_normalized.left_top(),
+            visible_rect_normalized.right_top(),
+            visible_rect_normalized.left_bottom(),
+            visible_rect_normalized.right_bottom(),
+        ]
+        .map(|corner| self.to_texture_uv(corner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn four_rotations_or_two_flips_are_identity() {
+        let mut transform = ViewTransform::default();
+        for _ in 0..4 {
+            transform.rotate_cw();
+        }
+        assert!(transform.is_identity());
+
+        transform.rotate_ccw();
+        transform.rotate_cw();
+        assert!(transform.is_identity());
+
+        transform.flip_vertical();
+        transform.flip_vertical();
+        assert!(transform.is_identity());
+    }
+
+    #[test]
+    fn flipping_both_ways_is_a_half_turn() {
+        let mut transform = ViewTransform::default();
+        transform.flip_horizontal();
+        transform.flip_vertical();
+        assert_eq!(transform, ViewTransform::new(2, false));
+    }
+
+    #[test]
+    fn composes_with_exif_orientation() {
+        let exif = ViewTransform::from_orientation(&Orientation::Rotate90CW);
+        let mut view = ViewTransform::default();
+        view.rotate_ccw();
+        assert_eq!(exif.followed_by(&view).exif_orientation(), 1);
+
+        let exif = ViewTransform::from_orientation(&Orientation::MirrorHorizontal);
+        let mut view = ViewTransform::default();
+        view.rotate_cw();
+        assert_eq!(
+            exif.followed_by(&view),
+            ViewTransform::from_orientation(&Orientation::MirrorHorizontalRotate90CW)
+        );
+    }
+
+    #[test]
+    fn maps_displayed_corners_to_texture() {
+        let mut transform = ViewTransform::default();
+        transform.rotate_cw();
+        //The top left of an image rotated clockwise is the bottom left of the texture
+        assert_eq!(
+            transform.to_texture_uv(Pos2::new(0., 0.)),
+            Pos2::new(0., 1.)
+        );
+
+        transform = ViewTransform::default();
+        transform.flip_horizontal();
+        assert_eq!(
+            transform.to_texture_uv(Pos2::new(0.25, 0.5)),
+            Pos2::new(0.75, 0.5)
+        );
+    }
+
+    #[test]
+    fn texture_uv_round_trips() {
+        let uv = Pos2::new(0.25, 0.75);
+        for quarter_turns in 0..4 {
+            for mirrored in [false, true] {
+                let transform = ViewTransform::new(quarter_turns, mirrored);
+                assert_eq!(transform.to_texture_uv(transform.from_texture_uv(uv)), uv);
+            }
+        }
+    }
+}
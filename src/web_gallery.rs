@@ -0,0 +1,305 @@
+use crate::config::{ExportConfig, ExportFormat, ExportMetadata, ExportResize, WebGalleryConfig};
+use crate::db::DbRepository;
+use crate::export;
+use crate::image::LoadSettings;
+use crate::metadata::Metadata;
+use chrono::Local;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const IMAGES_DIR: &str = "images";
+const THUMBNAILS_DIR: &str = "thumbnails";
+const PAGES_DIR: &str = "pages";
+///Profile of the published images, the only one browsers can be relied on to assume
+const WEB_ICC_PROFILE: &str = "sRGB";
+const STYLE: &str = "body{margin:0;padding:1rem;background:#1b1b1b;color:#ddd;font-family:sans-serif}\
+a{color:#ddd}\
+h1{font-weight:normal}\
+.grid{display:grid;grid-template-columns:repeat(auto-fill,minmax(12rem,1fr));gap:.5rem}\
+.grid img{width:100%;aspect-ratio:1;object-fit:cover;display:block}\
+.image{max-width:100%;max-height:80vh;display:block;margin:auto}\
+nav{display:flex;justify-content:space-between;margin:1rem 0}\
+dl{display:grid;grid-template-columns:max-content auto;gap:.25rem 1rem}\
+dt{color:#999}dd{margin:0}";
+
+///Static web gallery of a collection, written by the worker
+#[derive(Debug)]
+pub struct WebGalleryJob {
+    pub paths: Vec<PathBuf>,
+    pub config: WebGalleryConfig,
+    pub settings: LoadSettings,
+    ///Used when no title is configured
+    pub title: String,
+    pub output: PathBuf,
+}
+
+///Image written to the gallery
+struct GalleryEntry {
+    name: String,
+    image: String,
+    thumbnail: String,
+    metadata: Vec<(String, String)>,
+}
+
+impl WebGalleryJob {
+    ///Timestamped directory in the configured one, or in `fallback_dir` when none is set
+    pub fn default_output(config: &WebGalleryConfig, fallback_dir: &Path) -> PathBuf {
+        let dir = if config.dir.is_empty() {
+            fallback_dir.to_path_buf()
+        } else {
+            PathBuf::from(&config.dir)
+        };
+
+        dir.join(format!(
+            "web_gallery_{}",
+            Local::now().format("%Y%m%d_%H%M%S")
+        ))
+    }
+
+    ///Exports the images and writes the pages, calling progress with the index of the image
+    ///about to be exported. Returns the path of the index page.
+    pub fn run(&self, db_repo: &mut DbRepository, progress: impl Fn(usize)) -> Option<PathBuf> {
+        if self.paths.is_empty() {
+            tracing::error!("No images to create a web gallery from");
+            return None;
+        }
+
+        let image_config = self.export_config(
+            IMAGES_DIR,
+            self.config.image_long_edge,
+            self.config.metadata,
+        );
+        let thumbnail_config = self.export_config(
+            THUMBNAILS_DIR,
+            self.config.thumbnail_long_edge,
+            ExportMetadata::StripAll,
+        );
+
+        let mut entries = vec![];
+        for (index, path) in self.paths.iter().enumerate() {
+            progress(index);
+
+            let metadata =
+                Metadata::get_image_metadata(db_repo, &path.to_string_lossy()).unwrap_or_default();
+            let Some(decoded) = export::decode_for_export(path, &self.settings, &metadata, db_repo)
+            else {
+                tracing::error!("{path:?} -> Failure decoding for the web gallery, skipping");
+                continue;
+            };
+
+            //Both sizes are derived from the one decode, which is the slow part on raws
+            let image =
+                export::export_decoded(decoded.clone(), path, index, &image_config, &metadata);
+            let thumbnail =
+                export::export_decoded(decoded, path, index, &thumbnail_config, &metadata);
+            let (Some(image), Some(thumbnail)) = (image, thumbnail) else {
+                tracing::error!("{path:?} -> Failure exporting to the web gallery, skipping");
+                continue;
+            };
+
+            entries.push(GalleryEntry {
+                name: file_name(path),
+                image: file_name(&image),
+                thumbnail: file_name(&thumbnail),
+                metadata: selected_metadata(&metadata, &self.config.metadata_tags),
+            });
+        }
+
+        let title = if self.config.title.is_empty() {
+            &self.title
+        } else {
+            &self.config.title
+        };
+
+        let pages_dir = self.output.join(PAGES_DIR);
+        if let Err(e) = fs::create_dir_all(&pages_dir) {
+            tracing::error!("Failure creating web gallery directory {pages_dir:?} -> {e}");
+            return None;
+        }
+
+        for index in 0..entries.len() {
+            let page_path = pages_dir.join(page_file_name(index));
+            if let Err(e) = fs::write(&page_path, image_page(title, &entries, index)) {
+                tracing::error!("Failure writing web gallery page {page_path:?} -> {e}");
+            }
+        }
+
+        let index_path = self.output.join("index.html");
+        match fs::write(&index_path, index_page(title, &entries)) {
+            Ok(()) => Some(index_path),
+            Err(e) => {
+                tracing::error!("Failure writing web gallery index {index_path:?} -> {e}");
+                None
+            }
+        }
+    }
+
+    fn export_config(&self, dir: &str, long_edge: u32, metadata: ExportMetadata) -> ExportConfig {
+        ExportConfig {
+            dir: self.output.join(dir).to_string_lossy().to_string(),
+            filename_template: "{n}_{stem}".to_string(),
+            format: ExportFormat::Jpeg,
+            quality: self.config.quality,
+            resize: ExportResize::LongEdge(long_edge),
+            sharpen: 0.,
+            icc_profile: WEB_ICC_PROFILE.to_string(),
+            metadata,
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+fn page_file_name(index: usize) -> String {
+    format!("{:04}.html", index + 1)
+}
+
+///Configured tags present in the metadata, in the configured order
+fn selected_metadata(metadata: &HashMap<String, String>, tags: &[String]) -> Vec<(String, String)> {
+    tags.iter()
+        .filter_map(|tag| Some((tag.clone(), metadata.get(tag)?.clone())))
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+///Percent encodes a file name so it can be used in a relative link
+fn encode_url(file_name: &str) -> String {
+    file_name
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn html_document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape_html(title)
+    )
+}
+
+fn index_page(title: &str, entries: &[GalleryEntry]) -> String {
+    let mut body = format!("<h1>{}</h1>\n<div class=\"grid\">\n", escape_html(title));
+
+    for (index, entry) in entries.iter().enumerate() {
+        body.push_str(&format!(
+            "<a href=\"{PAGES_DIR}/{}\"><img src=\"{THUMBNAILS_DIR}/{}\" alt=\"{}\" loading=\"lazy\"></a>\n",
+            page_file_name(index),
+            encode_url(&entry.thumbnail),
+            escape_html(&entry.name)
+        ));
+    }
+
+    body.push_str("</div>\n");
+    html_document(title, &body)
+}
+
+fn image_page(title: &str, entries: &[GalleryEntry], index: usize) -> String {
+    let entry = &entries[index];
+    let link = |target: Option<usize>, text: &str| match target {
+        Some(target) => format!("<a href=\"{}\">{text}</a>", page_file_name(target)),
+        None => format!("<span>{text}</span>"),
+    };
+    let previous = index.checked_sub(1);
+    let next = (index + 1 < entries.len()).then_some(index + 1);
+
+    let mut body = format!(
+        "<nav>{}<a href=\"../index.html\">{}</a>{}</nav>\n\
+         <img class=\"image\" src=\"../{IMAGES_DIR}/{}\" alt=\"{}\">\n<h1>{}</h1>\n<dl>\n",
+        link(previous, "Previous"),
+        escape_html(title),
+        link(next, "Next"),
+        encode_url(&entry.image),
+        escape_html(&entry.name),
+        escape_html(&entry.name)
+    );
+
+    for (tag, value) in &entry.metadata {
+        body.push_str(&format!(
+            "<dt>{}</dt><dd>{}</dd>\n",
+            escape_html(tag),
+            escape_html(value)
+        ));
+    }
+
+    body.push_str("</dl>\n");
+    html_document(&format!("{} - {title}", entry.name), &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str) -> GalleryEntry {
+        GalleryEntry {
+            name: name.to_string(),
+            image: format!("{name}.jpg"),
+            thumbnail: format!("{name}.jpg"),
+            metadata: vec![("Model".to_string(), "X100V <T>".to_string())],
+        }
+    }
+
+    #[test]
+    fn image_pages_link_to_their_neighbours() {
+        let entries = vec![entry("a"), entry("b"), entry("c")];
+
+        let first = image_page("Trip", &entries, 0);
+        assert!(first.contains("<span>Previous</span>"));
+        assert!(first.contains("<a href=\"0002.html\">Next</a>"));
+        assert!(first.contains("src=\"../images/a.jpg\""));
+        assert!(first.contains("<dt>Model</dt><dd>X100V &lt;T&gt;</dd>"));
+
+        let last = image_page("Trip", &entries, 2);
+        assert!(last.contains("<a href=\"0002.html\">Previous</a>"));
+        assert!(last.contains("<span>Next</span>"));
+
+        let index = index_page("Trip", &entries);
+        assert!(index.contains("<a href=\"pages/0003.html\"><img src=\"thumbnails/c.jpg\""));
+    }
+
+    #[test]
+    fn file_names_are_encoded_in_links() {
+        assert_eq!(encode_url("0001_DSCF 01#2.jpg"), "0001_DSCF%2001%232.jpg");
+        assert_eq!(encode_url("été.jpg"), "%C3%A9t%C3%A9.jpg");
+    }
+
+    #[test]
+    fn only_configured_tags_are_listed() {
+        let mut metadata = HashMap::new();
+        metadata.insert("ISO".to_string(), "200".to_string());
+        metadata.insert("Model".to_string(), "X100V".to_string());
+        metadata.insert("GPS Position".to_string(), "somewhere".to_string());
+        let tags = vec![
+            "Model".to_string(),
+            "Lens Model".to_string(),
+            "ISO".to_string(),
+        ];
+
+        assert_eq!(
+            selected_metadata(&metadata, &tags),
+            vec![
+                ("Model".to_string(), "X100V".to_string()),
+                ("ISO".to_string(), "200".to_string())
+            ]
+        );
+    }
+}
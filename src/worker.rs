@@ -2,13 +2,20 @@ use std::path::PathBuf;
 use std::sync::mpsc::{Receiver, Sender, channel};
 use std::thread;
 use eframe::egui::Context;
+use crate::contact_sheet::ContactSheetJob;
 use crate::db::DbRepository;
+use crate::export::ExportJob;
 use crate::metadata::Metadata;
+use crate::quality::QualityWorker;
+use crate::web_gallery::WebGalleryJob;
 
 #[derive(Debug)]
 pub enum Job {
     CacheMetadataForImages(Vec<PathBuf>),
     ClearMovedFiles(Vec<PathBuf>),
+    ExportImages(ExportJob),
+    CreateContactSheet(ContactSheetJob),
+    ExportWebGallery(WebGalleryJob),
 }
 
 pub enum WorkerMessage {
@@ -59,6 +66,7 @@ fn worker_loop(ctx: Context,
         db_repo: DbRepository
     ) {
     let mut db_repo = db_repo.clone();
+    let quality_worker = QualityWorker::new(&db_repo);
     while let Ok(job) = job_rx.recv() {
         match job {
             Job::CacheMetadataForImages(paths) => {
@@ -67,6 +75,7 @@ fn worker_loop(ctx: Context,
                 worker_send_msg(&msg_tx, WorkerMessage::Log(log.to_string()));
 
                 Metadata::cache_metadata_for_images(&mut db_repo, &paths);
+                quality_worker.queue(paths);
 
                 worker_send_msg(&msg_tx, WorkerMessage::Done);
                 tracing::info!("Finished caching metadata for all images");
@@ -76,6 +85,36 @@ fn worker_loop(ctx: Context,
                 let _ = Metadata::clear_moved_files(&mut db_repo, &paths);
                 worker_send_msg(&msg_tx, WorkerMessage::Done);
             }
+            Job::ExportImages(job) => {
+                let total = job.paths.len();
+                let exported = job.run(&mut db_repo, |index| {
+                    worker_send_msg(&msg_tx, WorkerMessage::Log(format!("Exporting image {} of {total}", index + 1)));
+                    ctx.request_repaint();
+                });
+
+                worker_send_msg(&msg_tx, WorkerMessage::Done);
+                tracing::info!("Exported {exported} of {total} images");
+            }
+            Job::CreateContactSheet(job) => {
+                let total = job.paths.len();
+                let written = job.run(&mut db_repo, |index| {
+                    worker_send_msg(&msg_tx, WorkerMessage::Log(format!("Adding image {} of {total} to the contact sheet", index + 1)));
+                    ctx.request_repaint();
+                });
+
+                worker_send_msg(&msg_tx, WorkerMessage::Done);
+                tracing::info!("Contact sheet written to {:?}", written);
+            }
+            Job::ExportWebGallery(job) => {
+                let total = job.paths.len();
+                let index = job.run(&mut db_repo, |index| {
+                    worker_send_msg(&msg_tx, WorkerMessage::Log(format!("Adding image {} of {total} to the web gallery", index + 1)));
+                    ctx.request_repaint();
+                });
+
+                worker_send_msg(&msg_tx, WorkerMessage::Done);
+                tracing::info!("Web gallery written to {:?}", index);
+            }
         }
 
         //Repaint so the latest message, which is usually Done, is received and hides the message popup.
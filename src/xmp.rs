@@ -0,0 +1,40 @@
+use crate::config::{SidecarNaming, XmpWrite};
+use crate::metadata::Metadata;
+use crate::rating;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Sender, channel};
+use std::thread;
+
+///Writes XMP tags with exiftool in the background. Ratings and tags share a single writer so
+///consecutive changes to the same file are written in order.
+#[derive(Clone)]
+pub struct XmpWriter {
+    sidecar_naming: SidecarNaming,
+    tx: Sender<(PathBuf, Vec<String>)>,
+}
+
+impl XmpWriter {
+    pub fn new(sidecar_naming: SidecarNaming) -> XmpWriter {
+        let (tx, rx) = channel::<(PathBuf, Vec<String>)>();
+        thread::spawn(move || {
+            while let Ok((path, assignments)) = rx.recv() {
+                Metadata::write_xmp(&path, &assignments);
+            }
+        });
+
+        XmpWriter { sidecar_naming, tx }
+    }
+
+    ///Queues exiftool tag assignments, as in `-XMP-xmp:Rating=3`, for the image or its sidecar
+    pub fn write(&self, path: &Path, mode: XmpWrite, assignments: Vec<String>) {
+        let xmp_path = match mode {
+            XmpWrite::Sidecar => rating::sidecar_path(path, self.sidecar_naming),
+            XmpWrite::Embedded => path.to_path_buf(),
+            XmpWrite::Off => return,
+        };
+
+        if self.tx.send((xmp_path, assignments)).is_err() {
+            tracing::error!("Failure sending tags to the XMP writer");
+        }
+    }
+}